@@ -2,13 +2,38 @@
 
 #![warn(missing_docs)]
 
-use chrono::{Duration, Local, NaiveDateTime};
+use chrono::{
+    DateTime, Datelike, Duration, Local, Locale, NaiveDate, NaiveDateTime, NaiveTime, Utc, Weekday,
+};
 use clap::ValueEnum;
 use serde::{Deserialize, Serialize};
 
-use std::collections::{hash_map::Entry, HashMap};
+use chrono::format::{Item, StrftimeItems};
+
+use std::collections::{hash_map::Entry, BTreeMap, HashMap, HashSet};
 use std::fmt::Display;
 
+/// The date format used when showing time usages unless another format is specified.
+pub const DEFAULT_DATE_FORMAT: &str = "%-d/%-m/%Y %H:%M";
+
+/// The maximum number of characters of a category name in a text table.
+pub const TABLE_NAME_WIDTH: usize = 20;
+
+/// The category time is moved to when a category is removed with `remove_category_keep_time`.
+pub const UNCATEGORIZED: &str = "uncategorized";
+
+/// The fill colors given to categories in DOT exports in the alphabetical order of categories.
+const DOT_COLORS: [&str; 8] = [
+    "lightblue",
+    "lightpink",
+    "palegreen",
+    "khaki",
+    "plum",
+    "lightsalmon",
+    "lightcyan",
+    "wheat",
+];
+
 /// An error with a message intended to be shown to the user.
 #[derive(Debug, PartialEq, Eq)]
 pub enum Error {
@@ -22,6 +47,55 @@ pub enum Error {
     NotRecordingTime,
     /// Caused by time recording already been started.
     AlreadyRecordingTime,
+    /// Caused by trying to parse a duration from an invalid string.
+    InvalidDuration(String),
+    /// Caused by there being no gap after a `TimeUsage` into which time could be inserted.
+    NoGapAfterTimeUsage(usize),
+    /// Caused by the `TimeBook` changing when serialized and deserialized.
+    RoundTripMismatch,
+    /// Caused by an invalid date format string.
+    InvalidDateFormat(String),
+    /// Caused by data to be imported not being in the expected format.
+    InvalidImport(String),
+    /// Caused by an entry with the specified index in imported data being invalid.
+    InvalidImportEntry(usize, String),
+    /// Caused by there being no time usages when one is required.
+    NoTimeUsages,
+    /// Caused by trying to add a category when the maximum number of categories exists.
+    CategoryLimitReached(usize),
+    /// Caused by a missing description when descriptions are required.
+    DescriptionRequired,
+    /// Caused by the time usages with the specified ids not overlapping.
+    UsagesDontOverlap(usize, usize),
+    /// Caused by a failure while writing to a database.
+    Database(String),
+    /// Caused by a change that would make the time usage with the specified id stop before it
+    /// starts.
+    StopBeforeStart(usize),
+    /// Caused by a duration being longer than allowed.
+    DurationTooLong(Duration),
+    /// Caused by trying to pause time recording that is already paused.
+    AlreadyPaused,
+    /// Caused by trying to resume time recording that isn't paused.
+    NotPaused,
+    /// Caused by importing an export with a schema that isn't supported.
+    UnsupportedSchema(String),
+    /// Caused by trying to remove a category that time is being recorded to.
+    CategoryInUse(String),
+    /// Caused by time being recorded to a category that doesn't exist.
+    OrphanedRecording(String),
+    /// Caused by trying to add a tag that is empty.
+    EmptyTag,
+    /// Caused by trying to stop time recording before the moment it was started.
+    StopBeforeRecordingStart,
+    /// Caused by a duration that isn't longer than zero.
+    DurationNotPositive,
+    /// Caused by trying to chain a recording from a tentative one.
+    TentativeRecording,
+    /// Caused by trying to export to a file that already has data in it.
+    ExportTargetNotEmpty(String),
+    /// Caused by a failure while writing an export.
+    ExportFailed(String),
 }
 
 impl Display for Error {
@@ -34,6 +108,68 @@ impl Display for Error {
             }
             Self::NotRecordingTime => write!(f, "Time is not being recorded currently."),
             Self::AlreadyRecordingTime => write!(f, "Time is already being recorded."),
+            Self::InvalidDuration(s) => write!(
+                f,
+                "Invalid duration '{}'. Use a format such as 1h30m, 45m or 90s.",
+                s
+            ),
+            Self::NoGapAfterTimeUsage(id) => {
+                write!(
+                    f,
+                    "There is no gap after the Time Usage with the id {}.",
+                    id
+                )
+            }
+            Self::RoundTripMismatch => write!(
+                f,
+                "The data changes when saved and loaded again. Some data might be lost."
+            ),
+            Self::InvalidDateFormat(s) => write!(f, "Invalid date format '{}'.", s),
+            Self::InvalidImport(e) => write!(f, "Invalid data to import: {}.", e),
+            Self::InvalidImportEntry(i, e) => write!(f, "Invalid entry {} to import: {}.", i, e),
+            Self::NoTimeUsages => write!(f, "No time has been recorded yet."),
+            Self::CategoryLimitReached(max) => {
+                write!(f, "The maximum of {} categories has been reached.", max)
+            }
+            Self::DescriptionRequired => write!(f, "A description is required."),
+            Self::UsagesDontOverlap(a, b) => {
+                write!(f, "Time Usages with the ids {} and {} don't overlap.", a, b)
+            }
+            Self::Database(e) => write!(f, "Database error: {}.", e),
+            Self::StopBeforeStart(id) => {
+                write!(f, "Time Usage with the id {} would stop before it starts.", id)
+            }
+            Self::DurationTooLong(max) => write!(
+                f,
+                "The duration can be at most {}.",
+                format_duration(*max, Duration::minutes(1), RoundingMode::Nearest)
+            ),
+            Self::AlreadyPaused => write!(f, "Time recording is already paused."),
+            Self::NotPaused => write!(f, "Time recording isn't paused."),
+            Self::UnsupportedSchema(schema) => {
+                write!(f, "Unsupported export schema '{}'.", schema)
+            }
+            Self::CategoryInUse(cat) => {
+                write!(f, "Time is currently being recorded to category {}.", cat)
+            }
+            Self::OrphanedRecording(cat) => write!(
+                f,
+                "Time is being recorded to category {} which doesn't exist. Cancel the recording to fix this.",
+                cat
+            ),
+            Self::EmptyTag => write!(f, "A tag can't be empty."),
+            Self::StopBeforeRecordingStart => {
+                write!(f, "Time recording can't stop before it was started.")
+            }
+            Self::DurationNotPositive => write!(f, "The duration must be longer than zero."),
+            Self::TentativeRecording => write!(
+                f,
+                "The current recording is tentative. Stop it before starting another one."
+            ),
+            Self::ExportTargetNotEmpty(path) => {
+                write!(f, "File {} isn't empty. Export to a new file.", path)
+            }
+            Self::ExportFailed(e) => write!(f, "Could not write the export: {}.", e),
         }
     }
 }
@@ -45,7 +181,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 /// Defines a time span when time was spent on doing something.
 /// `TimeUsage`s are sorted by their starting time.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeUsage {
     /// The starting point of the `TimeUsage`.
     pub start: NaiveDateTime,
@@ -53,6 +189,28 @@ pub struct TimeUsage {
     pub stop: NaiveDateTime,
     /// An optional description of the `TimeUsage`.
     pub desc: Option<String>,
+    /// Notes of the subtasks done during the `TimeUsage`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subtasks: Vec<String>,
+    /// Tags of the `TimeUsage`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// An optional reference such as a URL to a ticket related to the `TimeUsage`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+}
+
+/// Optional details given to a `TimeUsage` when it's created.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UsageDetails {
+    /// An optional description of the `TimeUsage`.
+    pub desc: Option<String>,
+    /// Notes of the subtasks done during the `TimeUsage`.
+    pub subtasks: Vec<String>,
+    /// Tags of the `TimeUsage`.
+    pub tags: Vec<String>,
+    /// An optional reference such as a URL related to the `TimeUsage`.
+    pub reference: Option<String>,
 }
 
 impl Ord for TimeUsage {
@@ -63,7 +221,7 @@ impl Ord for TimeUsage {
 
 impl PartialOrd for TimeUsage {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.start.cmp(&other.start))
+        Some(self.cmp(other))
     }
 }
 
@@ -75,13 +233,70 @@ impl PartialEq for TimeUsage {
     }
 }
 
+/// A time recording that was cancelled instead of being stopped.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CancelledRecording {
+    /// The category the time was being recorded for.
+    pub category: String,
+    /// The starting point of the cancelled recording.
+    pub start: NaiveDateTime,
+    /// The moment the recording was cancelled.
+    pub cancelled_at: NaiveDateTime,
+    /// An optional reason for the cancellation.
+    pub reason: Option<String>,
+}
+
+/// The differences in `TimeUsage`s between two `TimeBook`s. Usages are identified by their
+/// category and starting time.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BookDiff {
+    /// Usages that exist only in the other book.
+    pub added: Vec<(String, NaiveDateTime)>,
+    /// Usages that exist only in this book.
+    pub removed: Vec<(String, NaiveDateTime)>,
+    /// Usages that exist in both books but have different contents.
+    pub modified: Vec<(String, NaiveDateTime)>,
+}
+
+impl BookDiff {
+    /// Returns true if there are no differences.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+}
+
+/// A single entry of time spent imported from other tools.
+#[derive(Debug, Deserialize)]
+struct ImportEntry {
+    category: String,
+    start: NaiveDateTime,
+    stop: NaiveDateTime,
+    #[serde(default)]
+    desc: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+/// The time planned to be spent on a category each week.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Goal {
+    #[serde(with = "duration_human")]
+    weekly: Duration,
+}
+
+/// The schema of the exports created by `TimeBook::to_export_json`.
+pub const EXPORT_SCHEMA: &str = "vihr-export/1";
+
 /// Specifies the time span from which to show records.
-#[derive(Clone, Copy, Debug, ValueEnum)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum ShownTimeSpan {
     /// Show all records.
     All,
     /// Show records from the past year.
     Year,
+    /// Show records from the current calendar year.
+    YearToDate,
     /// Show records from the past month.
     Month,
     /// Show records from the past week.
@@ -92,13 +307,228 @@ pub enum ShownTimeSpan {
     Today,
 }
 
+/// A summary of the time spent on categories within a time span.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Summary {
+    /// The time span of the summary.
+    pub span: ShownTimeSpan,
+    /// The time spent on each category. Pinned categories are first in the order they were pinned
+    /// and the rest are sorted alphabetically.
+    pub categories: Vec<CategorySummary>,
+    /// The total time spent on all categories of the summary.
+    #[serde(with = "duration_seconds")]
+    pub total: Duration,
+}
+
+/// The time spent on a single category within a `Summary`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CategorySummary {
+    /// The name of the category.
+    pub category: String,
+    /// The time spent on the category.
+    #[serde(with = "duration_seconds")]
+    pub spent: Duration,
+}
+
+impl Summary {
+    /// Keeps only the categories for which the predicate returns true and updates the total.
+    pub fn retain<F: FnMut(&CategorySummary) -> bool>(&mut self, f: F) {
+        self.categories.retain(f);
+        self.total = self
+            .categories
+            .iter()
+            .fold(Duration::zero(), |total, c| total + c.spent);
+    }
+
+    /// Formats the summary as a bordered plain text table with a row for every category and the
+    /// total. Category names longer than `TABLE_NAME_WIDTH` characters are truncated with an
+    /// ellipsis. Times are rounded to the nearest minute.
+    pub fn to_text_table(&self) -> String {
+        let time = |d| format_duration(d, Duration::minutes(1), RoundingMode::Nearest);
+        let name = |n: &str| {
+            if n.chars().count() > TABLE_NAME_WIDTH {
+                let mut truncated: String = n.chars().take(TABLE_NAME_WIDTH - 1).collect();
+                truncated.push('…');
+                truncated
+            } else {
+                n.to_string()
+            }
+        };
+
+        let rows: Vec<(String, String)> = self
+            .categories
+            .iter()
+            .map(|c| (name(&c.category), time(c.spent)))
+            .collect();
+        let total = ("Total".to_string(), time(self.total));
+
+        let name_width = rows
+            .iter()
+            .chain([&total])
+            .map(|(n, _)| n.chars().count())
+            .max()
+            .unwrap()
+            .max("Category".len());
+        let time_width = rows
+            .iter()
+            .chain([&total])
+            .map(|(_, t)| t.len())
+            .max()
+            .unwrap()
+            .max("Time".len());
+
+        let border = format!(
+            "+{}+{}+\n",
+            "-".repeat(name_width + 2),
+            "-".repeat(time_width + 2)
+        );
+        let row = |n: &str, t: &str| {
+            let padding = name_width - n.chars().count();
+            format!(
+                "| {}{} | {:>tw$} |\n",
+                n,
+                " ".repeat(padding),
+                t,
+                tw = time_width
+            )
+        };
+
+        let mut table = border.clone();
+        table.push_str(&row("Category", "Time"));
+        table.push_str(&border);
+        for (n, t) in &rows {
+            table.push_str(&row(n, t));
+        }
+        table.push_str(&border);
+        table.push_str(&row(&total.0, &total.1));
+        table.push_str(&border);
+        table
+    }
+}
+
+/// Specifies how a `Duration` is rounded to a granularity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum RoundingMode {
+    /// Round to the nearest multiple. Halfway values are rounded up.
+    Nearest,
+    /// Round up to the next multiple.
+    Up,
+    /// Round down to the previous multiple.
+    Down,
+}
+
+/// Specifies the length of the periods time spent is grouped into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum BucketSize {
+    /// A calendar day.
+    Day,
+    /// An ISO week starting on Monday.
+    Week,
+    /// A calendar month.
+    Month,
+}
+
+/// A start or a stop of recording time reconstructed from a `TimeUsage`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimelineEvent {
+    /// Recording time for the category started.
+    Start {
+        /// The category of the recording.
+        category: String,
+        /// The moment the recording started.
+        at: NaiveDateTime,
+    },
+    /// Recording time for the category stopped.
+    Stop {
+        /// The category of the recording.
+        category: String,
+        /// The moment the recording stopped.
+        at: NaiveDateTime,
+    },
+}
+
+impl TimelineEvent {
+    /// Returns the moment of the event.
+    pub fn at(&self) -> NaiveDateTime {
+        match self {
+            Self::Start { at, .. } | Self::Stop { at, .. } => *at,
+        }
+    }
+}
+
+/// Specifies how the descriptions of coalesced time usages are combined.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum DescMerge {
+    /// Join all descriptions that aren't empty with `; `.
+    Join,
+    /// Keep only the description of the first usage.
+    First,
+    /// Join the unique descriptions that aren't empty with `; `.
+    #[default]
+    Unique,
+}
+
+/// Specifies how the tags of a `TimeUsage` are matched against a set of tags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum TagMatch {
+    /// The usage must have all of the tags.
+    All,
+    /// The usage must have at least one of the tags.
+    Any,
+}
+
+/// Specifies how two overlapping `TimeUsage`s are made to not overlap.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum OverlapStrategy {
+    /// Move the stop of the usage starting first to the start of the other one.
+    TrimFirst,
+    /// Move the start of the usage starting second to the stop of the other one.
+    TrimSecond,
+    /// Replace both usages with a single usage covering both of them.
+    Merge,
+}
+
 /// Keeps track of all `TimeUsage`s and their associated categories as well as the the current
 /// task being done.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TimeBook {
     current_cat: Option<String>,
     current_cat_start: Option<NaiveDateTime>,
+    #[serde(default)]
+    current_tags: Vec<String>,
+    #[serde(default)]
+    current_pauses: Vec<(NaiveDateTime, NaiveDateTime)>,
+    #[serde(default)]
+    paused_at: Option<NaiveDateTime>,
+    #[serde(default)]
+    current_tentative: bool,
     time_map: HashMap<String, Vec<TimeUsage>>,
+    #[serde(default)]
+    cancelled: Vec<CancelledRecording>,
+    #[serde(default)]
+    billable_categories: HashSet<String>,
+    #[serde(default)]
+    favorites: Vec<String>,
+    #[serde(default)]
+    description_template: Option<String>,
+    #[serde(default)]
+    goals: HashMap<String, Goal>,
+    #[serde(skip)]
+    fixed_now: Option<NaiveDateTime>,
+    #[serde(skip, default = "Duration::zero")]
+    now_offset: Duration,
+    #[serde(skip)]
+    precise_spans: bool,
+    #[serde(skip)]
+    max_categories: Option<usize>,
+    #[serde(skip)]
+    require_description: bool,
+    #[serde(skip)]
+    restart_grace: Option<Duration>,
+    #[serde(skip)]
+    locale: Option<Locale>,
+    #[serde(skip, default = "default_week_start")]
+    week_start: Weekday,
 }
 
 impl Default for TimeBook {
@@ -107,59 +537,310 @@ impl Default for TimeBook {
         Self {
             current_cat: None,
             current_cat_start: None,
+            current_tags: Vec::new(),
+            current_pauses: Vec::new(),
+            paused_at: None,
+            current_tentative: false,
             time_map: HashMap::new(),
+            cancelled: Vec::new(),
+            billable_categories: HashSet::new(),
+            favorites: Vec::new(),
+            description_template: None,
+            goals: HashMap::new(),
+            fixed_now: None,
+            now_offset: Duration::zero(),
+            precise_spans: false,
+            max_categories: None,
+            require_description: false,
+            restart_grace: None,
+            locale: None,
+            week_start: default_week_start(),
         }
     }
 }
 
+/// Returns the day weeks start on unless another day is set.
+fn default_week_start() -> Weekday {
+    Weekday::Mon
+}
+
 impl TimeBook {
+    /// Fixes the moment the `TimeBook` considers to be the current one. Passing `None` makes it
+    /// use the system clock again. Mostly useful for testing.
+    pub fn set_now(&mut self, now: Option<NaiveDateTime>) {
+        self.fixed_now = now;
+    }
+
+    /// Shifts the moment the `TimeBook` considers to be the current one by the offset. Useful for
+    /// correcting a wrong system clock. The offset is also applied to a fixed moment.
+    pub fn set_now_offset(&mut self, offset: Duration) {
+        self.now_offset = offset;
+    }
+
+    /// Sets whether the `Year`, `Month` and `Week` time spans are measured precisely from the
+    /// current moment instead of whole days. By default a usage is in a week if it started on any
+    /// time of the day seven days ago. When precise, it must have started at most 7 * 24 hours ago.
+    pub fn set_precise_spans(&mut self, precise: bool) {
+        self.precise_spans = precise;
+    }
+
+    /// Sets the maximum number of categories. Passing `None` allows any number of categories,
+    /// which is the default. Existing categories are kept even if there are more of them.
+    pub fn set_max_categories(&mut self, max: Option<usize>) {
+        self.max_categories = max;
+    }
+
+    /// Sets whether new time usages must have a description that isn't empty or whitespace.
+    /// Descriptions aren't required by default.
+    pub fn set_require_description(&mut self, require: bool) {
+        self.require_description = require;
+    }
+
+    /// Sets the locale used for month and weekday names in logs. Passing `None` uses English,
+    /// which is the default.
+    pub fn set_locale(&mut self, locale: Option<Locale>) {
+        self.locale = locale;
+    }
+
+    /// Returns the locale used for month and weekday names or `None` if English is used.
+    pub fn locale(&self) -> Option<Locale> {
+        self.locale
+    }
+
+    /// Sets the day weeks start on in calendars. Weeks start on Monday by default.
+    pub fn set_week_start(&mut self, week_start: Weekday) {
+        self.week_start = week_start;
+    }
+
+    /// Returns the day weeks start on in calendars.
+    pub fn week_start(&self) -> Weekday {
+        self.week_start
+    }
+
+    /// Sets the grace period within which starting a category again reopens its last time usage
+    /// instead of creating a new one. See `start_tagged` for details. Passing `None` disables
+    /// reopening, which is the default.
+    pub fn set_restart_grace(&mut self, grace: Option<Duration>) {
+        self.restart_grace = grace;
+    }
+
+    /// Sets the template used as the description of new time usages that are given none. The
+    /// placeholders `{category}`, `{date}`, `{start}` and `{stop}` are replaced with the values
+    /// of the usage. Passing `None` removes the template.
+    pub fn set_description_template(&mut self, template: Option<String>) {
+        self.description_template = template;
+    }
+
+    /// Returns the moment the `TimeBook` considers to be the current one.
+    pub fn now(&self) -> NaiveDateTime {
+        self.fixed_now.unwrap_or_else(|| Local::now().naive_local()) + self.now_offset
+    }
+
+    /// Sorts the time usages of every category by their start. The usages are always kept sorted,
+    /// but a hand-edited save file may not be. Returns true if any usages had to be reordered.
+    pub fn ensure_sorted(&mut self) -> bool {
+        let mut reordered = false;
+
+        for usages in self.time_map.values_mut() {
+            if !usages.windows(2).all(|w| w[0] <= w[1]) {
+                usages.sort();
+                reordered = true;
+            }
+        }
+
+        reordered
+    }
+
     /// Starts recording time for a category. If start_time is not specified starts recording from
     /// the current moment.
     /// Returns an `Error` if the category doesn't exist or if time is already being recorded.
     pub fn start(&mut self, category: String, start_time: Option<NaiveDateTime>) -> Result<()> {
+        self.start_tagged(category, start_time, Vec::new())
+    }
+
+    /// Starts recording time like `start` but with tags that are given to the `TimeUsage` created
+    /// when the recording is stopped.
+    ///
+    /// If a restart grace period is set and the recording starts at most the grace period after
+    /// the stop of the category's time usage that stopped last, but not before that stop, the
+    /// usage is removed and the recording continues from its start instead. Its tags are kept
+    /// while its description, subtasks and reference are replaced by the ones given on stop.
+    ///
+    /// Returns an `Error` if the category doesn't exist or if time is already being recorded.
+    pub fn start_tagged(
+        &mut self,
+        category: String,
+        start_time: Option<NaiveDateTime>,
+        mut tags: Vec<String>,
+    ) -> Result<()> {
         if self.current_cat_start.is_some() {
             return Err(Error::AlreadyRecordingTime);
         }
 
-        if self.time_map.contains_key(&category) {
-            if let Some(datetime) = start_time {
-                self.current_cat_start = Some(datetime);
-            } else {
-                self.current_cat_start = Some(Local::now().naive_local());
+        let now = self.now();
+        if let Some(usages) = self.time_map.get_mut(&category) {
+            let mut start_time = start_time.unwrap_or(now);
+
+            let last = usages
+                .iter()
+                .enumerate()
+                .max_by_key(|(_, usage)| usage.stop)
+                .map(|(i, _)| i);
+            if let (Some(grace), Some(i)) = (self.restart_grace, last) {
+                let since_stop = start_time - usages[i].stop;
+                if since_stop >= Duration::zero() && since_stop <= grace {
+                    let reopened = usages.remove(i);
+                    start_time = reopened.start;
+                    for tag in reopened.tags.into_iter().rev() {
+                        if !tags.contains(&tag) {
+                            tags.insert(0, tag);
+                        }
+                    }
+                }
             }
+
+            self.current_cat_start = Some(start_time);
             self.current_cat = Some(category);
+            self.current_tags = tags;
             Ok(())
         } else {
             Err(Error::CategoryDoesntExist(category))
         }
     }
 
+    /// Starts recording time for a category so that there is no untracked time since the last
+    /// activity. If time is being recorded, the recording is stopped at the current moment and the
+    /// new one starts from there. Otherwise the recording starts from the latest stop of all time
+    /// usages. Returns the untracked time that the chaining filled.
+    /// Returns an `Error` if the category doesn't exist, if there is nothing to chain from or if
+    /// the current recording is tentative, as committing it needs a confirmation.
+    pub fn start_chained(&mut self, category: String, tags: Vec<String>) -> Result<Duration> {
+        if !self.time_map.contains_key(&category) {
+            return Err(Error::CategoryDoesntExist(category));
+        }
+
+        if self.current_tentative {
+            return Err(Error::TentativeRecording);
+        }
+
+        if self.current_cat_start.is_some() {
+            let now = self.now();
+            self.stop(Some(now), None)?;
+            self.start_tagged(category, Some(now), tags)?;
+            return Ok(Duration::zero());
+        }
+
+        let last_stop = self.last_activity().ok_or(Error::NoTimeUsages)?;
+        self.start_tagged(category, Some(last_stop), tags)?;
+        Ok((self.now() - last_stop).max(Duration::zero()))
+    }
+
     /// Stops recording time and adds the new `TimeUsage` to the category. If stop_time is not
     /// specified the recording will be stopped at the current moment.
     /// Returns an `Error` if time recording hasn't been started.
     pub fn stop(
         &mut self,
-        mut stop_time: Option<NaiveDateTime>,
+        stop_time: Option<NaiveDateTime>,
         description: Option<String>,
+    ) -> Result<()> {
+        self.stop_with(
+            stop_time,
+            UsageDetails {
+                desc: description,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Stops recording time like `stop` but allows specifying all details of the new `TimeUsage`.
+    /// The tags given when starting the recording are added to the tags of the details.
+    /// Returns an `Error` if time recording hasn't been started.
+    pub fn stop_with(
+        &mut self,
+        mut stop_time: Option<NaiveDateTime>,
+        mut details: UsageDetails,
     ) -> Result<()> {
         if stop_time.is_none() {
-            stop_time = Some(Local::now().naive_local());
+            stop_time = Some(self.now());
         }
 
         if let Some(start_time) = self.current_cat_start {
+            if stop_time.unwrap() < start_time {
+                return Err(Error::StopBeforeRecordingStart);
+            }
+
             // If start_time is Some then category is as well.
             let category = self.current_cat.clone().unwrap();
 
-            self.add_time_usage(&category, start_time, stop_time.unwrap(), description)?;
+            let mut tags = self.current_tags.clone();
+            for tag in details.tags {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            details.tags = tags;
+
+            // Paused time isn't spent, so every part between the pauses is a usage of its own.
+            let mut parts = Vec::new();
+            let mut part_start = start_time;
+            for (paused, resumed) in &self.current_pauses {
+                parts.push((part_start, *paused));
+                part_start = *resumed;
+            }
+            parts.push((part_start, self.paused_at.unwrap_or(stop_time.unwrap())));
+            // Nothing after the stop time is spent even if the recording was paused later.
+            for (_, stop) in parts.iter_mut() {
+                *stop = (*stop).min(stop_time.unwrap());
+            }
+            // Only parts left empty by pausing are dropped. A recording without pauses is always
+            // kept.
+            if !self.current_pauses.is_empty() || self.paused_at.is_some() {
+                parts.retain(|(start, stop)| stop > start);
+            }
+
+            for (start, stop) in parts {
+                self.add_time_usage_with(&category, start, stop, details.clone())?;
+            }
 
             self.current_cat = None;
             self.current_cat_start = None;
+            self.current_tags.clear();
+            self.current_pauses.clear();
+            self.paused_at = None;
+            self.current_tentative = false;
             Ok(())
         } else {
             Err(Error::NotRecordingTime)
         }
     }
 
+    /// Cancels time recording but keeps a record of the cancelled recording with an optional
+    /// reason. The recording doesn't affect the time spent on the category.
+    /// Returns an `Error` if time recording hasn't been started.
+    pub fn cancel_logged(&mut self, reason: Option<String>) -> Result<()> {
+        let (category, start) = self.status()?;
+        let cancelled = CancelledRecording {
+            category: category.to_string(),
+            start,
+            cancelled_at: self.now(),
+            reason,
+        };
+
+        self.cancel()?;
+        self.cancelled.push(cancelled);
+        Ok(())
+    }
+
+    /// Returns all logged cancelled recordings. The oldest cancellation is first.
+    pub fn cancelled(&self) -> &[CancelledRecording] {
+        &self.cancelled
+    }
+
+    /// Returns the most recently logged cancelled recording or `None` if there are none.
+    pub fn last_cancelled(&self) -> Option<&CancelledRecording> {
+        self.cancelled.last()
+    }
+
     /// Returns the current category that is being recorded and the start time for that recording.
     /// Returns an `Error` if time recording hasn't been started.
     pub fn status(&self) -> Result<(&str, NaiveDateTime)> {
@@ -173,12 +854,175 @@ impl TimeBook {
         }
     }
 
+    /// Checks that the category time is being recorded to exists. It can only be missing if the
+    /// saved data was edited or corrupted.
+    /// Returns an `Error` if time is being recorded to a category that doesn't exist.
+    pub fn validate_recording_state(&self) -> Result<()> {
+        match &self.current_cat {
+            Some(cat) if !self.time_map.contains_key(cat) => {
+                Err(Error::OrphanedRecording(cat.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the time recorded since the current recording started excluding pauses.
+    /// Returns an `Error` if time recording hasn't been started.
+    pub fn elapsed(&self) -> Result<Duration> {
+        self.elapsed_at(self.now())
+    }
+
+    /// Returns the time the current recording has recorded by the moment excluding pauses.
+    /// Returns an `Error` if time recording hasn't been started.
+    pub fn elapsed_at(&self, at: NaiveDateTime) -> Result<Duration> {
+        let (_, start) = self.status()?;
+        Ok(self.recorded_between(start, at))
+    }
+
+    /// Checks whether the current recording has lasted longer than the threshold, which usually
+    /// means that it was forgotten or the machine crashed. Returns a suggested stop time for a
+    /// stale recording, which is its start plus the average length of the category's time usages
+    /// but at most the threshold. Without earlier usages the threshold is used. Returns `None` if
+    /// the recording isn't stale.
+    /// Returns an `Error` if time recording hasn't been started or if the category it's recorded
+    /// to doesn't exist.
+    pub fn is_recording_stale(&self, threshold: Duration) -> Result<Option<NaiveDateTime>> {
+        let (category, start) = self.status()?;
+        if self.now() - start <= threshold {
+            return Ok(None);
+        }
+
+        let usages = self
+            .time_map
+            .get(category)
+            .ok_or_else(|| Error::OrphanedRecording(category.to_string()))?;
+        let length = if usages.is_empty() {
+            threshold
+        } else {
+            let total = usages
+                .iter()
+                .fold(Duration::zero(), |total, u| total + (u.stop - u.start));
+            (total / usages.len() as i32).min(threshold)
+        };
+
+        Ok(Some(start + length))
+    }
+
+    /// Sets whether the current recording is tentative, meaning that it should be confirmed
+    /// before it's stopped. Stopping a tentative recording works like stopping any other, so
+    /// asking for the confirmation is left to the user of the `TimeBook`.
+    /// Returns an `Error` if time recording hasn't been started.
+    pub fn set_tentative(&mut self, tentative: bool) -> Result<()> {
+        self.status()?;
+        self.current_tentative = tentative;
+        Ok(())
+    }
+
+    /// Returns whether the current recording is tentative. False if time isn't being recorded.
+    pub fn is_tentative(&self) -> bool {
+        self.current_tentative
+    }
+
+    /// Pauses time recording at the current moment. The time the recording is paused isn't
+    /// included in the time usages created when the recording is stopped.
+    /// Returns an `Error` if time recording hasn't been started or is already paused.
+    pub fn pause(&mut self) -> Result<()> {
+        self.status()?;
+        if self.paused_at.is_some() {
+            return Err(Error::AlreadyPaused);
+        }
+
+        self.paused_at = Some(self.now());
+        Ok(())
+    }
+
+    /// Resumes paused time recording at the current moment.
+    /// Returns an `Error` if time recording hasn't been started or isn't paused.
+    pub fn resume(&mut self) -> Result<()> {
+        self.status()?;
+        let paused_at = self.paused_at.take().ok_or(Error::NotPaused)?;
+
+        self.current_pauses.push((paused_at, self.now()));
+        Ok(())
+    }
+
+    /// Returns whether time recording is currently paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Returns the time recorded since the recording was last resumed or since it started if it
+    /// hasn't been paused. Returns zero while paused.
+    /// Returns an `Error` if time recording hasn't been started.
+    pub fn time_since_last_pause(&self) -> Result<Duration> {
+        let (_, start) = self.status()?;
+        if self.paused_at.is_some() {
+            return Ok(Duration::zero());
+        }
+
+        let since = self
+            .current_pauses
+            .last()
+            .map_or(start, |(_, resumed)| *resumed);
+        Ok(self.now() - since)
+    }
+
+    /// Returns the first moment at the daily cutoff time after the current recording started if
+    /// that moment has already passed. The recording should then be stopped at that moment as it
+    /// was most likely forgotten. Returns `None` if time isn't being recorded or no cutoff has
+    /// passed since the recording started.
+    pub fn auto_stop_at(&self, cutoff: NaiveTime) -> Option<NaiveDateTime> {
+        let (_, start) = self.status().ok()?;
+
+        let mut stop = start.date().and_time(cutoff);
+        if stop <= start {
+            stop += Duration::days(1);
+        }
+        (stop <= self.now()).then_some(stop)
+    }
+
+    /// Returns the time spent today on the category time is being recorded to including the
+    /// current recording. Only the part of the recording after midnight is counted and pauses
+    /// aren't counted.
+    /// Returns an `Error` if time recording hasn't been started.
+    pub fn current_category_today(&self) -> Result<Duration> {
+        let (cat, _) = self.status()?;
+        let midnight = self.now().date().and_hms(0, 0, 0);
+
+        Ok(self
+            .time_spent(cat, ShownTimeSpan::Today)
+            .unwrap_or_else(|_| Duration::zero())
+            + self.recorded_between(midnight, self.now()))
+    }
+
+    /// Returns the time the current recording has recorded between the moments excluding
+    /// pauses. Returns zero if time isn't being recorded.
+    fn recorded_between(&self, from: NaiveDateTime, until: NaiveDateTime) -> Duration {
+        let Some(start) = self.current_cat_start else {
+            return Duration::zero();
+        };
+
+        let pauses = self
+            .current_pauses
+            .iter()
+            .copied()
+            .chain(self.paused_at.map(|paused| (paused, until)));
+        let paused = pauses.fold(Duration::zero(), |acc, (paused, resumed)| {
+            acc + (resumed.min(until) - paused.max(from)).max(Duration::zero())
+        });
+        (until - start.max(from) - paused).max(Duration::zero())
+    }
+
     /// Cancels time recording.
     /// Returns an `Error` if time recording hasn't been started.
     pub fn cancel(&mut self) -> Result<()> {
         if self.current_cat_start.is_some() {
             self.current_cat = None;
             self.current_cat_start = None;
+            self.current_tags.clear();
+            self.current_pauses.clear();
+            self.paused_at = None;
+            self.current_tentative = false;
             Ok(())
         } else {
             Err(Error::NotRecordingTime)
@@ -186,8 +1030,15 @@ impl TimeBook {
     }
 
     /// Adds a new category.
-    /// Returns an `Error` if the category already exists.
+    /// Returns an `Error` if the category already exists or the maximum number of categories
+    /// has been reached.
     pub fn add_category(&mut self, category: String) -> Result<()> {
+        if let Some(max) = self.max_categories {
+            if self.time_map.len() >= max && !self.time_map.contains_key(&category) {
+                return Err(Error::CategoryLimitReached(max));
+            }
+        }
+
         // Checking isn't actually necessary, but I consider it to be useful feedback to the user
         if let Entry::Vacant(entry) = self.time_map.entry(category.clone()) {
             entry.insert(Vec::new());
@@ -197,606 +1048,8335 @@ impl TimeBook {
         }
     }
 
+    /// Adds multiple categories. Categories that already exist are skipped without affecting the
+    /// rest. Returns the result of adding each category in the same order as the names.
+    pub fn add_categories(&mut self, names: &[String]) -> Vec<Result<()>> {
+        names
+            .iter()
+            .map(|name| self.add_category(name.clone()))
+            .collect()
+    }
+
     /// Removes a category.
-    /// Returns an `Error` if the category doesn't exist.
+    /// Returns an `Error` if the category doesn't exist or if time is being recorded to it.
     pub fn remove_category(&mut self, category: &str) -> Result<()> {
+        if self.current_cat.as_deref() == Some(category) {
+            return Err(Error::CategoryInUse(category.to_string()));
+        }
+
         // Checking isn't actually necessary, but I consider it to be useful feedback to the user
         if self.time_map.contains_key(category) {
             self.time_map.remove(category);
+            self.billable_categories.remove(category);
+            self.favorites.retain(|c| c != category);
+            self.goals.remove(category);
             Ok(())
         } else {
             Err(Error::CategoryDoesntExist(category.to_string()))
         }
     }
 
-    /// Returns all categories.
-    pub fn categories(&self) -> Vec<&String> {
-        self.time_map.keys().collect()
-    }
+    /// Moves all time usages of a category into another category and then removes the category.
+    /// Reassigning a category into itself does nothing.
+    /// Returns an `Error` if either of the categories doesn't exist or if time is being recorded
+    /// to the removed category.
+    pub fn remove_category_reassign(&mut self, category: &str, into: &str) -> Result<()> {
+        if !self.time_map.contains_key(into) {
+            return Err(Error::CategoryDoesntExist(into.to_string()));
+        }
 
-    /// Creates a new `TimeUsage` and adds it to the `TimeBook` in the specified category.
-    /// Returns an `Error` if the category doesn't exist.
-    pub fn add_time_usage(
-        &mut self,
-        category: &str,
-        start_time: NaiveDateTime,
-        stop_time: NaiveDateTime,
-        desc: Option<String>,
-    ) -> Result<()> {
-        if let Some(usages) = self.time_map.get_mut(category) {
-            usages.push(TimeUsage {
-                start: start_time,
-                stop: stop_time,
-                desc,
-            });
-            usages.sort();
-            Ok(())
-        } else {
-            Err(Error::CategoryDoesntExist(category.to_string()))
+        if category == into {
+            return Ok(());
+        }
+
+        if self.current_cat.as_deref() == Some(category) {
+            return Err(Error::CategoryInUse(category.to_string()));
         }
+
+        let usages = self
+            .time_map
+            .remove(category)
+            .ok_or_else(|| Error::CategoryDoesntExist(category.to_string()))?;
+        self.billable_categories.remove(category);
+        self.favorites.retain(|c| c != category);
+        self.goals.remove(category);
+
+        // Checked above so unwrap is ok.
+        let target = self.time_map.get_mut(into).unwrap();
+        target.extend(usages);
+        target.sort();
+        Ok(())
     }
 
-    /// Removes time usage from a category.
-    /// Returns an `Error` if the category or the time usage with the specified id doesn't exist.
-    pub fn remove_time_usage(&mut self, category: &str, id: usize) -> Result<()> {
-        if let Some(usages) = self.time_map.get_mut(category) {
-            if usages.len() > id {
-                usages.remove(id);
-                usages.sort();
-                Ok(())
-            } else {
-                Err(Error::TimeUsageDoesntExist(id))
-            }
-        } else {
-            Err(Error::CategoryDoesntExist(category.to_string()))
+    /// Moves all time usages of a category into the `UNCATEGORIZED` category, which is created if
+    /// it doesn't exist, and then removes the category. Removing the uncategorized category itself
+    /// does nothing.
+    /// Returns an `Error` if the category doesn't exist or if time is being recorded to it.
+    pub fn remove_category_keep_time(&mut self, category: &str) -> Result<()> {
+        if !self.time_map.contains_key(category) {
+            return Err(Error::CategoryDoesntExist(category.to_string()));
+        }
+
+        if self.current_cat.as_deref() == Some(category) {
+            return Err(Error::CategoryInUse(category.to_string()));
         }
+
+        // The category count doesn't grow as the category is removed, so the limit isn't checked.
+        self.time_map.entry(UNCATEGORIZED.to_string()).or_default();
+        self.remove_category_reassign(category, UNCATEGORIZED)
     }
 
-    /// Returns the time spent on each category from the specifed time span as a `Duration`;
+    /// Sets whether all time spent on a category is billable.
     /// Returns an `Error` if the category doesn't exist.
-    pub fn time_spent(&self, category: &str, shown_span: ShownTimeSpan) -> Result<Duration> {
-        if let Some(usages) = self.time_map.get(category) {
-            let mut total_duration = Duration::zero();
+    pub fn set_billable(&mut self, category: &str, billable: bool) -> Result<()> {
+        if !self.time_map.contains_key(category) {
+            return Err(Error::CategoryDoesntExist(category.to_string()));
+        }
 
-            for usage in usages {
-                if TimeBook::in_time_span(usage.start, shown_span) {
-                    total_duration = total_duration + (usage.stop - usage.start);
-                }
-            }
-            Ok(total_duration)
+        if billable {
+            self.billable_categories.insert(category.to_string());
         } else {
-            Err(Error::CategoryDoesntExist(category.to_string()))
+            self.billable_categories.remove(category);
         }
+        Ok(())
     }
 
-    /// Returns a log of all time usages from the specified time span.
-    /// Optionally show logs only from a single category.
-    /// Returned log is sorted so that the newest item is at the beginning.
+    /// Returns true if the category is billable.
+    pub fn is_billable(&self, category: &str) -> bool {
+        self.billable_categories.contains(category)
+    }
+
+    /// Sets the time planned to be spent on a category each week. Passing `None` removes the goal.
     /// Returns an `Error` if the category doesn't exist.
-    pub fn time_usage_log(
-        &self,
-        shown_span: ShownTimeSpan,
-        category: Option<String>, // This being a owned string is a workaround for some borrow
-                                  // checker issues. It doesn't currently really cause any harm and
-                                  // solves an issue so it can stay.
-    ) -> Result<String> {
-        let category = &category;
+    pub fn set_goal(&mut self, category: &str, weekly: Option<Duration>) -> Result<()> {
+        if !self.time_map.contains_key(category) {
+            return Err(Error::CategoryDoesntExist(category.to_string()));
+        }
 
-        if let Some(cat) = category {
-            if let Some(usages) = self.time_map.get(cat) {
-                let mut st = String::new();
+        match weekly {
+            Some(weekly) => self.goals.insert(category.to_string(), Goal { weekly }),
+            None => self.goals.remove(category),
+        };
+        Ok(())
+    }
 
-                for (i, usage) in usages.iter().enumerate() {
-                    st = TimeBook::concat_usage(st, usage, i, shown_span, cat);
-                }
+    /// Returns the time planned to be spent on a category each week or `None` if it has no goal.
+    pub fn goal(&self, category: &str) -> Option<Duration> {
+        self.goals.get(category).map(|g| g.weekly)
+    }
 
-                Ok(st)
+    /// Pins a category so that it's listed before the other categories. Pinned categories are
+    /// listed in the order they were pinned. Pinning a pinned category does nothing.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn pin_category(&mut self, category: &str) -> Result<()> {
+        if !self.time_map.contains_key(category) {
+            return Err(Error::CategoryDoesntExist(category.to_string()));
+        }
+
+        if !self.favorites.iter().any(|c| c == category) {
+            self.favorites.push(category.to_string());
+        }
+        Ok(())
+    }
+
+    /// Unpins a category. Unpinning a category that isn't pinned does nothing.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn unpin_category(&mut self, category: &str) -> Result<()> {
+        if !self.time_map.contains_key(category) {
+            return Err(Error::CategoryDoesntExist(category.to_string()));
+        }
+
+        self.favorites.retain(|c| c != category);
+        Ok(())
+    }
+
+    /// Returns all categories. Pinned categories are first in the order they were pinned and the
+    /// rest are sorted alphabetically.
+    pub fn categories(&self) -> Vec<&String> {
+        let mut categories: Vec<&String> = self.time_map.keys().collect();
+        self.sort_categories(&mut categories);
+        categories
+    }
+
+    /// Returns all categories matching a pattern sorted alphabetically. A trailing `*` in the
+    /// pattern matches any suffix, otherwise the category must equal the pattern.
+    pub fn categories_matching(&self, pattern: &str) -> Vec<&String> {
+        let mut matching: Vec<&String> = self
+            .time_map
+            .keys()
+            .filter(|cat| match pattern.strip_suffix('*') {
+                Some(prefix) => cat.starts_with(prefix),
+                None => cat.as_str() == pattern,
+            })
+            .collect();
+
+        matching.sort();
+        matching
+    }
+
+    /// Creates a new `TimeUsage` that lasted the duration and stopped at the current moment.
+    /// Returns an `Error` if the category doesn't exist or if the duration isn't longer than zero
+    /// or is longer than a day.
+    pub fn log_finished(
+        &mut self,
+        category: &str,
+        duration: Duration,
+        desc: Option<String>,
+    ) -> Result<()> {
+        if duration <= Duration::zero() {
+            return Err(Error::DurationNotPositive);
+        }
+
+        let max = Duration::days(1);
+        if duration > max {
+            return Err(Error::DurationTooLong(max));
+        }
+
+        let now = self.now();
+        self.add_time_usage(category, now - duration, now, desc)
+    }
+
+    /// Creates a new `TimeUsage` and adds it to the `TimeBook` in the specified category.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn add_time_usage(
+        &mut self,
+        category: &str,
+        start_time: NaiveDateTime,
+        stop_time: NaiveDateTime,
+        desc: Option<String>,
+    ) -> Result<()> {
+        self.add_time_usage_with(
+            category,
+            start_time,
+            stop_time,
+            UsageDetails {
+                desc,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Creates a new `TimeUsage` like `add_time_usage` but allows specifying all details of the
+    /// `TimeUsage`. Without a description the description template is used if one is set.
+    /// Returns an `Error` if the category doesn't exist or if a required description is missing.
+    pub fn add_time_usage_with(
+        &mut self,
+        category: &str,
+        start_time: NaiveDateTime,
+        stop_time: NaiveDateTime,
+        mut details: UsageDetails,
+    ) -> Result<()> {
+        if details.desc.is_none() {
+            details.desc = self.description_template.as_ref().map(|template| {
+                template
+                    .replace("{category}", category)
+                    .replace("{date}", &start_time.format("%Y-%m-%d").to_string())
+                    .replace("{start}", &start_time.format("%H:%M").to_string())
+                    .replace("{stop}", &stop_time.format("%H:%M").to_string())
+            });
+        }
+
+        if self.require_description && details.desc.as_deref().is_none_or(|d| d.trim().is_empty()) {
+            return Err(Error::DescriptionRequired);
+        }
+
+        if let Some(usages) = self.time_map.get_mut(category) {
+            usages.push(TimeUsage {
+                start: start_time,
+                stop: stop_time,
+                desc: details.desc,
+                subtasks: details.subtasks,
+                tags: details.tags,
+                reference: details.reference,
+            });
+            usages.sort();
+            Ok(())
+        } else {
+            Err(Error::CategoryDoesntExist(category.to_string()))
+        }
+    }
+
+    /// Removes every time usage starting before the cutoff date from all categories. Returns the
+    /// number of removed usages.
+    pub fn purge_before(&mut self, cutoff: NaiveDate) -> usize {
+        let mut removed = 0;
+
+        for usages in self.time_map.values_mut() {
+            let before = usages.len();
+            usages.retain(|usage| usage.start.date() >= cutoff);
+            removed += before - usages.len();
+        }
+
+        removed
+    }
+
+    /// Moves the start and the stop of a time usage by the deltas, which may be negative.
+    /// Returns an `Error` if the category or the time usage doesn't exist or if the usage would
+    /// stop before it starts. Nothing is modified in that case.
+    pub fn adjust_time_usage(
+        &mut self,
+        category: &str,
+        id: usize,
+        start_delta: Duration,
+        stop_delta: Duration,
+    ) -> Result<()> {
+        let usages = self
+            .time_map
+            .get_mut(category)
+            .ok_or_else(|| Error::CategoryDoesntExist(category.to_string()))?;
+        let usage = usages.get_mut(id).ok_or(Error::TimeUsageDoesntExist(id))?;
+
+        let start = usage.start + start_delta;
+        let stop = usage.stop + stop_delta;
+        if stop < start {
+            return Err(Error::StopBeforeStart(id));
+        }
+
+        usage.start = start;
+        usage.stop = stop;
+        usages.sort();
+        Ok(())
+    }
+
+    /// Removes time usage from a category.
+    /// Returns an `Error` if the category or the time usage with the specified id doesn't exist.
+    pub fn remove_time_usage(&mut self, category: &str, id: usize) -> Result<()> {
+        if let Some(usages) = self.time_map.get_mut(category) {
+            if usages.len() > id {
+                usages.remove(id);
+                usages.sort();
+                Ok(())
             } else {
-                Err(Error::CategoryDoesntExist(cat.to_string()))
+                Err(Error::TimeUsageDoesntExist(id))
             }
         } else {
-            // The following bit of code is quite messy.
-            // We build a chronologically ordered log string that contains entries from EVERY
-            // category.
-            // We use the fact that every TimeUsage list is always sorted.
+            Err(Error::CategoryDoesntExist(category.to_string()))
+        }
+    }
 
-            // Calculate the total number of TimeUsage entries in all categories.
-            // Also create a new hash map containing indicies to every category.
-            let mut index_sum = 0;
-            let mut index_map = HashMap::new();
-            for (category, val) in &self.time_map {
-                index_sum += val.len();
-                index_map.insert(category, 0usize);
+    /// Moves the first time usage to the category of the second one and vice versa.
+    /// Returns an `Error` if either category or time usage doesn't exist. Nothing is modified in
+    /// that case.
+    pub fn swap_usage_categories(
+        &mut self,
+        cat_a: &str,
+        id_a: usize,
+        cat_b: &str,
+        id_b: usize,
+    ) -> Result<()> {
+        for (cat, id) in [(cat_a, id_a), (cat_b, id_b)] {
+            let usages = self
+                .time_map
+                .get(cat)
+                .ok_or_else(|| Error::CategoryDoesntExist(cat.to_string()))?;
+            if usages.len() <= id {
+                return Err(Error::TimeUsageDoesntExist(id));
             }
+        }
 
-            // The indicies start at 0 but every time a category is accessed the mapped index is
-            // increased by one.
+        // Swapping usages within a single category doesn't change anything.
+        if cat_a == cat_b {
+            return Ok(());
+        }
 
-            // Basically loop through every element in all the categories' TimeUsage lists.
-            // Every iteration look through all categories for the TimeUsage that is the oldest.
-            // Append that usage to the log.
-            // Increment that category's index by one because we don't want for it to get re-added.
+        // Both exist so unwrap is ok.
+        let usage_a = self.time_map.get_mut(cat_a).unwrap().remove(id_a);
+        let usage_b = self.time_map.get_mut(cat_b).unwrap().remove(id_b);
 
-            let mut log = String::new();
+        let usages_a = self.time_map.get_mut(cat_a).unwrap();
+        usages_a.push(usage_b);
+        usages_a.sort();
 
-            for _ in 0..index_sum {
-                // Random category as the oldest for a starting point
-                let mut oldest = index_map.keys().next().unwrap().to_owned();
+        let usages_b = self.time_map.get_mut(cat_b).unwrap();
+        usages_b.push(usage_a);
+        usages_b.sort();
 
-                for (cat, cat_index) in &index_map {
-                    // Both of these exist so unwrap is ok.
-                    let cat_items = self.time_map.get(cat.to_owned()).unwrap();
-                    let oldest_items = self.time_map.get(oldest).unwrap();
+        Ok(())
+    }
 
-                    let cat_index = cat_index.to_owned();
-                    let oldest_index = index_map[oldest];
+    /// Creates a new `TimeUsage` in `other_category` that fills the gap between the stop of the
+    /// specified usage and the start of the next usage in any category.
+    /// Returns an `Error` if either category or the time usage doesn't exist or if there is no
+    /// gap after the time usage.
+    pub fn insert_in_gap(
+        &mut self,
+        category: &str,
+        after_id: usize,
+        other_category: &str,
+        desc: Option<String>,
+    ) -> Result<()> {
+        let usages = self
+            .time_map
+            .get(category)
+            .ok_or_else(|| Error::CategoryDoesntExist(category.to_string()))?;
+        let gap_start = usages
+            .get(after_id)
+            .ok_or(Error::TimeUsageDoesntExist(after_id))?
+            .stop;
 
-                    // If the index is out of bounds, every TimeUsage has been already added from this
-                    // category.
-                    if cat_items.len() <= cat_index {
-                        continue;
-                    }
+        if !self.time_map.contains_key(other_category) {
+            return Err(Error::CategoryDoesntExist(other_category.to_string()));
+        }
 
-                    // If the index is out of bounds, every TimeUsage has been already added from the
-                    // oldest category.
-                    if oldest_items.len() <= oldest_index {
-                        oldest = cat;
-                        continue;
-                    }
+        let gap_stop = self
+            .time_map
+            .values()
+            .flatten()
+            .map(|usage| usage.start)
+            .filter(|start| *start >= gap_start)
+            .min();
 
-                    // Check if the oldest item should change
-                    if cat_items[cat_index] < oldest_items[oldest_index] {
-                        oldest = cat;
-                    }
-                }
+        match gap_stop {
+            Some(gap_stop) if gap_stop > gap_start => {
+                self.add_time_usage(other_category, gap_start, gap_stop, desc)
+            }
+            _ => Err(Error::NoGapAfterTimeUsage(after_id)),
+        }
+    }
 
-                let oldest_index = index_map[oldest];
-                let oldest_usage = self.time_map[oldest].get(oldest_index).unwrap();
+    /// Returns the total time spent within the specified time span on time usages whose tags
+    /// match the given tags. Every usage is counted once no matter how many of the tags it has.
+    pub fn time_by_tags(
+        &self,
+        tags: &[String],
+        mode: TagMatch,
+        shown_span: ShownTimeSpan,
+    ) -> Duration {
+        let mut total_duration = Duration::zero();
 
-                // Increment the index map for the oldest category
-                index_map.insert(oldest, index_map[oldest] + 1);
+        for usage in self.time_map.values().flatten() {
+            let matches = match mode {
+                TagMatch::All => tags.iter().all(|t| usage.tags.contains(t)),
+                TagMatch::Any => tags.iter().any(|t| usage.tags.contains(t)),
+            };
 
-                log = TimeBook::concat_usage(log, oldest_usage, oldest_index, shown_span, oldest);
+            if matches && self.in_time_span(usage.start, shown_span) {
+                total_duration = total_duration + (usage.stop - usage.start);
             }
+        }
 
-            Ok(log)
+        total_duration
+    }
+
+    /// Returns every tag used on time usages and the number of usages having it. The tags are
+    /// sorted by the count with the most used first. Tags with equal counts are sorted by name.
+    pub fn all_tags(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&String, usize> = HashMap::new();
+
+        for usage in self.time_map.values().flatten() {
+            for tag in &usage.tags {
+                *counts.entry(tag).or_insert(0) += 1;
+            }
         }
+
+        let mut tags: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(tag, count)| (tag.clone(), count))
+            .collect();
+        tags.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        tags
     }
 
-    /// Concatenates a time usage to the beginning of the specified string and returns the result.
-    fn concat_usage(
-        mut s: String,
-        usage: &TimeUsage,
-        usage_id: usize,
-        shown_span: ShownTimeSpan,
-        cat: &str,
-    ) -> String {
-        if TimeBook::in_time_span(usage.start, shown_span) {
-            let fstring = "%-d/%-m/%Y %H:%M";
-            let mut elem = format!(
-                "{} - {}: {} (ID: {})",
-                usage.start.format(fstring),
-                usage.stop.format(fstring),
-                cat,
-                usage_id
-            );
-            if let Some(d) = &usage.desc {
-                elem = format!("{}\n\t{}", elem, d);
+    /// Adds a tag to every time usage whose description contains the pattern. Usages that already
+    /// have the tag are left unchanged. Returns the number of usages that were changed.
+    /// Returns an `Error` if the tag is empty.
+    pub fn tag_matching(&mut self, pattern: &str, tag: &str) -> Result<usize> {
+        if tag.is_empty() {
+            return Err(Error::EmptyTag);
+        }
+
+        let mut changed = 0;
+        for usage in self.time_map.values_mut().flatten() {
+            if usage.desc.as_deref().is_some_and(|d| d.contains(pattern))
+                && !usage.tags.iter().any(|t| t == tag)
+            {
+                usage.tags.push(tag.to_string());
+                changed += 1;
             }
-            s = format!("{}\n\n{}", elem, s);
         }
-        s
+
+        Ok(changed)
     }
 
-    /// Returns true if a start_time is within the specified `ShownTimeSpan`.
-    fn in_time_span(start_time: NaiveDateTime, span: ShownTimeSpan) -> bool {
-        let now = Local::now().naive_local();
-        let today = now.date();
+    /// Renames a tag on every time usage and on the current recording. If a usage already has the
+    /// new tag, the old tag is just removed. Returns the number of usages that were changed.
+    pub fn rename_tag(&mut self, old: &str, new: &str) -> usize {
+        let mut changed = 0;
 
-        match span {
-            ShownTimeSpan::All => true,
-            ShownTimeSpan::Year => today - start_time.date() <= Duration::days(365),
-            ShownTimeSpan::Month => today - start_time.date() <= Duration::weeks(4),
-            ShownTimeSpan::Week => today - start_time.date() <= Duration::weeks(1),
-            ShownTimeSpan::Yesterday => today.pred() == start_time.date(),
-            ShownTimeSpan::Today => today == start_time.date(),
+        for usage in self.time_map.values_mut().flatten() {
+            if rename_in(&mut usage.tags, old, new) {
+                changed += 1;
+            }
         }
+        rename_in(&mut self.current_tags, old, new);
+
+        changed
     }
-}
 
-// Due to the small nature of this project I have only written integration test style large
-// tests that don't follow the AAA pattern. Basically I have merged tests together.
-//
-// Splitting the tests into smaller units would be more work and wouldn't provide much practical
-// benefit.
+    /// Splits every time usage crossing midnight into a time usage for each day it spans. The
+    /// parts share the description, subtasks and tags of the original. Returns the number of
+    /// new time usages created by splitting.
+    pub fn normalize_midnight(&mut self) -> usize {
+        let mut splits = 0;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use chrono::NaiveDate;
+        for usages in self.time_map.values_mut() {
+            let mut parts = Vec::new();
 
-    #[test]
-    fn category_can_be_added_and_removed() {
-        let mut book = TimeBook::default();
+            for usage in usages.iter_mut() {
+                loop {
+                    let midnight = (usage.start.date() + Duration::days(1)).and_hms(0, 0, 0);
+                    if usage.stop <= midnight {
+                        break;
+                    }
 
-        book.add_category("test".to_string()).unwrap();
+                    parts.push(TimeUsage {
+                        start: usage.start,
+                        stop: midnight,
+                        desc: usage.desc.clone(),
+                        subtasks: usage.subtasks.clone(),
+                        tags: usage.tags.clone(),
+                        reference: usage.reference.clone(),
+                    });
+                    usage.start = midnight;
+                    splits += 1;
+                }
+            }
 
-        assert!(book.categories().contains(&&("test".to_string())));
+            if !parts.is_empty() {
+                usages.extend(parts);
+                usages.sort();
+            }
+        }
 
-        book.add_category("test1".to_string()).unwrap();
+        splits
+    }
 
-        assert_eq!(book.categories().len(), 2);
+    /// Returns the time spent on each category from the specifed time span as a `Duration`;
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn time_spent(&self, category: &str, shown_span: ShownTimeSpan) -> Result<Duration> {
+        if let Some(usages) = self.time_map.get(category) {
+            let mut total_duration = Duration::zero();
+
+            for usage in usages {
+                if self.in_time_span(usage.start, shown_span) {
+                    total_duration = total_duration + (usage.stop - usage.start);
+                }
+            }
+            Ok(total_duration)
+        } else {
+            Err(Error::CategoryDoesntExist(category.to_string()))
+        }
+    }
+
+    /// Returns the total time ever spent on a category.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn lifetime_total(&self, category: &str) -> Result<Duration> {
+        let usages = self
+            .time_map
+            .get(category)
+            .ok_or_else(|| Error::CategoryDoesntExist(category.to_string()))?;
+
+        Ok(usages.iter().fold(Duration::zero(), |total, usage| {
+            total + (usage.stop - usage.start)
+        }))
+    }
+
+    /// Returns the time spent on a category within the specified time span and the time that
+    /// would be spent if a time usage from start to stop was added, without adding it. The usage
+    /// only affects the total if it starts within the time span.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn preview_with(
+        &self,
+        category: &str,
+        start_time: NaiveDateTime,
+        stop_time: NaiveDateTime,
+        shown_span: ShownTimeSpan,
+    ) -> Result<(Duration, Duration)> {
+        let current = self.time_spent(category, shown_span)?;
+        if self.in_time_span(start_time, shown_span) {
+            Ok((current, current + (stop_time - start_time)))
+        } else {
+            Ok((current, current))
+        }
+    }
+
+    /// Returns the time spent on a category within the specified time span with the total of each
+    /// day limited to the cap. Useful for keeping recordings that were left running from skewing
+    /// reports. Usages are attributed to the day they start on.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn time_spent_capped(
+        &self,
+        category: &str,
+        shown_span: ShownTimeSpan,
+        daily_cap: Duration,
+    ) -> Result<Duration> {
+        if !self.time_map.contains_key(category) {
+            return Err(Error::CategoryDoesntExist(category.to_string()));
+        }
+
+        Ok(self
+            .daily_totals(shown_span, Some(category))
+            .into_values()
+            .fold(Duration::zero(), |total, spent| {
+                total + spent.min(daily_cap)
+            }))
+    }
+
+    /// Returns the time spent on every category within the specified time span and their total,
+    /// all rounded to the granularity. The total is rounded with the rounding mode and the
+    /// categories are rounded so that they sum exactly to the total using largest remainder
+    /// apportionment.
+    pub fn time_spent_all_rounded(
+        &self,
+        shown_span: ShownTimeSpan,
+        granularity: Duration,
+        mode: RoundingMode,
+    ) -> (HashMap<String, Duration>, Duration) {
+        let mut spent: Vec<(&String, Duration)> = self
+            .time_map
+            .keys()
+            // The category exists so unwrap is ok.
+            .map(|cat| (cat, self.time_spent(cat, shown_span).unwrap()))
+            .collect();
+        let total = spent
+            .iter()
+            .fold(Duration::zero(), |total, (_, d)| total + *d);
+        let rounded_total = round_duration(total, granularity, mode);
+
+        let gran = granularity.num_seconds();
+        if gran <= 0 {
+            return (
+                spent.into_iter().map(|(c, d)| (c.clone(), d)).collect(),
+                rounded_total,
+            );
+        }
+
+        // Give every category its whole units and the units left over to the categories with
+        // the largest remainders.
+        spent.sort_by(|(cat_a, a), (cat_b, b)| {
+            (b.num_seconds() % gran)
+                .cmp(&(a.num_seconds() % gran))
+                .then(cat_a.cmp(cat_b))
+        });
+        let whole_units: i64 = spent.iter().map(|(_, d)| d.num_seconds() / gran).sum();
+        let mut left_over = rounded_total.num_seconds() / gran - whole_units;
+
+        let mut rounded = HashMap::new();
+        for (cat, d) in spent {
+            let mut units = d.num_seconds() / gran;
+            if left_over > 0 && d.num_seconds() % gran != 0 {
+                units += 1;
+                left_over -= 1;
+            }
+            rounded.insert(cat.clone(), Duration::seconds(units * gran));
+        }
+
+        (rounded, rounded_total)
+    }
+
+    /// Returns the number of time usages in a category within the specified time span.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn session_count(&self, category: &str, shown_span: ShownTimeSpan) -> Result<usize> {
+        Ok(self.usages_in_span(shown_span, Some(category))?.len())
+    }
+
+    /// Returns the number of sittings in a category within the specified time span. Unlike in
+    /// `session_count` consecutive usages separated by less than `max_gap` count as one sitting.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn sitting_count(
+        &self,
+        category: &str,
+        shown_span: ShownTimeSpan,
+        max_gap: Duration,
+    ) -> Result<usize> {
+        let mut sittings = 0;
+        let mut latest_stop: Option<NaiveDateTime> = None;
+
+        for (_, _, usage) in self.usages_in_span(shown_span, Some(category))? {
+            if latest_stop.is_none_or(|stop| usage.start - stop >= max_gap) {
+                sittings += 1;
+            }
+            latest_stop = Some(latest_stop.map_or(usage.stop, |stop| stop.max(usage.stop)));
+        }
+
+        Ok(sittings)
+    }
+
+    /// Returns all categories with no time spent on them within the specified time span sorted
+    /// alphabetically. Categories that have never been used are included.
+    pub fn inactive_categories(&self, shown_span: ShownTimeSpan) -> Vec<&String> {
+        let mut inactive: Vec<&String> = self
+            .time_map
+            .keys()
+            .filter(|cat| {
+                // The category exists so unwrap is ok.
+                self.time_spent(cat, shown_span).unwrap() == Duration::zero()
+            })
+            .collect();
+
+        inactive.sort();
+        inactive
+    }
+
+    /// Returns all categories with time usages starting in the specified calendar month sorted
+    /// alphabetically. Usages are attributed to the month they start in.
+    pub fn active_categories_in_month(&self, year: i32, month: u32) -> Vec<&String> {
+        let mut active: Vec<&String> = self
+            .time_map
+            .iter()
+            .filter(|(_, usages)| {
+                usages
+                    .iter()
+                    .any(|u| u.start.year() == year && u.start.month() == month)
+            })
+            .map(|(cat, _)| cat)
+            .collect();
+
+        active.sort();
+        active
+    }
+
+    /// Returns a summary of the time spent on every category within the specified time span.
+    /// The categories are ordered like in `categories`. Optionally the summary contains only a
+    /// single category.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn summary(&self, shown_span: ShownTimeSpan, category: Option<&str>) -> Result<Summary> {
+        let mut names: Vec<&String> = match category {
+            Some(c) => match self.time_map.get_key_value(c) {
+                Some((name, _)) => vec![name],
+                None => return Err(Error::CategoryDoesntExist(c.to_string())),
+            },
+            None => self.time_map.keys().collect(),
+        };
+        self.sort_categories(&mut names);
+
+        let mut summary = Summary {
+            span: shown_span,
+            categories: Vec::new(),
+            total: Duration::zero(),
+        };
+
+        for name in names {
+            let spent = self.time_spent(name, shown_span)?;
+            summary.total = summary.total + spent;
+            summary.categories.push(CategorySummary {
+                category: name.clone(),
+                spent,
+            });
+        }
+
+        Ok(summary)
+    }
+
+    /// Returns the share of the total time spent within the specified time span of every category
+    /// with time spent on it. The shares are between 0 and 1 and sorted from the largest.
+    /// Categories with equal shares are sorted alphabetically. Returns no shares if no time was
+    /// spent.
+    pub fn distribution(&self, shown_span: ShownTimeSpan) -> Vec<(String, f64)> {
+        // Without a category filter there can be no error.
+        let summary = self.summary(shown_span, None).unwrap();
+        let total = summary.total.num_seconds();
+        if total <= 0 {
+            return Vec::new();
+        }
+
+        let mut shares: Vec<(String, f64)> = summary
+            .categories
+            .into_iter()
+            .filter(|c| c.spent > Duration::zero())
+            .map(|c| (c.category, c.spent.num_seconds() as f64 / total as f64))
+            .collect();
+
+        shares.sort_by(|(cat_a, a), (cat_b, b)| b.total_cmp(a).then(cat_a.cmp(cat_b)));
+        shares
+    }
+
+    /// Returns how evenly the time spent within the specified time span is spread across the
+    /// categories with time spent on them as the normalized entropy of the distribution. 1 means
+    /// that every category has an equal share and 0 that all time was spent on a single category.
+    /// Returns 0 if no time was spent.
+    pub fn balance_score(&self, shown_span: ShownTimeSpan) -> f64 {
+        let shares = self.distribution(shown_span);
+        if shares.len() < 2 {
+            return 0.0;
+        }
+
+        let entropy: f64 = shares.iter().map(|(_, p)| -p * p.ln()).sum();
+        entropy / (shares.len() as f64).ln()
+    }
+
+    /// Returns the time spent on each category with time spent on it during the date, sorted from
+    /// the largest. Only the part of a time usage that falls on the date is counted, so usages
+    /// crossing midnight are split between the days. Categories with equal times are sorted
+    /// alphabetically.
+    pub fn day_distribution(&self, date: NaiveDate) -> Vec<(String, Duration)> {
+        let day_start = date.and_hms(0, 0, 0);
+        let day_end = day_start + Duration::days(1);
+
+        let mut totals: Vec<(String, Duration)> = self
+            .time_map
+            .iter()
+            .map(|(cat, usages)| {
+                let spent = usages
+                    .iter()
+                    .map(|u| u.stop.min(day_end) - u.start.max(day_start))
+                    .filter(|d| *d > Duration::zero())
+                    .fold(Duration::zero(), |acc, d| acc + d);
+                (cat.clone(), spent)
+            })
+            .filter(|(_, spent)| *spent > Duration::zero())
+            .collect();
+
+        totals.sort_by(|(cat_a, a), (cat_b, b)| b.cmp(a).then(cat_a.cmp(cat_b)));
+        totals
+    }
+
+    /// Returns the time spent within the specified time span grouped by the part of the
+    /// description before the first `sep`, sorted by the time spent with the longest first.
+    /// Prefixes are trimmed of surrounding whitespace. Usages without the separator or without a
+    /// description are grouped under "(none)".
+    pub fn totals_by_desc_prefix(
+        &self,
+        shown_span: ShownTimeSpan,
+        sep: char,
+    ) -> Vec<(String, Duration)> {
+        let mut groups: HashMap<&str, Duration> = HashMap::new();
+
+        // Without a category filter there can be no error.
+        for (_, _, usage) in self.usages_in_span(shown_span, None).unwrap() {
+            let prefix = usage
+                .desc
+                .as_deref()
+                .and_then(|desc| desc.split_once(sep))
+                .map_or("(none)", |(prefix, _)| prefix.trim());
+            let total = groups.entry(prefix).or_insert_with(Duration::zero);
+            *total = *total + (usage.stop - usage.start);
+        }
+
+        let mut totals: Vec<(String, Duration)> = groups
+            .into_iter()
+            .map(|(prefix, total)| (prefix.to_string(), total))
+            .collect();
+        totals.sort_by(|(prefix_a, a), (prefix_b, b)| b.cmp(a).then(prefix_a.cmp(prefix_b)));
+        totals
+    }
+
+    /// Returns how many times the category changes between consecutive time usages starting on
+    /// the date when the usages of all categories are ordered by their start.
+    pub fn context_switches(&self, date: NaiveDate) -> usize {
+        let mut usages: Vec<(NaiveDateTime, &String)> = self
+            .time_map
+            .iter()
+            .flat_map(|(cat, usages)| usages.iter().map(move |u| (u.start, cat)))
+            .filter(|(start, _)| start.date() == date)
+            .collect();
+        usages.sort();
+
+        usages.windows(2).filter(|w| w[0].1 != w[1].1).count()
+    }
+
+    /// Returns the total time spent on all categories except the excluded ones from the specified
+    /// time span. Excluded categories that don't exist are ignored.
+    pub fn total_excluding(&self, shown_span: ShownTimeSpan, exclude: &[String]) -> Duration {
+        let mut total_duration = Duration::zero();
+
+        for (cat, usages) in &self.time_map {
+            if exclude.contains(cat) {
+                continue;
+            }
+
+            for usage in usages {
+                if self.in_time_span(usage.start, shown_span) {
+                    total_duration = total_duration + (usage.stop - usage.start);
+                }
+            }
+        }
+
+        total_duration
+    }
+
+    /// Returns the average gap between consecutive time usages of a category within the specified
+    /// time span. Overlapping usages are counted as having no gap.
+    /// Returns `None` if there are fewer than two usages in the time span.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn average_gap(
+        &self,
+        category: &str,
+        shown_span: ShownTimeSpan,
+    ) -> Result<Option<Duration>> {
+        let usages = self.usages_in_span(shown_span, Some(category))?;
+
+        if usages.len() < 2 {
+            return Ok(None);
+        }
+
+        let mut total_gap = Duration::zero();
+        for pair in usages.windows(2) {
+            let gap = pair[1].2.start - pair[0].2.stop;
+            total_gap = total_gap + gap.max(Duration::zero());
+        }
+
+        Ok(Some(total_gap / (usages.len() - 1) as i32))
+    }
+
+    /// Returns the n longest gaps without any time usages between the first and the last usage of
+    /// all categories as the start and the end of the gap and its length. The gaps are sorted by
+    /// length with the longest first. Gaps of equal length are sorted by their start.
+    pub fn largest_gaps(&self, n: usize) -> Vec<(NaiveDateTime, NaiveDateTime, Duration)> {
+        // Without a category filter there can be no error.
+        let usages = self.usages_in_span(ShownTimeSpan::All, None).unwrap();
+
+        let mut gaps = Vec::new();
+        let mut latest_stop: Option<NaiveDateTime> = None;
+        for (_, _, usage) in usages {
+            if let Some(stop) = latest_stop {
+                if usage.start > stop {
+                    gaps.push((stop, usage.start, usage.start - stop));
+                }
+            }
+            latest_stop = Some(latest_stop.map_or(usage.stop, |stop| stop.max(usage.stop)));
+        }
+
+        gaps.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        gaps.truncate(n);
+        gaps
+    }
+
+    /// Returns the ids and durations of a category's time usages within the specified time span
+    /// sorted by the duration with the longest first. Usages with equal durations are sorted by
+    /// id. The stored order of the usages isn't changed.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn usages_by_duration(
+        &self,
+        category: &str,
+        shown_span: ShownTimeSpan,
+    ) -> Result<Vec<(usize, Duration)>> {
+        let mut ranked: Vec<(usize, Duration)> = self
+            .usages_in_span(shown_span, Some(category))?
+            .into_iter()
+            .map(|(_, id, usage)| (id, usage.stop - usage.start))
+            .collect();
+
+        ranked.sort_by(|(id_a, a), (id_b, b)| b.cmp(a).then(id_a.cmp(id_b)));
+        Ok(ranked)
+    }
+
+    /// Returns the start, stop and duration of each of a category's time usages within the
+    /// specified time span as seconds, sorted by start. The start and stop are seconds since
+    /// 1970-01-01 00:00 in the local time the usages are stored in, so no time zone conversion
+    /// is done.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn plot_data(
+        &self,
+        category: &str,
+        shown_span: ShownTimeSpan,
+    ) -> Result<Vec<(i64, i64, i64)>> {
+        Ok(self
+            .usages_in_span(shown_span, Some(category))?
+            .into_iter()
+            .map(|(_, _, usage)| {
+                (
+                    usage.start.timestamp(),
+                    usage.stop.timestamp(),
+                    (usage.stop - usage.start).num_seconds(),
+                )
+            })
+            .collect())
+    }
+
+    /// Returns the intervals of a category's time usages within the specified time span with
+    /// consecutive usages merged into a single block when the gap between them is shorter than
+    /// `max_gap`. Overlapping usages are always merged. The blocks are sorted by start.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn contiguous_blocks(
+        &self,
+        category: &str,
+        shown_span: ShownTimeSpan,
+        max_gap: Duration,
+    ) -> Result<Vec<(NaiveDateTime, NaiveDateTime)>> {
+        let mut blocks: Vec<(NaiveDateTime, NaiveDateTime)> = Vec::new();
+
+        for (_, _, usage) in self.usages_in_span(shown_span, Some(category))? {
+            match blocks.last_mut() {
+                Some((_, stop)) if usage.start - *stop < max_gap => {
+                    *stop = (*stop).max(usage.stop);
+                }
+                _ => blocks.push((usage.start, usage.stop)),
+            }
+        }
+
+        Ok(blocks)
+    }
+
+    /// Returns the average time spent on a category per day with time spent on it within the
+    /// specified time span. Days without time spent on the category aren't counted. Returns
+    /// `None` if there are no such days.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn per_active_day(
+        &self,
+        category: &str,
+        shown_span: ShownTimeSpan,
+    ) -> Result<Option<Duration>> {
+        if !self.time_map.contains_key(category) {
+            return Err(Error::CategoryDoesntExist(category.to_string()));
+        }
+
+        let days = self.daily_totals(shown_span, Some(category));
+        if days.is_empty() {
+            return Ok(None);
+        }
+
+        let total = days
+            .values()
+            .fold(Duration::zero(), |total, spent| total + *spent);
+        Ok(Some(total / days.len() as i32))
+    }
+
+    /// Returns the stop of each of a category's time usages within the specified time span in
+    /// chronological order paired with the total time spent up to and including that usage.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn cumulative_series(
+        &self,
+        category: &str,
+        shown_span: ShownTimeSpan,
+    ) -> Result<Vec<(NaiveDateTime, Duration)>> {
+        let mut total = Duration::zero();
+
+        Ok(self
+            .usages_in_span(shown_span, Some(category))?
+            .into_iter()
+            .map(|(_, _, usage)| {
+                total = total + (usage.stop - usage.start);
+                (usage.stop, total)
+            })
+            .collect())
+    }
+
+    /// Returns the requested percentiles of the durations of a category's time usages within the
+    /// specified time span. Percentiles are given from 0 to 100 and values outside that range are
+    /// clamped. The nearest-rank method is used, so every returned duration is the duration of
+    /// an actual usage. If there are no usages, every percentile is zero.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn duration_percentiles(
+        &self,
+        category: &str,
+        shown_span: ShownTimeSpan,
+        ps: &[f64],
+    ) -> Result<Vec<Duration>> {
+        let mut durations: Vec<Duration> = self
+            .usages_in_span(shown_span, Some(category))?
+            .into_iter()
+            .map(|(_, _, usage)| usage.stop - usage.start)
+            .collect();
+        durations.sort();
+
+        if durations.is_empty() {
+            return Ok(vec![Duration::zero(); ps.len()]);
+        }
+
+        let n = durations.len();
+        Ok(ps
+            .iter()
+            .map(|p| {
+                let rank = (p.clamp(0.0, 100.0) / 100.0 * n as f64).ceil() as usize;
+                durations[rank.clamp(1, n) - 1]
+            })
+            .collect())
+    }
+
+    /// Returns the fraction of a day's waking window that is covered by time usages of any
+    /// category. Overlapping usages are only counted once. If `wake_end` isn't after `wake_start`
+    /// the window is considered to continue to the next day.
+    pub fn day_coverage(&self, date: NaiveDate, wake_start: NaiveTime, wake_end: NaiveTime) -> f64 {
+        let window_start = date.and_time(wake_start);
+        let mut window_end = date.and_time(wake_end);
+        if window_end <= window_start {
+            window_end += Duration::days(1);
+        }
+
+        let clipped = self
+            .time_map
+            .values()
+            .flatten()
+            .map(|usage| (usage.start.max(window_start), usage.stop.min(window_end)))
+            .collect();
+
+        let covered = union_length(clipped).num_seconds() as f64;
+        covered / (window_end - window_start).num_seconds() as f64
+    }
+
+    /// Returns the fraction of the wall time of the specified time span that is covered by time
+    /// usages of any category. Overlapping usages are only counted once. For all records the span
+    /// lasts from the earliest start to the latest stop. Returns 0 if the span has no length.
+    pub fn tracking_ratio(&self, shown_span: ShownTimeSpan) -> f64 {
+        let (window_start, window_end) = match self.span_window(shown_span) {
+            Some(window) => window,
+            None => return 0.0,
+        };
+        if window_end <= window_start {
+            return 0.0;
+        }
+
+        // Without a category filter there can be no error.
+        let clipped = self
+            .usages_in_span(shown_span, None)
+            .unwrap()
+            .into_iter()
+            .map(|(_, _, usage)| (usage.start.max(window_start), usage.stop.min(window_end)))
+            .collect();
+
+        let covered = union_length(clipped).num_seconds() as f64;
+        covered / (window_end - window_start).num_seconds() as f64
+    }
+
+    /// Estimates the date when the total time spent on a category reaches the target if time is
+    /// spent at the average daily rate of the lookback span from now on. Returns today if the
+    /// target has already been reached and `None` if no time was spent within the lookback span.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn milestone_eta(
+        &self,
+        category: &str,
+        target: Duration,
+        lookback: ShownTimeSpan,
+    ) -> Result<Option<NaiveDate>> {
+        let today = self.now().date();
+        let remaining = target - self.time_spent(category, ShownTimeSpan::All)?;
+        if remaining <= Duration::zero() {
+            return Ok(Some(today));
+        }
+
+        let recent = self.time_spent(category, lookback)?;
+        let window = match self.span_window(lookback) {
+            Some((start, end)) if recent > Duration::zero() => end - start,
+            _ => return Ok(None),
+        };
+        // Spans shorter than a day would overestimate the daily rate.
+        let days = (window.num_seconds() as f64 / 86400.0).max(1.0);
+        let per_day = recent.num_seconds() as f64 / days;
+
+        let days_left = (remaining.num_seconds() as f64 / per_day).ceil() as i64;
+        Ok(Some(today + Duration::days(days_left)))
+    }
+
+    /// Returns the time spent on two categories within the specified time span and the ratio of
+    /// the time spent on the first to the time spent on the second. If no time is spent on the
+    /// second category the ratio is infinite, or 0 if no time is spent on either.
+    /// Returns an `Error` if either of the categories doesn't exist.
+    pub fn compare_categories(
+        &self,
+        a: &str,
+        b: &str,
+        shown_span: ShownTimeSpan,
+    ) -> Result<(Duration, Duration, f64)> {
+        let spent_a = self.time_spent(a, shown_span)?;
+        let spent_b = self.time_spent(b, shown_span)?;
+
+        let ratio = if spent_b > Duration::zero() {
+            spent_a.num_seconds() as f64 / spent_b.num_seconds() as f64
+        } else if spent_a > Duration::zero() {
+            f64::INFINITY
+        } else {
+            0.0
+        };
+        Ok((spent_a, spent_b, ratio))
+    }
+
+    /// Returns the fraction of the time spent within the specified time span that is spent on
+    /// time usages with a description. Empty or whitespace-only descriptions don't count, just like
+    /// when a description is required. Optionally only a single category is counted. A category
+    /// that doesn't exist has no time spent. Returns 0 if no time is spent.
+    pub fn described_ratio(&self, shown_span: ShownTimeSpan, category: Option<&str>) -> f64 {
+        let mut described = Duration::zero();
+        let mut total = Duration::zero();
+
+        for (_, _, usage) in self
+            .usages_in_span(shown_span, category)
+            .unwrap_or_default()
+        {
+            let spent = usage.stop - usage.start;
+            if usage.desc.as_deref().is_some_and(|d| !d.trim().is_empty()) {
+                described = described + spent;
+            }
+            total = total + spent;
+        }
+
+        if total == Duration::zero() {
+            0.0
+        } else {
+            described.num_seconds() as f64 / total.num_seconds() as f64
+        }
+    }
+
+    /// Returns the time spent on every category within the specified time span counting only the
+    /// parts of usages within the daily business hours from `start` to `end`. If `end` isn't after
+    /// `start` the business hours continue to the next day. Every category is included.
+    pub fn business_hours_totals(
+        &self,
+        shown_span: ShownTimeSpan,
+        start: NaiveTime,
+        end: NaiveTime,
+    ) -> HashMap<String, Duration> {
+        let mut totals: HashMap<String, Duration> = self
+            .time_map
+            .keys()
+            .map(|cat| (cat.clone(), Duration::zero()))
+            .collect();
+
+        // Without a category filter there can be no error.
+        for (cat, _, usage) in self.usages_in_span(shown_span, None).unwrap() {
+            // A window starting the day before can reach into the day the usage starts on.
+            let mut date = usage.start.date().pred();
+            while date <= usage.stop.date() {
+                let window_start = date.and_time(start);
+                let mut window_end = date.and_time(end);
+                if window_end <= window_start {
+                    window_end += Duration::days(1);
+                }
+
+                let within = usage.stop.min(window_end) - usage.start.max(window_start);
+                if within > Duration::zero() {
+                    let total = totals.get_mut(cat).unwrap();
+                    *total = *total + within;
+                }
+                date = date.succ();
+            }
+        }
+
+        totals
+    }
+
+    /// Returns the time spent in each ISO week within the specified time span as the Monday of the
+    /// week and the total, sorted from the oldest week. Usages are attributed to the week they
+    /// start in. Weeks without time spent between the first and the last week are included with a
+    /// total of zero. Optionally only a single category is counted.
+    /// Returns nothing if the category doesn't exist.
+    pub fn weekly_totals(
+        &self,
+        category: Option<&str>,
+        shown_span: ShownTimeSpan,
+    ) -> Vec<(NaiveDate, Duration)> {
+        let usages = self
+            .usages_in_span(shown_span, category)
+            .unwrap_or_default();
+
+        let mut totals: Vec<(NaiveDate, Duration)> = Vec::new();
+
+        for (_, _, usage) in usages {
+            let date = usage.start.date();
+            let monday = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+
+            // Fill the weeks without time spent since the previous usage.
+            while let Some((last, _)) = totals.last() {
+                if *last >= monday {
+                    break;
+                }
+                let next = *last + Duration::weeks(1);
+                totals.push((next, Duration::zero()));
+            }
+
+            match totals.last_mut() {
+                Some((week, total)) if *week == monday => {
+                    *total = *total + (usage.stop - usage.start)
+                }
+                _ => totals.push((monday, usage.stop - usage.start)),
+            }
+        }
+
+        totals
+    }
+
+    /// Returns the sum of each active day's difference to the daily target within the specified
+    /// time span. Days with more time spent than the target add overtime and days with less
+    /// subtract it. If `count_inactive` is true, days of the time span without any time spent
+    /// count as full undertime, otherwise they are ignored.
+    pub fn overtime(
+        &self,
+        shown_span: ShownTimeSpan,
+        daily_target: Duration,
+        count_inactive: bool,
+    ) -> Duration {
+        let totals = self.daily_totals(shown_span, None);
+        let mut overtime = totals
+            .values()
+            .fold(Duration::zero(), |sum, total| sum + (*total - daily_target));
+
+        if count_inactive {
+            if let Some((first, last)) = self.span_dates(shown_span) {
+                let days = (last - first).num_days() + 1;
+                let inactive = days - totals.len() as i64;
+                overtime = overtime - daily_target * inactive as i32;
+            }
+        }
+
+        overtime
+    }
+
+    /// Returns the time still to be spent today to reach the target or zero if the target has
+    /// been reached. Optionally the part of the current recording that is on today is counted
+    /// as well excluding pauses.
+    pub fn remaining_to_target(&self, target: Duration, include_running: bool) -> Duration {
+        let mut spent = self.total_excluding(ShownTimeSpan::Today, &[]);
+
+        if include_running {
+            let now = self.now();
+            spent = spent + self.recorded_between(now.date().and_hms(0, 0, 0), now);
+        }
+
+        (target - spent).max(Duration::zero())
+    }
+
+    /// Returns the average length of the time usages starting on each weekday within the
+    /// specified time span from Monday to Sunday. Weekdays without time usages are `None`.
+    /// Optionally only a single category is counted. A category that doesn't exist has no usages.
+    pub fn avg_session_by_weekday(
+        &self,
+        shown_span: ShownTimeSpan,
+        category: Option<&str>,
+    ) -> [Option<Duration>; 7] {
+        let mut totals = [(Duration::zero(), 0); 7];
+        for (_, _, usage) in self
+            .usages_in_span(shown_span, category)
+            .unwrap_or_default()
+        {
+            let (total, count) = &mut totals[usage.start.weekday().num_days_from_monday() as usize];
+            *total = *total + (usage.stop - usage.start);
+            *count += 1;
+        }
+
+        totals.map(|(total, count)| (count > 0).then(|| total / count))
+    }
+
+    /// Returns the time spent on usages starting on the weekday within the specified time span.
+    /// Optionally only a single category is counted. A category that doesn't exist has no time
+    /// spent.
+    pub fn total_on_weekday(
+        &self,
+        weekday: Weekday,
+        shown_span: ShownTimeSpan,
+        category: Option<&str>,
+    ) -> Duration {
+        self.usages_in_span(shown_span, category)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, _, usage)| usage.start.weekday() == weekday)
+            .fold(Duration::zero(), |total, (_, _, usage)| {
+                total + (usage.stop - usage.start)
+            })
+    }
+
+    /// Returns the category with the longest run of consecutive days with time spent on it and
+    /// the length of the run in days. Usages are attributed to the day they start on. Ties go to
+    /// the alphabetically first category. Returns `None` if there are no time usages.
+    pub fn best_streak_category(&self) -> Option<(String, u32)> {
+        let mut best: Option<(String, u32)> = None;
+
+        for cat in self.categories_matching("*") {
+            let mut longest = 0;
+            let mut current = 0;
+            let mut previous: Option<NaiveDate> = None;
+
+            // The days are sorted, so a run continues when a day follows the previous one.
+            for date in self.daily_totals(ShownTimeSpan::All, Some(cat)).into_keys() {
+                if previous.is_some_and(|p| p + Duration::days(1) == date) {
+                    current += 1;
+                } else {
+                    current = 1;
+                }
+                longest = longest.max(current);
+                previous = Some(date);
+            }
+
+            if longest > 0 && best.as_ref().is_none_or(|(_, most)| longest > *most) {
+                best = Some((cat.clone(), longest));
+            }
+        }
+
+        best
+    }
+
+    /// Returns the number of consecutive days up to today on which the time spent on all
+    /// categories met the daily target. Usages are attributed to the day they start on. If the
+    /// target hasn't been met today yet, the count starts from yesterday.
+    pub fn goal_streak(&self, daily_target: Duration) -> u32 {
+        let totals = self.daily_totals(ShownTimeSpan::All, None);
+        let met = |date: &NaiveDate| totals.get(date).is_some_and(|total| *total >= daily_target);
+
+        let mut date = self.now().date();
+        if !met(&date) {
+            date = date.pred();
+        }
+
+        let mut streak = 0;
+        while met(&date) {
+            streak += 1;
+            date = date.pred();
+        }
+
+        streak
+    }
+
+    /// Returns the day with the most time spent on all categories within the specified time span
+    /// and the time spent on it. Ties go to the earliest day.
+    /// Returns `None` if there is no time spent in the time span.
+    pub fn busiest_day(&self, shown_span: ShownTimeSpan) -> Option<(NaiveDate, Duration)> {
+        let mut busiest: Option<(NaiveDate, Duration)> = None;
+
+        // The days are iterated from the earliest so only a strictly larger total replaces.
+        for (date, total) in self.daily_totals(shown_span, None) {
+            if busiest.is_none_or(|(_, most)| total > most) {
+                busiest = Some((date, total));
+            }
+        }
+
+        busiest
+    }
+
+    /// Returns the median time of day at which the time usages within the specified time span
+    /// start. With an even number of usages the earlier of the two middle times is returned so
+    /// that the result is always an actual start time. Optionally only a single category is
+    /// counted. Returns `None` if there are no usages.
+    pub fn median_start_time(
+        &self,
+        shown_span: ShownTimeSpan,
+        category: Option<&str>,
+    ) -> Option<NaiveTime> {
+        let mut times: Vec<NaiveTime> = self
+            .usages_in_span(shown_span, category)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(_, _, usage)| usage.start.time())
+            .collect();
+
+        if times.is_empty() {
+            return None;
+        }
+
+        times.sort();
+        Some(times[(times.len() - 1) / 2])
+    }
+
+    /// Returns the time from the first start to the last stop of each day with time spent within
+    /// the specified time span, including any gaps between the usages. Usages are attributed to
+    /// the day they start on. The days are sorted from the earliest.
+    pub fn daily_spans(&self, shown_span: ShownTimeSpan) -> Vec<(NaiveDate, Duration)> {
+        let mut bounds: BTreeMap<NaiveDate, (NaiveDateTime, NaiveDateTime)> = BTreeMap::new();
+
+        // Without a category filter there can be no error.
+        for (_, _, usage) in self.usages_in_span(shown_span, None).unwrap() {
+            let day = bounds
+                .entry(usage.start.date())
+                .or_insert((usage.start, usage.stop));
+            day.0 = day.0.min(usage.start);
+            day.1 = day.1.max(usage.stop);
+        }
+
+        bounds
+            .into_iter()
+            .map(|(date, (first, last))| (date, last - first))
+            .collect()
+    }
+
+    /// Returns how evenly the time spent within the specified time span is spread across the days
+    /// with time spent as `1 - standard deviation / mean` of the daily totals clamped between 0
+    /// and 1. Usages are attributed to the day they start on. Returns 0 if no time was spent.
+    pub fn consistency_index(&self, shown_span: ShownTimeSpan) -> f64 {
+        let totals: Vec<f64> = self
+            .daily_totals(shown_span, None)
+            .into_values()
+            .map(|d| d.num_seconds() as f64)
+            .collect();
+        if totals.is_empty() {
+            return 0.0;
+        }
+
+        let n = totals.len() as f64;
+        let mean = totals.iter().sum::<f64>() / n;
+        if mean <= 0.0 {
+            return 0.0;
+        }
+        let variance = totals.iter().map(|t| (t - mean).powi(2)).sum::<f64>() / n;
+
+        (1.0 - variance.sqrt() / mean).clamp(0.0, 1.0)
+    }
+
+    /// Returns the time spent before noon and the time spent from noon to midnight within the
+    /// specified time span. Usages are split at every noon and midnight they cross, so an overnight
+    /// usage counts towards both. Optionally only a single category is counted. A category that
+    /// doesn't exist has no time spent.
+    pub fn morning_vs_rest(
+        &self,
+        shown_span: ShownTimeSpan,
+        category: Option<&str>,
+    ) -> (Duration, Duration) {
+        let mut morning = Duration::zero();
+        let mut rest = Duration::zero();
+
+        for (_, _, usage) in self
+            .usages_in_span(shown_span, category)
+            .unwrap_or_default()
+        {
+            let mut start = usage.start;
+            while start < usage.stop {
+                let noon = start.date().and_hms(12, 0, 0);
+                let (boundary, is_morning) = if start < noon {
+                    (noon, true)
+                } else {
+                    (start.date().succ().and_hms(0, 0, 0), false)
+                };
+
+                let end = boundary.min(usage.stop);
+                if is_morning {
+                    morning = morning + (end - start);
+                } else {
+                    rest = rest + (end - start);
+                }
+                start = end;
+            }
+        }
+
+        (morning, rest)
+    }
+
+    /// Returns the planned time, the actual time spent and their signed difference for each
+    /// category with a goal or time spent within the specified time span. The planned time is the
+    /// weekly goal scaled to the length of the time span. A month is four weeks like in the
+    /// time spans and all records last from the first recorded day to today. The categories are
+    /// sorted by the largest absolute difference and then alphabetically.
+    pub fn variance_report(
+        &self,
+        shown_span: ShownTimeSpan,
+    ) -> Vec<(String, Duration, Duration, Duration)> {
+        let today = self.now().date();
+        let days = match shown_span {
+            ShownTimeSpan::All => self
+                .tracking_since()
+                .map_or(0, |since| (today - since).num_days() + 1),
+            ShownTimeSpan::Year => 365,
+            ShownTimeSpan::YearToDate => today.ordinal() as i64,
+            ShownTimeSpan::Month => 28,
+            ShownTimeSpan::Week => 7,
+            ShownTimeSpan::Yesterday | ShownTimeSpan::Today => 1,
+        };
+
+        let mut report: Vec<(String, Duration, Duration, Duration)> = self
+            .time_map
+            .keys()
+            .filter_map(|cat| {
+                // The category exists so unwrap is ok.
+                let actual = self.time_spent(cat, shown_span).unwrap();
+                let planned = self.goal(cat).map(|weekly| weekly * days as i32 / 7);
+                if planned.is_none() && actual == Duration::zero() {
+                    return None;
+                }
+                let planned = planned.unwrap_or_else(Duration::zero);
+                Some((cat.clone(), planned, actual, actual - planned))
+            })
+            .collect();
+
+        report.sort_by(|a, b| {
+            b.3.num_seconds()
+                .abs()
+                .cmp(&a.3.num_seconds().abs())
+                .then(a.0.cmp(&b.0))
+        });
+        report
+    }
+
+    /// Returns the total untracked time between the first start and the last stop of each day
+    /// with time spent within the specified time span. Usages are attributed to the day they start
+    /// on like in `daily_spans` and overlapping usages are only counted once.
+    pub fn total_idle(&self, shown_span: ShownTimeSpan) -> Duration {
+        let mut days: BTreeMap<NaiveDate, Vec<(NaiveDateTime, NaiveDateTime)>> = BTreeMap::new();
+
+        // Without a category filter there can be no error.
+        for (_, _, usage) in self.usages_in_span(shown_span, None).unwrap() {
+            days.entry(usage.start.date())
+                .or_default()
+                .push((usage.start, usage.stop));
+        }
+
+        self.daily_spans(shown_span)
+            .into_iter()
+            .map(|(date, span)| span - union_length(days.remove(&date).unwrap_or_default()))
+            .fold(Duration::zero(), |total, idle| total + idle)
+    }
+
+    /// Returns the time spent from Monday to Friday of the ISO week containing the date. Usages
+    /// are attributed to the day they start on. Optionally only a single category is counted. A
+    /// category that doesn't exist has no time spent.
+    pub fn work_week_total(&self, category: Option<&str>, week_of: NaiveDate) -> Duration {
+        let monday = week_of - Duration::days(week_of.weekday().num_days_from_monday() as i64);
+        let saturday = monday + Duration::days(5);
+
+        self.daily_totals(ShownTimeSpan::All, category)
+            .range(monday..saturday)
+            .fold(Duration::zero(), |total, (_, spent)| total + *spent)
+    }
+
+    /// Returns the time spent on Saturdays and Sundays within the specified time span. Usages are
+    /// attributed to the day they start on. Optionally only a single category is counted. A
+    /// category that doesn't exist has no time spent.
+    pub fn weekend_time(&self, shown_span: ShownTimeSpan, category: Option<&str>) -> Duration {
+        self.daily_totals(shown_span, category)
+            .into_iter()
+            .filter(|(date, _)| matches!(date.weekday(), Weekday::Sat | Weekday::Sun))
+            .fold(Duration::zero(), |total, (_, spent)| total + spent)
+    }
+
+    /// Returns a log of all time usages from the specified time span.
+    /// Optionally show logs only from a single category.
+    /// Returned log is sorted so that the newest item is at the beginning.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn time_usage_log(
+        &self,
+        shown_span: ShownTimeSpan,
+        category: Option<String>, // This being a owned string is a workaround for some borrow
+                                  // checker issues. It doesn't currently really cause any harm and
+                                  // solves an issue so it can stay.
+    ) -> Result<String> {
+        self.time_usage_log_formatted(shown_span, category, DEFAULT_DATE_FORMAT)
+    }
+
+    /// Returns the start and the end of the wall time a time span covers formatted using the
+    /// specified `strftime` style format string, or nothing if the span covers no time.
+    /// Returns an `Error` if the format string is invalid.
+    pub fn span_heading(
+        &self,
+        shown_span: ShownTimeSpan,
+        date_format: &str,
+    ) -> Result<Option<String>> {
+        check_date_format(date_format)?;
+
+        Ok(self.span_window(shown_span).map(|(start, stop)| {
+            format!(
+                "{} - {}",
+                format_datetime(start, date_format, self.locale),
+                format_datetime(stop, date_format, self.locale)
+            )
+        }))
+    }
+
+    /// Returns a log like `time_usage_log` but with dates formatted using the specified
+    /// `strftime` style format string.
+    /// Returns an `Error` if the category doesn't exist or if the format string is invalid.
+    pub fn time_usage_log_formatted(
+        &self,
+        shown_span: ShownTimeSpan,
+        category: Option<String>,
+        date_format: &str,
+    ) -> Result<String> {
+        check_date_format(date_format)?;
+
+        let category = &category;
+
+        if let Some(cat) = category {
+            if let Some(usages) = self.time_map.get(cat) {
+                let mut st = String::new();
+
+                for (i, usage) in usages.iter().enumerate() {
+                    st = self.concat_usage(st, usage, i, shown_span, cat, date_format);
+                }
+
+                Ok(st)
+            } else {
+                Err(Error::CategoryDoesntExist(cat.to_string()))
+            }
+        } else {
+            // The following bit of code is quite messy.
+            // We build a chronologically ordered log string that contains entries from EVERY
+            // category.
+            // We use the fact that every TimeUsage list is always sorted.
+
+            // Calculate the total number of TimeUsage entries in all categories.
+            // Also create a new hash map containing indicies to every category.
+            let mut index_sum = 0;
+            let mut index_map = HashMap::new();
+            for (category, val) in &self.time_map {
+                index_sum += val.len();
+                index_map.insert(category, 0usize);
+            }
+
+            // The indicies start at 0 but every time a category is accessed the mapped index is
+            // increased by one.
+
+            // Basically loop through every element in all the categories' TimeUsage lists.
+            // Every iteration look through all categories for the TimeUsage that is the oldest.
+            // Append that usage to the log.
+            // Increment that category's index by one because we don't want for it to get re-added.
+
+            let mut log = String::new();
+
+            for _ in 0..index_sum {
+                // Random category as the oldest for a starting point
+                let mut oldest = index_map.keys().next().unwrap().to_owned();
+
+                for (cat, cat_index) in &index_map {
+                    // Both of these exist so unwrap is ok.
+                    let cat_items = self.time_map.get(cat.to_owned()).unwrap();
+                    let oldest_items = self.time_map.get(oldest).unwrap();
+
+                    let cat_index = cat_index.to_owned();
+                    let oldest_index = index_map[oldest];
+
+                    // If the index is out of bounds, every TimeUsage has been already added from this
+                    // category.
+                    if cat_items.len() <= cat_index {
+                        continue;
+                    }
+
+                    // If the index is out of bounds, every TimeUsage has been already added from the
+                    // oldest category.
+                    if oldest_items.len() <= oldest_index {
+                        oldest = cat;
+                        continue;
+                    }
+
+                    // Check if the oldest item should change. Usages starting at the same time are
+                    // ordered by category name so that the log doesn't depend on hash map order.
+                    let (item, oldest_item) = (&cat_items[cat_index], &oldest_items[oldest_index]);
+                    if item < oldest_item || (item == oldest_item && *cat < oldest) {
+                        oldest = cat;
+                    }
+                }
+
+                let oldest_index = index_map[oldest];
+                let oldest_usage = self.time_map[oldest].get(oldest_index).unwrap();
+
+                // Increment the index map for the oldest category
+                index_map.insert(oldest, index_map[oldest] + 1);
+
+                log = self.concat_usage(
+                    log,
+                    oldest_usage,
+                    oldest_index,
+                    shown_span,
+                    oldest,
+                    date_format,
+                );
+            }
+
+            Ok(log)
+        }
+    }
+
+    /// Returns the time usages from the specified time span as CSV with a header row. The rows
+    /// are sorted by the starting time.
+    pub fn to_csv(&self, shown_span: ShownTimeSpan) -> String {
+        self.to_csv_filtered(shown_span, None)
+    }
+
+    /// Returns the time usages from the specified time span as CSV like `to_csv`. Optionally only
+    /// usages whose description contains the text are included. The header row is always
+    /// included.
+    pub fn to_csv_filtered(
+        &self,
+        shown_span: ShownTimeSpan,
+        desc_contains: Option<&str>,
+    ) -> String {
+        self.csv_where(shown_span, |usage| {
+            desc_contains.is_none_or(|text| usage.desc.as_deref().is_some_and(|d| d.contains(text)))
+        })
+    }
+
+    /// Returns the time usages from the specified time span that have the tag as CSV like
+    /// `to_csv`. The header row is always included.
+    pub fn export_tag_csv(&self, tag: &str, shown_span: ShownTimeSpan) -> String {
+        self.csv_where(shown_span, |usage| usage.tags.iter().any(|t| t == tag))
+    }
+
+    /// Returns the time usages from the specified time span for which `keep` returns true as CSV
+    /// like `to_csv`.
+    fn csv_where(&self, shown_span: ShownTimeSpan, keep: impl Fn(&TimeUsage) -> bool) -> String {
+        let mut csv = String::from("category,start,stop,duration_minutes,description,tags\n");
+
+        // Without a category filter there can be no error.
+        for (cat, _, usage) in self.usages_in_span(shown_span, None).unwrap() {
+            if !keep(usage) {
+                continue;
+            }
+
+            let fstring = "%Y-%m-%dT%H:%M:%S";
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                csv_escape(cat),
+                usage.start.format(fstring),
+                usage.stop.format(fstring),
+                (usage.stop - usage.start).num_minutes(),
+                csv_escape(usage.desc.as_deref().unwrap_or("")),
+                csv_escape(&usage.tags.join(";"))
+            ));
+        }
+
+        csv
+    }
+
+    /// Returns the time usages within the specified time span as CSV in the format Toggl imports.
+    /// Categories are used as projects and the email is left empty for Toggl to fill in.
+    pub fn to_toggl_csv(&self, shown_span: ShownTimeSpan) -> String {
+        let mut csv = String::from(
+            "Email,Project,Client,Description,Billable,Start date,Start time,Duration,Tags\n",
+        );
+
+        // Without a category filter there can be no error.
+        for (cat, _, usage) in self.usages_in_span(shown_span, None).unwrap() {
+            let seconds = (usage.stop - usage.start).num_seconds();
+            csv.push_str(&format!(
+                ",{},,{},{},{},{},{:02}:{:02}:{:02},{}\n",
+                csv_escape(cat),
+                csv_escape(usage.desc.as_deref().unwrap_or("")),
+                if self.is_billable(cat) { "Yes" } else { "No" },
+                usage.start.format("%Y-%m-%d"),
+                usage.start.format("%H:%M:%S"),
+                seconds / 3600,
+                seconds % 3600 / 60,
+                seconds % 60,
+                csv_escape(&usage.tags.join(","))
+            ));
+        }
+
+        csv
+    }
+
+    /// Returns the summary of the specified time span as a bordered plain text table. See
+    /// `Summary::to_text_table` for details.
+    pub fn to_text_table(&self, shown_span: ShownTimeSpan) -> String {
+        // Without a category filter there can be no error.
+        self.summary(shown_span, None).unwrap().to_text_table()
+    }
+
+    /// Returns the time usages of a category within the specified time span as an invoice grouped
+    /// by the day they start on. Every day has its usages with descriptions and a subtotal and
+    /// the invoice ends with the total. Usages without a description are flagged. Times are
+    /// rounded to the nearest minute.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn invoice(&self, category: &str, shown_span: ShownTimeSpan) -> Result<String> {
+        let time = |d| format_duration(d, Duration::minutes(1), RoundingMode::Nearest);
+        let mut days: BTreeMap<NaiveDate, Vec<&TimeUsage>> = BTreeMap::new();
+        for (_, _, usage) in self.usages_in_span(shown_span, Some(category))? {
+            days.entry(usage.start.date()).or_default().push(usage);
+        }
+
+        let mut invoice = format!("Invoice: {}\n", category);
+        let mut total = Duration::zero();
+        for (date, usages) in days {
+            invoice.push_str(&format!("\n{}\n", date.format("%Y-%m-%d")));
+
+            let mut subtotal = Duration::zero();
+            for usage in usages {
+                let spent = usage.stop - usage.start;
+                let desc = match usage.desc.as_deref().map(str::trim) {
+                    Some(desc) if !desc.is_empty() => desc,
+                    _ => "[NO DESCRIPTION]",
+                };
+                invoice.push_str(&format!(
+                    "  {} - {}  {}  {}\n",
+                    usage.start.format("%H:%M"),
+                    usage.stop.format("%H:%M"),
+                    time(spent),
+                    desc
+                ));
+                subtotal = subtotal + spent;
+            }
+
+            invoice.push_str(&format!("  Subtotal: {}\n", time(subtotal)));
+            total = total + subtotal;
+        }
+
+        invoice.push_str(&format!("\nTotal: {}\n", time(total)));
+        Ok(invoice)
+    }
+
+    /// Returns the hours spent on each category in each month of the year as CSV with a category
+    /// per row and a month per column. Usages are attributed to the month they start in. Hours
+    /// are rounded to two decimals. The categories are ordered like in `categories`.
+    pub fn to_pivot_csv(&self, year: i32) -> String {
+        let months = [
+            "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+        ];
+        let mut csv = format!("category,{}\n", months.join(","));
+
+        for cat in self.categories() {
+            let mut spent = [Duration::zero(); 12];
+            for usage in &self.time_map[cat] {
+                if usage.start.year() == year {
+                    let month = usage.start.month0() as usize;
+                    spent[month] = spent[month] + (usage.stop - usage.start);
+                }
+            }
+
+            let cells: Vec<String> = spent
+                .iter()
+                .map(|d| {
+                    let hours = d.num_seconds() as f64 / 3600.0;
+                    ((hours * 100.0).round() / 100.0).to_string()
+                })
+                .collect();
+            csv.push_str(&format!("{},{}\n", csv_escape(cat), cells.join(",")));
+        }
+
+        csv
+    }
+
+    /// Returns a text calendar of a month with a row for each week and a column for each weekday
+    /// starting from the day set with `set_week_start`. Every day shows its number and the hours
+    /// spent on it rounded to a tenth of an hour. The hours of days without time spent are left
+    /// blank. Usages are attributed to the day they start on. Month and weekday names use the
+    /// locale set with `set_locale`.
+    /// Panics if the month isn't between 1 and 12.
+    pub fn month_calendar(&self, year: i32, month: u32) -> String {
+        let first = NaiveDate::from_ymd(year, month, 1);
+        let totals = self.daily_totals(ShownTimeSpan::All, None);
+
+        let mut days = Vec::new();
+        let mut date = first;
+        while date.month() == month {
+            let hours = match totals.get(&date) {
+                Some(spent) => format!("{:.1}", spent.num_seconds() as f64 / 3600.0),
+                None => String::new(),
+            };
+            days.push((date.day(), hours));
+            date = date.succ();
+        }
+        // Cells are widened to fit the longest total so that the columns stay aligned.
+        let hours_width = days.iter().map(|(_, h)| h.len()).max().unwrap_or(0).max(5);
+        let cell_width = hours_width + 3;
+
+        let offset = (first.weekday().num_days_from_monday() + 7
+            - self.week_start.num_days_from_monday())
+            % 7;
+
+        let month_start = first.and_hms(0, 0, 0);
+        let mut calendar = format!("{}\n", format_datetime(month_start, "%B %Y", self.locale));
+        // The weekday names are taken from the days of the first row.
+        let headers: Vec<String> = (0..7)
+            .map(|i| {
+                let day = month_start + Duration::days(i - offset as i64);
+                format!(
+                    "{:>width$}",
+                    format_datetime(day, "%a", self.locale),
+                    width = cell_width
+                )
+            })
+            .collect();
+        calendar.push_str(headers.join(" ").trim_end());
+        calendar.push('\n');
+
+        let mut cells = vec![" ".repeat(cell_width); offset as usize];
+        for (day, hours) in days {
+            cells.push(format!("{:>2} {:>width$}", day, hours, width = hours_width));
+        }
+
+        for week in cells.chunks(7) {
+            calendar.push_str(week.join(" ").trim_end());
+            calendar.push('\n');
+        }
+        calendar
+    }
+
+    /// Returns the time usages started on the date as an HTML table sorted by the starting time.
+    pub fn day_to_html(&self, date: NaiveDate) -> String {
+        let mut html = String::from(
+            "<table>\n<tr><th>Start</th><th>Stop</th><th>Duration</th><th>Category</th><th>Description</th></tr>\n",
+        );
+
+        // Without a category filter there can be no error.
+        for (cat, _, usage) in self.usages_in_span(ShownTimeSpan::All, None).unwrap() {
+            if usage.start.date() != date {
+                continue;
+            }
+
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                usage.start.format("%H:%M"),
+                usage.stop.format("%H:%M"),
+                format_duration(
+                    usage.stop - usage.start,
+                    Duration::minutes(1),
+                    RoundingMode::Nearest
+                ),
+                html_escape(cat),
+                html_escape(usage.desc.as_deref().unwrap_or(""))
+            ));
+        }
+
+        html.push_str("</table>\n");
+        html
+    }
+
+    /// Returns the time usages started on the date as a GraphViz DOT graph. Every usage is a node
+    /// labeled with its category and times and the nodes are connected in chronological order.
+    /// Every category has its own fill color.
+    pub fn to_dot(&self, date: NaiveDate) -> String {
+        let mut categories: Vec<&String> = self.time_map.keys().collect();
+        categories.sort();
+
+        let mut dot = String::from(
+            "digraph timeline {\n    rankdir=LR;\n    node [shape=box, style=filled];\n",
+        );
+        let mut nodes = 0;
+
+        // Without a category filter there can be no error.
+        for (cat, _, usage) in self.usages_in_span(ShownTimeSpan::All, None).unwrap() {
+            if usage.start.date() != date {
+                continue;
+            }
+
+            let color = categories.iter().position(|c| *c == cat).unwrap() % DOT_COLORS.len();
+            dot.push_str(&format!(
+                "    u{} [label=\"{}\\n{} - {}\", fillcolor={}];\n",
+                nodes,
+                dot_escape(cat),
+                usage.start.format("%H:%M"),
+                usage.stop.format("%H:%M"),
+                DOT_COLORS[color]
+            ));
+            nodes += 1;
+        }
+
+        for i in 1..nodes {
+            dot.push_str(&format!("    u{} -> u{};\n", i - 1, i));
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Writes all categories and time usages to a new SQLite database in `categories` and
+    /// `usages` tables. The file must not exist or must be empty so that no existing data is
+    /// overwritten.
+    /// Returns an `Error` if the file isn't empty or if the database can't be written.
+    #[cfg(feature = "sqlite")]
+    pub fn to_sqlite(&self, path: &std::path::Path) -> Result<()> {
+        let db_error = |e: rusqlite::Error| Error::Database(e.to_string());
+
+        if std::fs::metadata(path).is_ok_and(|m| m.len() > 0) {
+            return Err(Error::ExportTargetNotEmpty(path.display().to_string()));
+        }
+
+        let mut conn = rusqlite::Connection::open(path).map_err(db_error)?;
+        // Foreign keys can't be enabled within a transaction.
+        conn.execute_batch("PRAGMA foreign_keys = ON;")
+            .map_err(db_error)?;
+        let tx = conn.transaction().map_err(db_error)?;
+        tx.execute_batch(
+            "CREATE TABLE categories (name TEXT PRIMARY KEY);
+            CREATE TABLE usages (
+                category TEXT NOT NULL REFERENCES categories(name),
+                id INTEGER NOT NULL,
+                start TEXT NOT NULL,
+                stop TEXT NOT NULL,
+                duration_seconds INTEGER NOT NULL,
+                description TEXT,
+                tags TEXT NOT NULL,
+                reference TEXT,
+                PRIMARY KEY (category, id)
+            );",
+        )
+        .map_err(db_error)?;
+
+        let fstring = "%Y-%m-%dT%H:%M:%S";
+        for (cat, usages) in &self.time_map {
+            tx.execute("INSERT INTO categories (name) VALUES (?1)", [cat])
+                .map_err(db_error)?;
+
+            for (i, usage) in usages.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO usages VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    rusqlite::params![
+                        cat,
+                        i,
+                        usage.start.format(fstring).to_string(),
+                        usage.stop.format(fstring).to_string(),
+                        (usage.stop - usage.start).num_seconds(),
+                        usage.desc,
+                        usage.tags.join(";"),
+                        usage.reference,
+                    ],
+                )
+                .map_err(db_error)?;
+            }
+        }
+
+        tx.commit().map_err(db_error)
+    }
+
+    /// Returns the names of all categories as a JSON array without any time usages. The categories
+    /// are ordered like in `categories`.
+    pub fn export_structure(&self) -> String {
+        // A list of strings can always be serialized.
+        serde_json::to_string(&self.categories()).unwrap()
+    }
+
+    /// Creates the categories named in a JSON array such as `["work", "study"]`. Categories that
+    /// already exist are skipped. Returns the number of created categories.
+    /// Returns an `Error` if the data isn't an array of strings or if the maximum number of
+    /// categories is reached. Categories before the failing one are still created.
+    pub fn import_structure(&mut self, json: &str) -> Result<usize> {
+        let names: Vec<String> =
+            serde_json::from_str(json).map_err(|e| Error::InvalidImport(e.to_string()))?;
+
+        let mut created = 0;
+        for result in self.add_categories(&names) {
+            match result {
+                Ok(()) => created += 1,
+                Err(Error::CategoryExists(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(created)
+    }
+
+    /// Imports time usages from a JSON array of entries such as
+    /// `[{"category": "work", "start": "2022-01-01T09:00:00", "stop": "2022-01-01T10:00:00",
+    /// "desc": "Meeting"}]`. Missing categories are created. Returns the number of imported
+    /// entries. Nothing is imported if any of the entries is invalid.
+    /// Returns an `Error` if the data isn't an array, if an entry is invalid or if creating the
+    /// categories would exceed the maximum number of categories.
+    pub fn import_json_entries(&mut self, json: &str) -> Result<usize> {
+        let values: Vec<serde_json::Value> =
+            serde_json::from_str(json).map_err(|e| Error::InvalidImport(e.to_string()))?;
+
+        self.import_entries(values)
+    }
+
+    /// Returns the time usages within the specified time span as JSON wrapped in an envelope
+    /// with the schema `EXPORT_SCHEMA` and the moment the export was generated. Unlike the save
+    /// format the envelope is meant to stay stable for other tools.
+    pub fn to_export_json(&self, shown_span: ShownTimeSpan) -> String {
+        // Without a category filter there can be no error.
+        let usages: Vec<serde_json::Value> = self
+            .usages_in_span(shown_span, None)
+            .unwrap()
+            .into_iter()
+            .map(|(cat, _, usage)| {
+                serde_json::json!({
+                    "category": cat,
+                    "start": usage.start,
+                    "stop": usage.stop,
+                    "desc": usage.desc,
+                    "tags": usage.tags,
+                })
+            })
+            .collect();
+
+        serde_json::json!({
+            "schema": EXPORT_SCHEMA,
+            "generated_at": self.now(),
+            "usages": usages,
+        })
+        .to_string()
+    }
+
+    /// Imports time usages from JSON created by `to_export_json` like `import_json_entries`.
+    /// Returns an `Error` if the schema of the export isn't supported or if the export is
+    /// invalid.
+    pub fn import_export_json(&mut self, json: &str) -> Result<usize> {
+        let mut envelope: serde_json::Value =
+            serde_json::from_str(json).map_err(|e| Error::InvalidImport(e.to_string()))?;
+
+        match envelope["schema"].as_str() {
+            Some(EXPORT_SCHEMA) => {}
+            Some(schema) => return Err(Error::UnsupportedSchema(schema.to_string())),
+            None => return Err(Error::InvalidImport("missing schema".to_string())),
+        }
+
+        match envelope["usages"].take() {
+            serde_json::Value::Array(values) => self.import_entries(values),
+            _ => Err(Error::InvalidImport("usages isn't an array".to_string())),
+        }
+    }
+
+    /// Imports time usages from JSON values of `ImportEntry`. See `import_json_entries`.
+    fn import_entries(&mut self, values: Vec<serde_json::Value>) -> Result<usize> {
+        let mut entries = Vec::new();
+        for (i, value) in values.into_iter().enumerate() {
+            let entry: ImportEntry = serde_json::from_value(value)
+                .map_err(|e| Error::InvalidImportEntry(i, e.to_string()))?;
+            if entry.stop < entry.start {
+                return Err(Error::InvalidImportEntry(
+                    i,
+                    "stop is before start".to_string(),
+                ));
+            }
+            entries.push(entry);
+        }
+
+        if let Some(max) = self.max_categories {
+            let mut new: Vec<&String> = entries
+                .iter()
+                .map(|e| &e.category)
+                .filter(|c| !self.time_map.contains_key(*c))
+                .collect();
+            new.sort();
+            new.dedup();
+            if !new.is_empty() && self.time_map.len() + new.len() > max {
+                return Err(Error::CategoryLimitReached(max));
+            }
+        }
+
+        // Adding can still fail, for example if a description is required, so the entries are
+        // added to a copy that replaces the book only once all of them have been added.
+        let mut book = self.clone();
+        let count = entries.len();
+        for entry in entries {
+            book.time_map.entry(entry.category.clone()).or_default();
+            book.add_time_usage_with(
+                &entry.category,
+                entry.start,
+                entry.stop,
+                UsageDetails {
+                    desc: entry.desc,
+                    tags: entry.tags,
+                    ..Default::default()
+                },
+            )?;
+        }
+
+        *self = book;
+        Ok(count)
+    }
+
+    /// Returns the category and id of every `TimeUsage` that starts after the current moment.
+    /// These are most likely caused by the system clock being wrong when they were recorded.
+    /// The returned usages are sorted by category and id.
+    pub fn future_usages(&self) -> Vec<(String, usize)> {
+        let now = self.now();
+        let mut future = Vec::new();
+
+        for (cat, usages) in &self.time_map {
+            for (i, usage) in usages.iter().enumerate() {
+                if usage.start > now {
+                    future.push((cat.clone(), i));
+                }
+            }
+        }
+
+        future.sort();
+        future
+    }
+
+    /// Returns the category and id of every `TimeUsage` whose start and stop are the same.
+    /// These are usually mistakes, for example caused by stopping twice.
+    /// The returned usages are sorted by category and id.
+    pub fn zero_length_usages(&self) -> Vec<(String, usize)> {
+        let mut zero_length = Vec::new();
+
+        for (cat, usages) in &self.time_map {
+            for (i, usage) in usages.iter().enumerate() {
+                if usage.start == usage.stop {
+                    zero_length.push((cat.clone(), i));
+                }
+            }
+        }
+
+        zero_length.sort();
+        zero_length
+    }
+
+    /// Returns the category and ids of every pair of `TimeUsage`s in the same category that
+    /// overlap. The pairs are sorted by category and ids.
+    pub fn overlapping_usages(&self) -> Vec<(String, usize, usize)> {
+        let mut overlapping = Vec::new();
+
+        for (cat, usages) in &self.time_map {
+            for (i, a) in usages.iter().enumerate() {
+                // Usages are sorted so only the later ones starting before a stops can overlap.
+                for (j, b) in usages.iter().enumerate().skip(i + 1) {
+                    if b.start >= a.stop {
+                        break;
+                    }
+                    overlapping.push((cat.clone(), i, j));
+                }
+            }
+        }
+
+        overlapping.sort();
+        overlapping
+    }
+
+    /// Resolves the overlap of two `TimeUsage`s in a category with the strategy. The usage
+    /// starting first is considered to be the first one regardless of the order of the ids.
+    /// Trimming the second usage that is fully within the first leaves it with no length.
+    /// Merging combines the descriptions, subtasks and tags of both usages and keeps the reference
+    /// of the first usage if it has one.
+    /// Returns an `Error` if the category or either of the usages doesn't exist or if the usages
+    /// don't overlap.
+    pub fn resolve_overlap(
+        &mut self,
+        category: &str,
+        id_a: usize,
+        id_b: usize,
+        strategy: OverlapStrategy,
+    ) -> Result<()> {
+        let usages = self
+            .time_map
+            .get_mut(category)
+            .ok_or_else(|| Error::CategoryDoesntExist(category.to_string()))?;
+
+        for id in [id_a, id_b] {
+            if usages.len() <= id {
+                return Err(Error::TimeUsageDoesntExist(id));
+            }
+        }
+
+        let (first, second) = if id_a <= id_b {
+            (id_a, id_b)
+        } else {
+            (id_b, id_a)
+        };
+        if first == second
+            || usages[second].start >= usages[first].stop
+            || usages[first].start >= usages[second].stop
+        {
+            return Err(Error::UsagesDontOverlap(id_a, id_b));
+        }
+
+        match strategy {
+            OverlapStrategy::TrimFirst => usages[first].stop = usages[second].start,
+            OverlapStrategy::TrimSecond => {
+                usages[second].start = usages[first].stop.min(usages[second].stop);
+            }
+            OverlapStrategy::Merge => {
+                let other = usages.remove(second);
+                let usage = &mut usages[first];
+
+                usage.stop = usage.stop.max(other.stop);
+                usage.desc = match (usage.desc.take(), other.desc) {
+                    (Some(a), Some(b)) if a != b => Some(format!("{}; {}", a, b)),
+                    (a, b) => a.or(b),
+                };
+                usage.reference = usage.reference.take().or(other.reference);
+                usage.subtasks.extend(other.subtasks);
+                for tag in other.tags {
+                    if !usage.tags.contains(&tag) {
+                        usage.tags.push(tag);
+                    }
+                }
+            }
+        }
+
+        usages.sort();
+        Ok(())
+    }
+
+    /// Merges the time usages of a category that are at most `max_gap` apart into single usages
+    /// covering them. Overlapping usages are always merged. Descriptions are combined as specified
+    /// by `desc_merge`, tags and subtasks are combined and the first reference is kept. Returns
+    /// the number of usages removed by merging.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn coalesce(
+        &mut self,
+        category: &str,
+        max_gap: Duration,
+        desc_merge: DescMerge,
+    ) -> Result<usize> {
+        let usages = self
+            .time_map
+            .get_mut(category)
+            .ok_or_else(|| Error::CategoryDoesntExist(category.to_string()))?;
+
+        let before = usages.len();
+        let mut groups: Vec<Vec<TimeUsage>> = Vec::new();
+        // The usages are sorted by start so a usage can only join the latest group.
+        for usage in usages.drain(..) {
+            match groups.last_mut() {
+                Some(group)
+                    if usage.start - group.iter().map(|u| u.stop).max().unwrap() <= max_gap =>
+                {
+                    group.push(usage)
+                }
+                _ => groups.push(vec![usage]),
+            }
+        }
+
+        for group in groups {
+            let mut descs: Vec<String> = Vec::new();
+            let mut parts = group.into_iter();
+            // Every group has at least one usage.
+            let mut merged = parts.next().unwrap();
+            let first_desc = merged.desc.clone();
+            descs.extend(merged.desc.take().filter(|d| !d.trim().is_empty()));
+
+            for usage in parts {
+                merged.stop = merged.stop.max(usage.stop);
+                merged.reference = merged.reference.take().or(usage.reference);
+                merged.subtasks.extend(usage.subtasks);
+                for tag in usage.tags {
+                    if !merged.tags.contains(&tag) {
+                        merged.tags.push(tag);
+                    }
+                }
+                if let Some(desc) = usage.desc.filter(|d| !d.trim().is_empty()) {
+                    if desc_merge == DescMerge::Join || !descs.contains(&desc) {
+                        descs.push(desc);
+                    }
+                }
+            }
+
+            merged.desc = match desc_merge {
+                DescMerge::First => first_desc,
+                _ if descs.is_empty() => None,
+                _ => Some(descs.join("; ")),
+            };
+            usages.push(merged);
+        }
+
+        Ok(before - usages.len())
+    }
+
+    /// Returns a start and a stop event for every time usage within the specified time span
+    /// sorted by time. At the same moment stops come before starts so that a switch between
+    /// categories reads in order. Otherwise events at the same moment are sorted by category.
+    pub fn event_timeline(&self, shown_span: ShownTimeSpan) -> Vec<TimelineEvent> {
+        // Without a category filter there can be no error.
+        let mut events: Vec<TimelineEvent> = self
+            .usages_in_span(shown_span, None)
+            .unwrap()
+            .into_iter()
+            .flat_map(|(cat, _, usage)| {
+                [
+                    TimelineEvent::Start {
+                        category: cat.to_string(),
+                        at: usage.start,
+                    },
+                    TimelineEvent::Stop {
+                        category: cat.to_string(),
+                        at: usage.stop,
+                    },
+                ]
+            })
+            .collect();
+
+        events.sort_by_key(|event| {
+            let (is_start, category) = match event {
+                TimelineEvent::Start { category, .. } => (true, category.clone()),
+                TimelineEvent::Stop { category, .. } => (false, category.clone()),
+            };
+            (event.at(), is_start, category)
+        });
+        events
+    }
+
+    /// Returns the descriptions shared by more than one `TimeUsage` with the category and id of
+    /// every usage having it. Only identical descriptions are grouped and usages without a
+    /// description are ignored. The groups are sorted by the description and the usages by
+    /// category and id.
+    pub fn description_groups(&self) -> Vec<(String, Vec<(String, usize)>)> {
+        let mut groups: BTreeMap<&str, Vec<(String, usize)>> = BTreeMap::new();
+
+        for (cat, usages) in &self.time_map {
+            for (i, usage) in usages.iter().enumerate() {
+                if let Some(desc) = &usage.desc {
+                    groups.entry(desc).or_default().push((cat.clone(), i));
+                }
+            }
+        }
+
+        groups
+            .into_iter()
+            .filter(|(_, members)| members.len() > 1)
+            .map(|(desc, mut members)| {
+                members.sort();
+                (desc.to_string(), members)
+            })
+            .collect()
+    }
+
+    /// Returns true if serializing the `TimeBook` and deserializing it again results in exactly
+    /// the same data.
+    pub fn roundtrip_ok(&self) -> bool {
+        let original = match serde_json::to_value(self) {
+            Ok(value) => value,
+            Err(_) => return false,
+        };
+
+        let parsed: TimeBook = match serde_json::from_value(original.clone()) {
+            Ok(book) => book,
+            Err(_) => return false,
+        };
+
+        serde_json::to_value(&parsed).is_ok_and(|value| value == original)
+    }
+
+    /// Compares the time usages of this book to another book. Usages are matched by their category
+    /// and starting time. The differences are sorted by category and starting time.
+    pub fn diff(&self, other: &TimeBook) -> BookDiff {
+        let mut diff = BookDiff::default();
+        let empty = Vec::new();
+
+        let mut categories: Vec<&String> =
+            self.time_map.keys().chain(other.time_map.keys()).collect();
+        categories.sort();
+        categories.dedup();
+
+        for cat in categories {
+            let ours = self.time_map.get(cat).unwrap_or(&empty);
+            let theirs = other.time_map.get(cat).unwrap_or(&empty);
+
+            // Both lists are sorted so they can be walked through side by side.
+            let (mut i, mut j) = (0, 0);
+            while i < ours.len() || j < theirs.len() {
+                let ordering = match (ours.get(i), theirs.get(j)) {
+                    (Some(a), Some(b)) => a.start.cmp(&b.start),
+                    (Some(_), None) => std::cmp::Ordering::Less,
+                    _ => std::cmp::Ordering::Greater,
+                };
+
+                match ordering {
+                    std::cmp::Ordering::Less => {
+                        diff.removed.push((cat.clone(), ours[i].start));
+                        i += 1;
+                    }
+                    std::cmp::Ordering::Greater => {
+                        diff.added.push((cat.clone(), theirs[j].start));
+                        j += 1;
+                    }
+                    std::cmp::Ordering::Equal => {
+                        if !TimeBook::same_contents(&ours[i], &theirs[j]) {
+                            diff.modified.push((cat.clone(), ours[i].start));
+                        }
+                        i += 1;
+                        j += 1;
+                    }
+                }
+            }
+        }
+
+        diff
+    }
+
+    /// Returns true if all fields of the usages are equal. Unlike `==` this doesn't only compare
+    /// the starting times.
+    fn same_contents(a: &TimeUsage, b: &TimeUsage) -> bool {
+        a.start == b.start
+            && a.stop == b.stop
+            && a.desc == b.desc
+            && a.subtasks == b.subtasks
+            && a.tags == b.tags
+            && a.reference == b.reference
+    }
+
+    /// Returns a diary of all time usages from the specified time span grouped by day.
+    /// Optionally show usages only from a single category.
+    /// Days are sorted so that the newest day is at the beginning while the usages within a day
+    /// are sorted so that the oldest is first.
+    /// Returns an `Error` if the category doesn't exist.
+    pub fn diary(&self, shown_span: ShownTimeSpan, category: Option<String>) -> Result<String> {
+        let usages = self.usages_in_span(shown_span, category.as_deref())?;
+
+        let mut days: Vec<String> = Vec::new();
+        let mut current_date = None;
+
+        for (cat, id, usage) in usages {
+            let date = usage.start.date();
+
+            if current_date != Some(date) {
+                current_date = Some(date);
+                days.push(format!("== {} ==\n", date.format("%Y-%m-%d")));
+            }
+
+            let stop_fstring = if usage.stop.date() == date {
+                "%H:%M"
+            } else {
+                "%-d/%-m/%Y %H:%M"
+            };
+
+            // There is always a day since one was pushed above.
+            let day = days.last_mut().unwrap();
+            day.push_str(&format!(
+                "{} - {}: {} (ID: {})\n",
+                usage.start.format("%H:%M"),
+                usage.stop.format(stop_fstring),
+                cat,
+                id
+            ));
+            if let Some(d) = &usage.desc {
+                day.push_str(&format!("\t{}\n", d));
+            }
+            for subtask in &usage.subtasks {
+                day.push_str(&format!("\t- {}\n", subtask));
+            }
+            if !usage.tags.is_empty() {
+                day.push_str(&format!("\tTags: {}\n", usage.tags.join(", ")));
+            }
+        }
+
+        days.reverse();
+        Ok(days.join("\n"))
+    }
+
+    /// Returns the date of the earliest start of all time usages or `None` if there are no time
+    /// usages.
+    pub fn tracking_since(&self) -> Option<NaiveDate> {
+        // Every category is sorted so its first usage starts the earliest.
+        self.time_map
+            .values()
+            .filter_map(|usages| usages.first())
+            .map(|usage| usage.start.date())
+            .min()
+    }
+
+    /// Returns the category time was spent on at the moment with the start and the stop of that
+    /// time. A recording in progress counts from its start until now, excluding pauses, and is
+    /// returned without a stop. If several usages contain the moment the one started last is
+    /// returned. Returns `None` if no time was spent at the moment.
+    pub fn activity_at(
+        &self,
+        at: NaiveDateTime,
+    ) -> Option<(&str, NaiveDateTime, Option<NaiveDateTime>)> {
+        if let Ok((cat, start)) = self.status() {
+            let paused = self
+                .current_pauses
+                .iter()
+                .any(|(paused, resumed)| *paused <= at && at < *resumed)
+                || self.paused_at.is_some_and(|paused| paused <= at);
+            if start <= at && at <= self.now() && !paused {
+                return Some((cat, start, None));
+            }
+        }
+
+        // Without a category filter there can be no error.
+        self.usages_in_span(ShownTimeSpan::All, None)
+            .unwrap()
+            .into_iter()
+            .rev()
+            .find(|(_, _, usage)| usage.start <= at && at < usage.stop)
+            .map(|(cat, _, usage)| (cat, usage.start, Some(usage.stop)))
+    }
+
+    /// Returns the latest stop of all time usages or `None` if there are no time usages.
+    pub fn last_activity(&self) -> Option<NaiveDateTime> {
+        self.time_map
+            .values()
+            .flatten()
+            .map(|usage| usage.stop)
+            .max()
+    }
+
+    /// Returns the category of the time usage that stopped last or `None` if there are no time
+    /// usages.
+    pub fn last_category(&self) -> Option<&str> {
+        self.time_map
+            .iter()
+            .flat_map(|(cat, usages)| usages.iter().map(move |usage| (usage.stop, cat)))
+            .max()
+            .map(|(_, cat)| cat.as_str())
+    }
+
+    /// Returns the time elapsed since the last activity.
+    /// Returns `None` if time is currently being recorded or if there are no time usages.
+    pub fn idle_since(&self) -> Option<Duration> {
+        if self.current_cat_start.is_some() {
+            return None;
+        }
+
+        self.last_activity().map(|last| self.now() - last)
+    }
+
+    /// Returns the `n` most recently started time usages across all categories with their
+    /// category and id. The newest usage is first.
+    pub fn recent(&self, n: usize) -> Vec<(&str, usize, &TimeUsage)> {
+        let mut found = Vec::new();
+
+        // Every category is sorted, so only the last n usages of each can be among the n newest.
+        for (cat, usages) in &self.time_map {
+            let first = usages.len().saturating_sub(n);
+            for (i, usage) in usages.iter().enumerate().skip(first) {
+                found.push((cat.as_str(), i, usage));
+            }
+        }
+
+        found.sort_by(|a, b| b.2.start.cmp(&a.2.start).then(a.0.cmp(b.0)));
+        found.truncate(n);
+        found
+    }
+
+    /// Returns every time usage across all categories that overlaps the interval from `from` to
+    /// `to`, `to` excluded, with its category and id sorted by start.
+    pub fn usages_overlapping(
+        &self,
+        from: NaiveDateTime,
+        to: NaiveDateTime,
+    ) -> Vec<(&str, usize, &TimeUsage)> {
+        let mut found = Vec::new();
+
+        for (cat, usages) in &self.time_map {
+            for (i, usage) in usages.iter().enumerate() {
+                if usage.start < to && usage.stop > from {
+                    found.push((cat.as_str(), i, usage));
+                }
+            }
+        }
+
+        found.sort_by(|a, b| a.2.start.cmp(&b.2.start).then(a.0.cmp(b.0)));
+        found
+    }
+
+    /// Returns the total time spent on each day within the specified time span. Usages are
+    /// attributed to the day they start on and days without time spent are left out.
+    /// Optionally only a single category is counted. A category that doesn't exist has no days.
+    pub fn daily_totals(
+        &self,
+        shown_span: ShownTimeSpan,
+        category: Option<&str>,
+    ) -> BTreeMap<NaiveDate, Duration> {
+        let mut totals = BTreeMap::new();
+
+        for (_, _, usage) in self
+            .usages_in_span(shown_span, category)
+            .unwrap_or_default()
+        {
+            let total = totals
+                .entry(usage.start.date())
+                .or_insert_with(Duration::zero);
+            *total = *total + (usage.stop - usage.start);
+        }
+
+        totals
+    }
+
+    /// Returns the earliest start and the latest stop of the time usages on each day within the
+    /// specified time span, sorted from the oldest day. Usages are attributed to the day they
+    /// start on and days without time spent are left out.
+    pub fn daily_windows(
+        &self,
+        shown_span: ShownTimeSpan,
+    ) -> Vec<(NaiveDate, NaiveTime, NaiveTime)> {
+        let mut windows: BTreeMap<NaiveDate, (NaiveDateTime, NaiveDateTime)> = BTreeMap::new();
+
+        // Without a category filter there can be no error.
+        for (_, _, usage) in self.usages_in_span(shown_span, None).unwrap() {
+            let window = windows
+                .entry(usage.start.date())
+                .or_insert((usage.start, usage.stop));
+            window.0 = window.0.min(usage.start);
+            window.1 = window.1.max(usage.stop);
+        }
+
+        windows
+            .into_iter()
+            .map(|(date, (start, stop))| (date, start.time(), stop.time()))
+            .collect()
+    }
+
+    /// Returns the total time spent on today and each of the six days before it, ordered from the
+    /// oldest day. Usages are attributed to the day they start on and days without time spent have
+    /// a total of zero.
+    pub fn last_7_days(&self) -> [(NaiveDate, Duration); 7] {
+        let today = self.now().date();
+        let totals = self.daily_totals(ShownTimeSpan::All, None);
+
+        let mut days = [(today, Duration::zero()); 7];
+        for (i, day) in days.iter_mut().enumerate() {
+            let date = today - Duration::days(6 - i as i64);
+            *day = (
+                date,
+                totals.get(&date).copied().unwrap_or_else(Duration::zero),
+            );
+        }
+        days
+    }
+
+    /// Returns the total time spent in each day, week or month within the specified time span as
+    /// the first day of the period and the total, sorted from the oldest period. Usages are
+    /// attributed to the period they start in and periods without time spent are left out.
+    /// Optionally only a single category is counted. A category that doesn't exist has no periods.
+    pub fn bucketed_totals(
+        &self,
+        category: Option<&str>,
+        shown_span: ShownTimeSpan,
+        bucket: BucketSize,
+    ) -> Vec<(NaiveDate, Duration)> {
+        let mut totals: BTreeMap<NaiveDate, Duration> = BTreeMap::new();
+
+        for (date, spent) in self.daily_totals(shown_span, category) {
+            let bucket_start = match bucket {
+                BucketSize::Day => date,
+                BucketSize::Week => {
+                    date - Duration::days(date.weekday().num_days_from_monday() as i64)
+                }
+                BucketSize::Month => NaiveDate::from_ymd(date.year(), date.month(), 1),
+            };
+            let total = totals.entry(bucket_start).or_insert_with(Duration::zero);
+            *total = *total + spent;
+        }
+
+        totals.into_iter().collect()
+    }
+
+    /// Sorts categories so that pinned categories are first in the order they were pinned and the
+    /// rest are sorted alphabetically.
+    fn sort_categories(&self, categories: &mut [&String]) {
+        categories.sort_by_key(|c| {
+            (
+                self.favorites
+                    .iter()
+                    .position(|f| f == *c)
+                    .unwrap_or(usize::MAX),
+                c.to_string(),
+            )
+        });
+    }
+
+    /// Returns the first and the last day of the specified time span. The last day is today
+    /// except for `Yesterday`. For `All` the first day is the day of the first time usage.
+    /// Returns `None` for `All` if there are no time usages.
+    fn span_dates(&self, shown_span: ShownTimeSpan) -> Option<(NaiveDate, NaiveDate)> {
+        let today = self.now().date();
+
+        let first = match shown_span {
+            ShownTimeSpan::All => self
+                .time_map
+                .values()
+                .flatten()
+                .map(|usage| usage.start.date())
+                .min()?
+                .min(today),
+            ShownTimeSpan::Year => today - Duration::days(365),
+            ShownTimeSpan::YearToDate => NaiveDate::from_ymd(today.year(), 1, 1),
+            ShownTimeSpan::Month => today - Duration::weeks(4),
+            ShownTimeSpan::Week => today - Duration::weeks(1),
+            ShownTimeSpan::Yesterday => return Some((today.pred(), today.pred())),
+            ShownTimeSpan::Today => today,
+        };
+
+        Some((first, today))
+    }
+
+    /// Returns the category, id and the usage itself of every `TimeUsage` within the specified
+    /// time span sorted by the starting time. Usages with the same starting time are sorted by
+    /// their category.
+    /// Optionally only usages from a single category are returned.
+    /// Returns an `Error` if the category doesn't exist.
+    fn usages_in_span(
+        &self,
+        shown_span: ShownTimeSpan,
+        category: Option<&str>,
+    ) -> Result<Vec<(&str, usize, &TimeUsage)>> {
+        let mut found = Vec::new();
+
+        for (cat, usages) in &self.time_map {
+            if category.is_some_and(|c| c != cat) {
+                continue;
+            }
+
+            for (i, usage) in usages.iter().enumerate() {
+                if self.in_time_span(usage.start, shown_span) {
+                    found.push((cat.as_str(), i, usage));
+                }
+            }
+        }
+
+        if let Some(c) = category {
+            if !self.time_map.contains_key(c) {
+                return Err(Error::CategoryDoesntExist(c.to_string()));
+            }
+        }
+
+        found.sort_by(|a, b| a.2.start.cmp(&b.2.start).then(a.0.cmp(b.0)));
+        Ok(found)
+    }
+
+    /// Concatenates a time usage to the beginning of the specified string and returns the result.
+    fn concat_usage(
+        &self,
+        mut s: String,
+        usage: &TimeUsage,
+        usage_id: usize,
+        shown_span: ShownTimeSpan,
+        cat: &str,
+        fstring: &str,
+    ) -> String {
+        if self.in_time_span(usage.start, shown_span) {
+            let mut elem = format!(
+                "{} - {}: {} (ID: {})",
+                format_datetime(usage.start, fstring, self.locale),
+                format_datetime(usage.stop, fstring, self.locale),
+                cat,
+                usage_id
+            );
+            if let Some(d) = &usage.desc {
+                elem = format!("{}\n\t{}", elem, d);
+            }
+            if let Some(r) = &usage.reference {
+                elem = format!("{}\n\tRef: {}", elem, r);
+            }
+            for subtask in &usage.subtasks {
+                elem = format!("{}\n\t- {}", elem, subtask);
+            }
+            if !usage.tags.is_empty() {
+                elem = format!("{}\n\tTags: {}", elem, usage.tags.join(", "));
+            }
+            s = format!("{}\n\n{}", elem, s);
+        }
+        s
+    }
+
+    /// Returns the start and the end of the wall time a time span covers. For all records these
+    /// are the earliest start and the latest stop, or nothing if there are no time usages.
+    fn span_window(&self, span: ShownTimeSpan) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        let now = self.now();
+        let today = now.date();
+
+        if self.precise_spans {
+            let length = match span {
+                ShownTimeSpan::Year => Some(Duration::days(365)),
+                ShownTimeSpan::Month => Some(Duration::weeks(4)),
+                ShownTimeSpan::Week => Some(Duration::weeks(1)),
+                _ => None,
+            };
+            if let Some(length) = length {
+                return Some((now - length, now));
+            }
+        }
+
+        let midnight = NaiveTime::from_hms(0, 0, 0);
+        let start = match span {
+            ShownTimeSpan::All => {
+                let usages = self.time_map.values().flatten();
+                let start = usages.clone().map(|u| u.start).min()?;
+                let stop = usages.map(|u| u.stop).max()?;
+                return Some((start, stop));
+            }
+            ShownTimeSpan::Year => today - Duration::days(365),
+            ShownTimeSpan::YearToDate => NaiveDate::from_ymd(today.year(), 1, 1),
+            ShownTimeSpan::Month => today - Duration::weeks(4),
+            ShownTimeSpan::Week => today - Duration::weeks(1),
+            ShownTimeSpan::Yesterday => {
+                return Some((today.pred().and_time(midnight), today.and_time(midnight)))
+            }
+            ShownTimeSpan::Today => today,
+        };
+
+        Some((start.and_time(midnight), now))
+    }
+
+    /// Returns true if a start_time is within the specified `ShownTimeSpan`.
+    fn in_time_span(&self, start_time: NaiveDateTime, span: ShownTimeSpan) -> bool {
+        let now = self.now();
+        let today = now.date();
+
+        if self.precise_spans {
+            let length = match span {
+                ShownTimeSpan::Year => Some(Duration::days(365)),
+                ShownTimeSpan::Month => Some(Duration::weeks(4)),
+                ShownTimeSpan::Week => Some(Duration::weeks(1)),
+                _ => None,
+            };
+            if let Some(length) = length {
+                return now - start_time <= length;
+            }
+        }
+
+        match span {
+            ShownTimeSpan::All => true,
+            ShownTimeSpan::Year => today - start_time.date() <= Duration::days(365),
+            ShownTimeSpan::YearToDate => today.year() == start_time.year(),
+            ShownTimeSpan::Month => today - start_time.date() <= Duration::weeks(4),
+            ShownTimeSpan::Week => today - start_time.date() <= Duration::weeks(1),
+            ShownTimeSpan::Yesterday => today.pred() == start_time.date(),
+            ShownTimeSpan::Today => today == start_time.date(),
+        }
+    }
+}
+
+/// Renames a tag in a list of tags without creating duplicates. Returns true if the list changed.
+fn rename_in(tags: &mut Vec<String>, old: &str, new: &str) -> bool {
+    if old == new || !tags.iter().any(|t| t == old) {
+        return false;
+    }
+
+    if tags.iter().any(|t| t == new) {
+        tags.retain(|t| t != old);
+    } else {
+        for tag in tags.iter_mut().filter(|t| *t == old) {
+            *tag = new.to_string();
+        }
+    }
+
+    true
+}
+
+/// Returns an `Error` if a `strftime` style format string is invalid.
+fn check_date_format(date_format: &str) -> Result<()> {
+    if StrftimeItems::new(date_format).any(|item| item == Item::Error) {
+        return Err(Error::InvalidDateFormat(date_format.to_string()));
+    }
+    Ok(())
+}
+
+/// Quotes a CSV field if it contains characters with a special meaning in CSV.
+fn csv_escape(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Escapes the characters that have a special meaning in quoted GraphViz DOT strings.
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Escapes the characters that have a special meaning in HTML.
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Serializes a `Duration` as whole seconds.
+mod duration_seconds {
+    use chrono::Duration;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i64(duration.num_seconds())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Ok(Duration::seconds(i64::deserialize(deserializer)?))
+    }
+}
+
+/// Serializes a `Duration` as a human-readable string such as `5h` or `1h30m` which is easy to
+/// edit by hand. Use with `#[serde(with = "vihr::duration_human")]`. Strings are parsed with
+/// `parse_duration`. Only non-negative durations can be read back.
+pub mod duration_human {
+    use super::{format_duration, parse_duration, RoundingMode};
+    use chrono::Duration;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    /// Serializes the `Duration` as for example `1h30m`.
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        let formatted = format_duration(*duration, Duration::seconds(1), RoundingMode::Down);
+        serializer.serialize_str(&formatted.replace(' ', ""))
+    }
+
+    /// Deserializes a `Duration` from a string accepted by `parse_duration`.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse_duration(&s).map_err(de::Error::custom)
+    }
+}
+
+/// Returns the total length of the intervals counting overlapping parts only once.
+/// Intervals whose end isn't after their start are ignored.
+fn union_length(mut intervals: Vec<(NaiveDateTime, NaiveDateTime)>) -> Duration {
+    intervals.retain(|(start, stop)| stop > start);
+    intervals.sort();
+
+    let mut total = Duration::zero();
+    let mut current: Option<(NaiveDateTime, NaiveDateTime)> = None;
+
+    for (start, stop) in intervals {
+        match current {
+            Some((cur_start, cur_stop)) if start <= cur_stop => {
+                current = Some((cur_start, cur_stop.max(stop)));
+            }
+            _ => {
+                if let Some((cur_start, cur_stop)) = current {
+                    total = total + (cur_stop - cur_start);
+                }
+                current = Some((start, stop));
+            }
+        }
+    }
+
+    if let Some((cur_start, cur_stop)) = current {
+        total = total + (cur_stop - cur_start);
+    }
+
+    total
+}
+
+/// Rounds a `Duration` to a multiple of the granularity using the specified `RoundingMode`.
+/// A granularity that isn't positive leaves the duration untouched.
+pub fn round_duration(duration: Duration, granularity: Duration, mode: RoundingMode) -> Duration {
+    round_duration_with(duration, granularity, mode, false)
+}
+
+/// Rounds a `Duration` like `round_duration`. If `round_up_nonzero` is set, a positive duration
+/// is rounded to at least one granularity even if the rounding mode would round it to zero, so
+/// that short time spent is never left unbilled.
+pub fn round_duration_with(
+    duration: Duration,
+    granularity: Duration,
+    mode: RoundingMode,
+    round_up_nonzero: bool,
+) -> Duration {
+    let gran = granularity.num_seconds();
+    if gran <= 0 {
+        return duration;
+    }
+
+    let secs = duration.num_seconds();
+    let rounded = match mode {
+        RoundingMode::Down => secs.div_euclid(gran),
+        RoundingMode::Up => secs.div_euclid(gran) + (secs.rem_euclid(gran) != 0) as i64,
+        RoundingMode::Nearest => (secs + gran / 2).div_euclid(gran),
+    };
+
+    if round_up_nonzero && secs > 0 && rounded == 0 {
+        return granularity;
+    }
+
+    Duration::seconds(rounded * gran)
+}
+
+/// Rounds a moment to a multiple of the granularity counted from the midnight of its day.
+pub fn round_datetime(
+    datetime: NaiveDateTime,
+    granularity: Duration,
+    mode: RoundingMode,
+) -> NaiveDateTime {
+    let midnight = datetime.date().and_hms(0, 0, 0);
+    midnight + round_duration(datetime - midnight, granularity, mode)
+}
+
+/// Formats a `Duration` as for example `1h 42m` after rounding it to the granularity.
+/// Components that are zero are left out.
+pub fn format_duration(duration: Duration, granularity: Duration, mode: RoundingMode) -> String {
+    let duration = round_duration(duration, granularity, mode);
+    let sign = if duration < Duration::zero() { "-" } else { "" };
+    let secs = duration.num_seconds().abs();
+
+    let components = [
+        (secs / 3600, "h"),
+        (secs % 3600 / 60, "m"),
+        (secs % 60, "s"),
+    ];
+    let parts: Vec<String> = components
+        .iter()
+        .filter(|(value, _)| *value != 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect();
+
+    if parts.is_empty() {
+        // Show a zero duration using the smallest unit the granularity allows.
+        let unit = if granularity.num_seconds() >= 3600 {
+            "h"
+        } else if granularity.num_seconds() >= 60 {
+            "m"
+        } else {
+            "s"
+        };
+        format!("0{}", unit)
+    } else {
+        format!("{}{}", sign, parts.join(" "))
+    }
+}
+
+/// Formats a moment using a `strftime` style format string. Month and weekday names are in the
+/// language of the locale or in English if no locale is given.
+pub fn format_datetime(datetime: NaiveDateTime, fmt: &str, locale: Option<Locale>) -> String {
+    match locale {
+        Some(locale) => DateTime::<Utc>::from_utc(datetime, Utc)
+            .format_localized(fmt, locale)
+            .to_string(),
+        None => datetime.format(fmt).to_string(),
+    }
+}
+
+/// Formats the time from `from` to `now` relative to `now`, for example `just now`,
+/// `5 minutes ago` or `2 days ago`. Times after `now` are formatted as for example `in 3 hours`.
+pub fn format_relative(from: NaiveDateTime, now: NaiveDateTime) -> String {
+    let secs = (now - from).num_seconds();
+    let abs = secs.abs();
+
+    let (value, unit) = if abs < 60 {
+        return "just now".to_string();
+    } else if abs < 3600 {
+        (abs / 60, "minute")
+    } else if abs < 86400 {
+        (abs / 3600, "hour")
+    } else {
+        (abs / 86400, "day")
+    };
+    let plural = if value == 1 { "" } else { "s" };
+
+    if secs < 0 {
+        format!("in {} {}{}", value, unit, plural)
+    } else {
+        format!("{} {}{} ago", value, unit, plural)
+    }
+}
+
+/// Parses a `Duration` from a string such as `1h30m`, `45m` or `90s`.
+/// A number without a unit is interpreted as minutes.
+/// Returns an `Error` if the string isn't a valid duration.
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let invalid = || Error::InvalidDuration(s.to_string());
+    let trimmed = s.trim();
+
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+
+    // Signs are left to parse_signed_duration.
+    if trimmed.chars().all(|c| c.is_ascii_digit()) {
+        let minutes: i64 = trimmed.parse().map_err(|_| invalid())?;
+        return checked_seconds(minutes, 60).ok_or_else(invalid);
+    }
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+
+    for c in trimmed.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let value: i64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+
+        let unit = match c {
+            'd' => 24 * 60 * 60,
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        total = checked_seconds(value, unit)
+            .and_then(|part| total.checked_add(&part))
+            .ok_or_else(invalid)?;
+    }
+
+    // A trailing number without a unit is ambiguous in a compound duration.
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(total)
+}
+
+/// Returns a `Duration` of the value multiplied by the number of seconds in a unit or `None` if
+/// it doesn't fit in a `Duration`.
+fn checked_seconds(value: i64, unit: i64) -> Option<Duration> {
+    let seconds = value.checked_mul(unit)?;
+    (seconds <= Duration::max_value().num_seconds()).then(|| Duration::seconds(seconds))
+}
+
+// Due to the small nature of this project I have only written integration test style large
+// tests that don't follow the AAA pattern. Basically I have merged tests together.
+//
+// Splitting the tests into smaller units would be more work and wouldn't provide much practical
+// benefit.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn category_can_be_added_and_removed() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+
+        assert!(book.categories().contains(&&("test".to_string())));
+
+        book.add_category("test1".to_string()).unwrap();
+
+        assert_eq!(book.categories().len(), 2);
+
+        book.remove_category("test").unwrap();
+
+        assert!(book.categories().contains(&&("test1".to_string())));
+        assert_eq!(book.categories().len(), 1);
+    }
+
+    #[test]
+    fn trying_to_remove_nonexistant_category_fails() {
+        let mut book = TimeBook::default();
+        assert_eq!(
+            book.remove_category("test").unwrap_err(),
+            Error::CategoryDoesntExist("test".to_string())
+        );
+    }
+
+    #[test]
+    fn adding_already_existing_category_fails() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        assert_eq!(
+            book.add_category("test".to_string()).unwrap_err(),
+            Error::CategoryExists("test".to_string())
+        );
+    }
+
+    #[test]
+    fn time_usage_cannot_be_added_to_category_that_doesnt_exist() {
+        let mut book = TimeBook::default();
+        assert_eq!(
+            book.add_time_usage(
+                "test",
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+                None
+            )
+            .unwrap_err(),
+            Error::CategoryDoesntExist("test".to_string())
+        );
+    }
+
+    #[test]
+    fn time_usage_can_be_added_and_removed_from_multiple_categories() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::All).unwrap(),
+            Duration::hours(1)
+        );
+        assert_eq!(
+            book.time_spent("test_second", ShownTimeSpan::All).unwrap(),
+            Duration::zero()
+        );
+
+        book.add_time_usage(
+            "test_second",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::All).unwrap(),
+            Duration::hours(1)
+        );
+        assert_eq!(
+            book.time_spent("test_second", ShownTimeSpan::All).unwrap(),
+            Duration::hours(1)
+        );
+
+        book.remove_time_usage("test", 0).unwrap();
+
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::All).unwrap(),
+            Duration::zero()
+        );
+        assert_eq!(
+            book.time_spent("test_second", ShownTimeSpan::All).unwrap(),
+            Duration::hours(1)
+        );
+    }
+
+    fn time_book_with_usages() -> TimeBook {
+        let now = Local::now().naive_local();
+        let yesterday = Local::now().naive_local() - Duration::days(1);
+        let week_ago = Local::now().naive_local() - Duration::weeks(1);
+        let month_ago = Local::now().naive_local() - Duration::weeks(4);
+        let year_ago = Local::now().naive_local() - Duration::days(365);
+        let two_years_ago = Local::now().naive_local() - Duration::days(700);
+
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+
+        book.add_time_usage(
+            "test",
+            now,
+            now + Duration::minutes(30),
+            Some("Time usage of today".to_string()),
+        )
+        .unwrap();
+
+        book.add_time_usage("test", yesterday, yesterday + Duration::minutes(30), None)
+            .unwrap();
+
+        book.add_time_usage(
+            "test",
+            week_ago,
+            week_ago + Duration::minutes(30),
+            Some("Week ago".to_string()),
+        )
+        .unwrap();
+
+        book.add_time_usage("test", month_ago, month_ago + Duration::minutes(30), None)
+            .unwrap();
+
+        book.add_time_usage(
+            "test",
+            year_ago,
+            year_ago + Duration::minutes(30),
+            Some("A Year ago".to_string()),
+        )
+        .unwrap();
+
+        book.add_time_usage(
+            "test",
+            two_years_ago,
+            two_years_ago + Duration::minutes(30),
+            None,
+        )
+        .unwrap();
+
+        book
+    }
+
+    #[test]
+    fn time_usage_is_reported_correctly_for_the_correct_shown_time_spans() {
+        let book = time_book_with_usages();
+
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Today).unwrap(),
+            Duration::minutes(30)
+        );
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Yesterday).unwrap(),
+            Duration::minutes(30)
+        );
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Week).unwrap(),
+            Duration::minutes(90)
+        );
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Month).unwrap(),
+            Duration::minutes(120)
+        );
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Year).unwrap(),
+            Duration::minutes(150)
+        );
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::All).unwrap(),
+            Duration::minutes(180)
+        );
+    }
+
+    #[test]
+    fn time_spent_returns_err_for_nonexistant_category() {
+        let book = TimeBook::default();
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::All).unwrap_err(),
+            Error::CategoryDoesntExist("test".to_string())
+        );
+    }
+
+    #[test]
+    fn time_usage_log_is_written_correctly_for_the_correct_shown_time_spans() {
+        let book = time_book_with_usages();
+
+        let now = Local::now().naive_local();
+        let yesterday = Local::now().naive_local() - Duration::days(1);
+        let week_ago = Local::now().naive_local() - Duration::weeks(1);
+        let month_ago = Local::now().naive_local() - Duration::weeks(4);
+        let year_ago = Local::now().naive_local() - Duration::days(365);
+        let two_years_ago = Local::now().naive_local() - Duration::days(700);
+
+        let fstring = "%-d/%-m/%Y %H:%M";
+
+        let now_str = format!(
+            "{} - {}: test (ID: 5)\n\tTime usage of today\n\n",
+            now.format(fstring),
+            (now + Duration::minutes(30)).format(fstring)
+        );
+
+        let yesterday_str = format!(
+            "{} - {}: test (ID: 4)\n\n",
+            yesterday.format(fstring),
+            (yesterday + Duration::minutes(30)).format(fstring)
+        );
+
+        let week_str = format!(
+            "{} - {}: test (ID: 3)\n\tWeek ago\n\n",
+            week_ago.format(fstring),
+            (week_ago + Duration::minutes(30)).format(fstring)
+        );
+
+        let month_str = format!(
+            "{} - {}: test (ID: 2)\n\n",
+            month_ago.format(fstring),
+            (month_ago + Duration::minutes(30)).format(fstring)
+        );
+
+        let year_str = format!(
+            "{} - {}: test (ID: 1)\n\tA Year ago\n\n",
+            year_ago.format(fstring),
+            (year_ago + Duration::minutes(30)).format(fstring)
+        );
+
+        let two_years_ago_str = format!(
+            "{} - {}: test (ID: 0)\n\n",
+            two_years_ago.format(fstring),
+            (two_years_ago + Duration::minutes(30)).format(fstring)
+        );
+
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::Today, None).unwrap(),
+            now_str
+        );
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::Yesterday, None).unwrap(),
+            yesterday_str
+        );
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::Week, None).unwrap(),
+            now_str.clone() + &yesterday_str + &week_str
+        );
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            now_str + &yesterday_str + &week_str + &month_str + &year_str + &two_years_ago_str
+        );
+    }
+
+    #[test]
+    fn time_usage_log_is_written_correctly_for_different_category_specifications() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        book.add_time_usage(
+            "test_second",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            "1/1/2022 10:00 - 1/1/2022 11:00: test (ID: 0)\n\n1/1/2022 09:00 - 1/1/2022 10:00: test_second (ID: 0)\n\n"
+        );
+
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, Some("test".to_string()))
+                .unwrap(),
+            "1/1/2022 10:00 - 1/1/2022 11:00: test (ID: 0)\n\n"
+        );
+
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, Some("test_second".to_string()))
+                .unwrap(),
+            "1/1/2022 09:00 - 1/1/2022 10:00: test_second (ID: 0)\n\n"
+        );
+    }
+
+    #[test]
+    fn time_usage_log_returns_err_for_nonexistant_category() {
+        let book = TimeBook::default();
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, Some("test".to_string()))
+                .unwrap_err(),
+            Error::CategoryDoesntExist("test".to_string())
+        );
+    }
+
+    #[test]
+    fn cancelling_without_starting_fails() {
+        let mut book = TimeBook::default();
+        assert_eq!(book.cancel().unwrap_err(), Error::NotRecordingTime);
+    }
+
+    #[test]
+    fn cancelling_causes_stop_and_cancel_to_fail_and_doesnt_modify_time_usages() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.start("test".to_string(), None).unwrap();
+        book.cancel().unwrap();
+
+        assert_eq!(book.cancel().unwrap_err(), Error::NotRecordingTime);
+        assert_eq!(book.stop(None, None).unwrap_err(), Error::NotRecordingTime);
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::All).unwrap(),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn cancelling_stopping_and_status_fail_when_nothing_has_been_started() {
+        let mut book = TimeBook::default();
+
+        assert_eq!(book.cancel().unwrap_err(), Error::NotRecordingTime);
+        assert_eq!(book.stop(None, None).unwrap_err(), Error::NotRecordingTime);
+        assert_eq!(book.status().unwrap_err(), Error::NotRecordingTime);
+    }
+
+    #[test]
+    fn starting_twice_fails() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.start("test".to_string(), None).unwrap();
+
+        assert_eq!(
+            book.start("test".to_string(), None).unwrap_err(),
+            Error::AlreadyRecordingTime
+        );
+    }
+
+    #[test]
+    fn starting_with_nonexistant_category_fails() {
+        let mut book = TimeBook::default();
+        assert_eq!(
+            book.start("test".to_string(), None).unwrap_err(),
+            Error::CategoryDoesntExist("test".to_string())
+        );
+    }
+
+    #[test]
+    fn starting_updates_status_and_stop_adds_time_usage() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+
+        let now = Local::now().naive_local();
+
+        book.start("test".to_string(), Some(now)).unwrap();
+
+        assert_eq!(book.status().unwrap(), ("test", now));
+
+        book.stop(Some(now + Duration::hours(2)), None).unwrap();
+
+        assert!(book.time_spent("test", ShownTimeSpan::All).unwrap() >= Duration::hours(2));
+    }
+
+    #[test]
+    fn future_usages_are_detected() {
+        let mut book = TimeBook::default();
+        let now = Local::now().naive_local();
+
+        book.add_category("test".to_string()).unwrap();
+
+        book.add_time_usage(
+            "test",
+            now - Duration::hours(2),
+            now - Duration::hours(1),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            now + Duration::days(1),
+            now + Duration::days(1) + Duration::hours(1),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.future_usages(), vec![("test".to_string(), 1)]);
+    }
+
+    #[test]
+    fn durations_are_rounded_and_formatted() {
+        let d = Duration::minutes(2) + Duration::seconds(40);
+
+        assert_eq!(
+            round_duration(d, Duration::minutes(1), RoundingMode::Nearest),
+            Duration::minutes(3)
+        );
+        assert_eq!(
+            round_duration(d, Duration::minutes(1), RoundingMode::Down),
+            Duration::minutes(2)
+        );
+        assert_eq!(
+            format_duration(d, Duration::minutes(1), RoundingMode::Nearest),
+            "3m"
+        );
+        assert_eq!(
+            format_duration(
+                Duration::hours(1) + Duration::minutes(42) + Duration::seconds(13),
+                Duration::minutes(1),
+                RoundingMode::Nearest
+            ),
+            "1h 42m"
+        );
+        assert_eq!(
+            format_duration(Duration::hours(2), Duration::minutes(1), RoundingMode::Up),
+            "2h"
+        );
+        assert_eq!(
+            format_duration(
+                Duration::seconds(20),
+                Duration::minutes(1),
+                RoundingMode::Down
+            ),
+            "0m"
+        );
+    }
+
+    #[test]
+    fn durations_are_parsed() {
+        assert_eq!(parse_duration("10m").unwrap(), Duration::minutes(10));
+        assert_eq!(
+            parse_duration("1h30m").unwrap(),
+            Duration::hours(1) + Duration::minutes(30)
+        );
+        assert_eq!(parse_duration("90s").unwrap(), Duration::seconds(90));
+        assert_eq!(parse_duration("15").unwrap(), Duration::minutes(15));
+        assert_eq!(
+            parse_duration("1x").unwrap_err(),
+            Error::InvalidDuration("1x".to_string())
+        );
+        assert_eq!(
+            parse_duration("1h30").unwrap_err(),
+            Error::InvalidDuration("1h30".to_string())
+        );
+        assert_eq!(
+            parse_duration("").unwrap_err(),
+            Error::InvalidDuration("".to_string())
+        );
+        assert_eq!(
+            parse_duration("-30").unwrap_err(),
+            Error::InvalidDuration("-30".to_string())
+        );
+        assert_eq!(
+            parse_duration("+30").unwrap_err(),
+            Error::InvalidDuration("+30".to_string())
+        );
+    }
+
+    #[test]
+    fn overflowing_durations_are_invalid() {
+        for s in [
+            "99999999999999999",
+            "99999999999999999s",
+            "9999999999999999d",
+            "99999999999999h99999999999999h",
+        ] {
+            assert_eq!(
+                parse_duration(s).unwrap_err(),
+                Error::InvalidDuration(s.to_string())
+            );
+        }
+    }
+
+    #[test]
+    fn time_usage_can_be_inserted_into_gap() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(13, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        book.insert_in_gap("test", 0, "test_second", Some("Forgot".to_string()))
+            .unwrap();
+
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, Some("test_second".to_string()))
+                .unwrap(),
+            "1/1/2022 10:00 - 1/1/2022 11:00: test_second (ID: 0)\n\tForgot\n\n"
+        );
+
+        assert_eq!(
+            book.insert_in_gap("test", 1, "test_second", None)
+                .unwrap_err(),
+            Error::NoGapAfterTimeUsage(1)
+        );
+        assert_eq!(
+            book.insert_in_gap("test", 2, "test_second", None)
+                .unwrap_err(),
+            Error::NoGapAfterTimeUsage(2)
+        );
+        assert_eq!(
+            book.insert_in_gap("test", 3, "test_second", None)
+                .unwrap_err(),
+            Error::TimeUsageDoesntExist(3)
+        );
+    }
+
+    #[test]
+    fn total_excluding_skips_excluded_categories() {
+        let mut book = TimeBook::default();
+
+        for (i, cat) in ["test", "test_second", "test_third"].iter().enumerate() {
+            book.add_category(cat.to_string()).unwrap();
+            book.add_time_usage(
+                cat,
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(10 + i as u32, 0, 0),
+                None,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            book.total_excluding(ShownTimeSpan::All, &[]),
+            Duration::hours(6)
+        );
+        assert_eq!(
+            book.total_excluding(
+                ShownTimeSpan::All,
+                &["test_second".to_string(), "nonexistant".to_string()]
+            ),
+            Duration::hours(4)
+        );
+    }
+
+    #[test]
+    fn year_to_date_follows_calendar_year() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2021, 12, 31).and_hms(22, 0, 0),
+            NaiveDate::from_ymd(2021, 12, 31).and_hms(23, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        book.set_now(Some(NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 0, 0)));
+
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::YearToDate).unwrap(),
+            Duration::hours(2)
+        );
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Year).unwrap(),
+            Duration::hours(3)
+        );
+
+        book.set_now(Some(NaiveDate::from_ymd(2021, 12, 31).and_hms(23, 30, 0)));
+
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::YearToDate).unwrap(),
+            Duration::hours(1)
+        );
+    }
+
+    #[test]
+    fn usage_categories_can_be_swapped() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            Some("Second".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(13, 0, 0),
+            Some("First".to_string()),
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.swap_usage_categories("test", 0, "test_second", 1)
+                .unwrap_err(),
+            Error::TimeUsageDoesntExist(1)
+        );
+
+        book.swap_usage_categories("test", 0, "test_second", 0)
+            .unwrap();
+
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::All).unwrap(),
+            Duration::hours(3)
+        );
+        assert_eq!(
+            book.time_spent("test_second", ShownTimeSpan::All).unwrap(),
+            Duration::hours(1)
+        );
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            "1/1/2022 10:00 - 1/1/2022 13:00: test (ID: 0)\n\tFirst\n\n1/1/2022 09:00 - 1/1/2022 10:00: test_second (ID: 0)\n\tSecond\n\n"
+        );
+    }
+
+    #[test]
+    fn diary_groups_usages_by_day() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            Some("Morning".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 3).and_hms(23, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 4).and_hms(1, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        let diary = book.diary(ShownTimeSpan::All, None).unwrap();
+
+        assert_eq!(diary.matches("== 2022-01-01 ==").count(), 1);
+        assert_eq!(diary.matches("== 2022-01-03 ==").count(), 1);
+        assert_eq!(diary.matches("==").count(), 4);
+        assert_eq!(
+            diary,
+            "== 2022-01-03 ==\n23:00 - 4/1/2022 01:00: test (ID: 1)\n\n== 2022-01-01 ==\n09:00 - 10:00: test (ID: 0)\n\tMorning\n10:00 - 11:00: test_second (ID: 0)\n"
+        );
+
+        assert_eq!(
+            book.diary(ShownTimeSpan::All, Some("test_second".to_string()))
+                .unwrap(),
+            "== 2022-01-01 ==\n10:00 - 11:00: test_second (ID: 0)\n"
+        );
+        assert_eq!(
+            book.diary(ShownTimeSpan::All, Some("nonexistant".to_string()))
+                .unwrap_err(),
+            Error::CategoryDoesntExist("nonexistant".to_string())
+        );
+    }
+
+    #[test]
+    fn zero_length_usages_are_detected() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.zero_length_usages(), vec![("test".to_string(), 1)]);
+    }
+
+    #[test]
+    fn categories_can_be_matched_with_a_prefix() {
+        let mut book = TimeBook::default();
+
+        book.add_category("client-acme".to_string()).unwrap();
+        book.add_category("client-globex".to_string()).unwrap();
+        book.add_category("internal".to_string()).unwrap();
+
+        assert_eq!(
+            book.categories_matching("client-*"),
+            vec!["client-acme", "client-globex"]
+        );
+        assert_eq!(book.categories_matching("internal"), vec!["internal"]);
+        assert_eq!(book.categories_matching("client"), Vec::<&String>::new());
+        assert_eq!(book.categories_matching("*").len(), 3);
+    }
+
+    #[test]
+    fn recent_returns_newest_usages_across_categories() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        for (cat, hour) in [("test", 9), ("test_second", 10), ("test", 11), ("test", 8)] {
+            book.add_time_usage(
+                cat,
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(hour, 0, 0),
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(hour, 30, 0),
+                None,
+            )
+            .unwrap();
+        }
+
+        let recent: Vec<(&str, usize, NaiveDateTime)> = book
+            .recent(2)
+            .into_iter()
+            .map(|(cat, id, usage)| (cat, id, usage.start))
+            .collect();
+
+        assert_eq!(
+            recent,
+            vec![
+                ("test", 2, NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 0, 0)),
+                (
+                    "test_second",
+                    0,
+                    NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0)
+                ),
+            ]
+        );
+        assert_eq!(book.recent(10).len(), 4);
+        assert!(TimeBook::default().recent(3).is_empty());
+    }
+
+    #[test]
+    fn logged_cancel_is_recorded_without_affecting_time_usages() {
+        let mut book = TimeBook::default();
+        let start = NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0);
+
+        book.add_category("test".to_string()).unwrap();
+        book.set_now(Some(start + Duration::minutes(12)));
+        book.start("test".to_string(), Some(start)).unwrap();
+        book.cancel_logged(Some("Wrong category".to_string()))
+            .unwrap();
+
+        assert_eq!(
+            book.cancelled(),
+            &[CancelledRecording {
+                category: "test".to_string(),
+                start,
+                cancelled_at: start + Duration::minutes(12),
+                reason: Some("Wrong category".to_string()),
+            }]
+        );
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::All).unwrap(),
+            Duration::zero()
+        );
+        assert_eq!(book.status().unwrap_err(), Error::NotRecordingTime);
+        assert_eq!(
+            book.cancel_logged(None).unwrap_err(),
+            Error::NotRecordingTime
+        );
+        assert_eq!(book.cancelled().len(), 1);
+    }
+
+    #[test]
+    fn removed_category_time_can_be_reassigned() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(8, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(8, 30, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.remove_category_reassign("test", "nonexistant")
+                .unwrap_err(),
+            Error::CategoryDoesntExist("nonexistant".to_string())
+        );
+
+        book.remove_category_reassign("test", "test_second")
+            .unwrap();
+
+        assert_eq!(book.categories(), vec!["test_second"]);
+        assert_eq!(
+            book.time_spent("test_second", ShownTimeSpan::All).unwrap(),
+            Duration::minutes(90)
+        );
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            "1/1/2022 09:00 - 1/1/2022 10:00: test_second (ID: 1)\n\n1/1/2022 08:00 - 1/1/2022 08:30: test_second (ID: 0)\n\n"
+        );
+    }
+
+    #[test]
+    fn idle_time_is_measured_from_the_last_activity() {
+        let mut book = TimeBook::default();
+
+        book.set_now(Some(NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 0, 0)));
+        book.add_category("test".to_string()).unwrap();
+
+        assert_eq!(book.idle_since(), None);
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(8, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 15, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.last_activity(),
+            Some(NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 15, 0))
+        );
+        assert_eq!(book.idle_since(), Some(Duration::minutes(45)));
+
+        book.start("test".to_string(), None).unwrap();
+
+        assert_eq!(book.idle_since(), None);
+    }
+
+    #[test]
+    fn subtasks_are_rendered_in_the_log() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_time_usage_with(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            UsageDetails {
+                desc: Some("Chores".to_string()),
+                subtasks: vec!["Dishes".to_string(), "Laundry".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            "1/1/2022 09:00 - 1/1/2022 10:00: test (ID: 0)\n\tChores\n\t- Dishes\n\t- Laundry\n\n"
+        );
+    }
+
+    #[test]
+    fn book_roundtrips_through_serialization() {
+        let mut book = time_book_with_usages();
+
+        book.add_category("test_second".to_string()).unwrap();
+        book.start("test_second".to_string(), None).unwrap();
+
+        assert!(book.roundtrip_ok());
+        assert!(TimeBook::default().roundtrip_ok());
+    }
+
+    #[test]
+    fn average_gap_is_computed_between_consecutive_sessions() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.average_gap("test", ShownTimeSpan::All).unwrap(), None);
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 30, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 30, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(13, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.average_gap("test", ShownTimeSpan::All).unwrap(),
+            Some(Duration::minutes(60))
+        );
+
+        // Overlapping with the previous usage adds a gap of zero.
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 45, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(13, 30, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.average_gap("test", ShownTimeSpan::All).unwrap(),
+            Some(Duration::minutes(40))
+        );
+        assert_eq!(
+            book.average_gap("nonexistant", ShownTimeSpan::All)
+                .unwrap_err(),
+            Error::CategoryDoesntExist("nonexistant".to_string())
+        );
+    }
+
+    #[test]
+    fn tags_given_at_start_are_merged_with_tags_given_at_stop() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.start_tagged(
+            "test".to_string(),
+            Some(NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0)),
+            vec!["client".to_string(), "onsite".to_string()],
+        )
+        .unwrap();
+
+        let json = serde_json::to_string(&book).unwrap();
+        let mut book: TimeBook = serde_json::from_str(&json).unwrap();
+
+        book.stop_with(
+            Some(NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0)),
+            UsageDetails {
+                tags: vec!["onsite".to_string(), "urgent".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            "1/1/2022 09:00 - 1/1/2022 10:00: test (ID: 0)\n\tTags: client, onsite, urgent\n\n"
+        );
+
+        // Tags of a stopped recording aren't carried to the next one.
+        book.start("test".to_string(), None).unwrap();
+        book.stop(None, None).unwrap();
+
+        assert_eq!(book.recent(1)[0].2.tags, Vec::<String>::new());
+    }
+
+    #[test]
+    fn diff_finds_added_removed_and_modified_usages() {
+        let mut book = TimeBook::default();
+        let mut other = TimeBook::default();
+
+        for b in [&mut book, &mut other] {
+            b.add_category("test".to_string()).unwrap();
+            b.add_time_usage(
+                "test",
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+                None,
+            )
+            .unwrap();
+        }
+
+        assert!(book.diff(&other).is_empty());
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 2).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 2).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        other
+            .add_time_usage(
+                "test",
+                NaiveDate::from_ymd(2022, 1, 2).and_hms(9, 0, 0),
+                NaiveDate::from_ymd(2022, 1, 2).and_hms(11, 0, 0),
+                None,
+            )
+            .unwrap();
+
+        other.add_category("test_second".to_string()).unwrap();
+        other
+            .add_time_usage(
+                "test_second",
+                NaiveDate::from_ymd(2022, 1, 3).and_hms(9, 0, 0),
+                NaiveDate::from_ymd(2022, 1, 3).and_hms(10, 0, 0),
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(
+            book.diff(&other),
+            BookDiff {
+                added: vec![(
+                    "test_second".to_string(),
+                    NaiveDate::from_ymd(2022, 1, 3).and_hms(9, 0, 0)
+                )],
+                removed: vec![],
+                modified: vec![(
+                    "test".to_string(),
+                    NaiveDate::from_ymd(2022, 1, 2).and_hms(9, 0, 0)
+                )],
+            }
+        );
+        assert_eq!(
+            other.diff(&book).removed,
+            vec![(
+                "test_second".to_string(),
+                NaiveDate::from_ymd(2022, 1, 3).and_hms(9, 0, 0)
+            )]
+        );
+    }
+
+    #[test]
+    fn multiple_categories_can_be_added_skipping_existing() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+
+        let results = book.add_categories(&[
+            "test_second".to_string(),
+            "test".to_string(),
+            "test_third".to_string(),
+        ]);
+
+        assert_eq!(
+            results,
+            vec![
+                Ok(()),
+                Err(Error::CategoryExists("test".to_string())),
+                Ok(())
+            ]
+        );
+        assert_eq!(book.categories().len(), 3);
+    }
+
+    #[test]
+    fn duration_percentiles_use_nearest_rank() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+
+        assert_eq!(
+            book.duration_percentiles("test", ShownTimeSpan::All, &[50.0])
+                .unwrap(),
+            vec![Duration::zero()]
+        );
+
+        for (day, minutes) in [(1, 40), (2, 10), (3, 30), (4, 20), (5, 50)] {
+            let start = NaiveDate::from_ymd(2022, 1, day).and_hms(9, 0, 0);
+            book.add_time_usage("test", start, start + Duration::minutes(minutes), None)
+                .unwrap();
+        }
+
+        assert_eq!(
+            book.duration_percentiles("test", ShownTimeSpan::All, &[0.0, 20.0, 50.0, 90.0, 100.0])
+                .unwrap(),
+            vec![
+                Duration::minutes(10),
+                Duration::minutes(10),
+                Duration::minutes(30),
+                Duration::minutes(50),
+                Duration::minutes(50)
+            ]
+        );
+        assert_eq!(
+            book.duration_percentiles("nonexistant", ShownTimeSpan::All, &[50.0])
+                .unwrap_err(),
+            Error::CategoryDoesntExist("nonexistant".to_string())
+        );
+    }
+
+    #[test]
+    fn time_usage_log_can_use_a_custom_date_format() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 3).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 3).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.time_usage_log_formatted(ShownTimeSpan::All, None, "%a %d %b")
+                .unwrap(),
+            "Mon 03 Jan - Mon 03 Jan: test (ID: 0)\n\n"
+        );
+        assert_eq!(
+            book.time_usage_log_formatted(ShownTimeSpan::All, None, "%Q")
+                .unwrap_err(),
+            Error::InvalidDateFormat("%Q".to_string())
+        );
+    }
+
+    #[test]
+    fn span_heading_uses_a_custom_date_format() {
+        let mut book = TimeBook::default();
+        book.set_now(Some(NaiveDate::from_ymd(2022, 1, 5).and_hms(12, 0, 0)));
+
+        assert_eq!(
+            book.span_heading(ShownTimeSpan::All, "%a %d %b").unwrap(),
+            None
+        );
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 3).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 3).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.span_heading(ShownTimeSpan::All, "%a %d %b").unwrap(),
+            Some("Mon 03 Jan - Mon 03 Jan".to_string())
+        );
+        assert_eq!(
+            book.span_heading(ShownTimeSpan::Today, "%a %d %b %H:%M")
+                .unwrap(),
+            Some("Wed 05 Jan 00:00 - Wed 05 Jan 12:00".to_string())
+        );
+        assert_eq!(
+            book.span_heading(ShownTimeSpan::All, "%Q").unwrap_err(),
+            Error::InvalidDateFormat("%Q".to_string())
+        );
+    }
+
+    #[test]
+    fn inactive_categories_have_no_time_in_span() {
+        let mut book = TimeBook::default();
+        let now = NaiveDate::from_ymd(2022, 1, 31).and_hms(12, 0, 0);
+
+        book.set_now(Some(now));
+
+        for cat in ["active", "old", "unused"] {
+            book.add_category(cat.to_string()).unwrap();
+        }
+
+        book.add_time_usage(
+            "active",
+            now - Duration::days(2),
+            now - Duration::days(2) + Duration::hours(1),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "old",
+            now - Duration::days(20),
+            now - Duration::days(20) + Duration::hours(1),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.inactive_categories(ShownTimeSpan::Week),
+            vec!["old", "unused"]
+        );
+        assert_eq!(book.inactive_categories(ShownTimeSpan::All), vec!["unused"]);
+    }
+
+    #[test]
+    fn day_coverage_counts_overlapping_usages_once() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let wake_start = NaiveTime::from_hms(7, 0, 0);
+        let wake_end = NaiveTime::from_hms(23, 0, 0);
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        assert_eq!(book.day_coverage(day, wake_start, wake_end), 0.0);
+
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(12, 0, 0), None)
+            .unwrap();
+        // Partly outside of the waking window.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(21, 0, 0),
+            day.and_hms(23, 59, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.day_coverage(day, wake_start, wake_end), 6.0 / 16.0);
+
+        // Overlaps with the first usage.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(10, 0, 0),
+            day.and_hms(14, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.day_coverage(day, wake_start, wake_end), 8.0 / 16.0);
+    }
+
+    #[test]
+    fn tracking_ratio_counts_overlapping_usages_once() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.set_now(Some(day.and_hms(12, 0, 0)));
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        assert_eq!(book.tracking_ratio(ShownTimeSpan::Today), 0.0);
+        assert_eq!(book.tracking_ratio(ShownTimeSpan::All), 0.0);
+
+        book.add_time_usage("test", day.and_hms(0, 0, 0), day.and_hms(3, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(2, 0, 0),
+            day.and_hms(6, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        // 6 of the 12 hours of today are covered.
+        assert_eq!(book.tracking_ratio(ShownTimeSpan::Today), 0.5);
+        // All records only last from midnight to 6.
+        assert_eq!(book.tracking_ratio(ShownTimeSpan::All), 1.0);
+        assert_eq!(book.tracking_ratio(ShownTimeSpan::Yesterday), 0.0);
+    }
+
+    #[test]
+    fn summary_contains_category_totals() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 30, 0),
+            None,
+        )
+        .unwrap();
+
+        let mut summary = book.summary(ShownTimeSpan::All, None).unwrap();
+
+        assert_eq!(summary.total, Duration::minutes(90));
+        assert_eq!(
+            summary.categories,
+            vec![
+                CategorySummary {
+                    category: "test".to_string(),
+                    spent: Duration::hours(1)
+                },
+                CategorySummary {
+                    category: "test_second".to_string(),
+                    spent: Duration::minutes(30)
+                }
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&summary).unwrap(),
+            r#"{"span":"all","categories":[{"category":"test","spent":3600},{"category":"test_second","spent":1800}],"total":5400}"#
+        );
+
+        summary.retain(|c| c.category != "test");
+
+        assert_eq!(summary.total, Duration::minutes(30));
+        assert_eq!(
+            book.summary(ShownTimeSpan::All, Some("test"))
+                .unwrap()
+                .total,
+            Duration::hours(1)
+        );
+        assert_eq!(
+            book.summary(ShownTimeSpan::All, Some("nonexistant"))
+                .unwrap_err(),
+            Error::CategoryDoesntExist("nonexistant".to_string())
+        );
+    }
+
+    #[test]
+    fn precise_spans_are_measured_from_the_current_moment() {
+        let mut book = TimeBook::default();
+        let now = NaiveDate::from_ymd(2022, 1, 15).and_hms(12, 0, 0);
+
+        book.set_now(Some(now));
+        book.add_category("test".to_string()).unwrap();
+
+        let just_inside = now - Duration::weeks(1) + Duration::minutes(1);
+        let just_outside = now - Duration::weeks(1) - Duration::minutes(1);
+
+        book.add_time_usage(
+            "test",
+            just_inside,
+            just_inside + Duration::minutes(10),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            just_outside,
+            just_outside + Duration::minutes(20),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Week).unwrap(),
+            Duration::minutes(30)
+        );
+
+        book.set_precise_spans(true);
+
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Week).unwrap(),
+            Duration::minutes(10)
+        );
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Month).unwrap(),
+            Duration::minutes(30)
+        );
+    }
+
+    #[test]
+    fn usages_are_exported_as_csv() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test, second".to_string()).unwrap();
+        book.add_time_usage_with(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            UsageDetails {
+                desc: Some("Said \"hi\"".to_string()),
+                tags: vec!["a".to_string(), "b".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test, second",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 30, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.to_csv(ShownTimeSpan::All),
+            "category,start,stop,duration_minutes,description,tags\n\
+             test,2022-01-01T09:00:00,2022-01-01T10:00:00,60,\"Said \"\"hi\"\"\",a;b\n\
+             \"test, second\",2022-01-01T10:00:00,2022-01-01T10:30:00,30,,\n"
+        );
+        assert_eq!(
+            TimeBook::default().to_csv(ShownTimeSpan::All),
+            "category,start,stop,duration_minutes,description,tags\n"
+        );
+    }
+
+    #[test]
+    fn weekly_totals_are_grouped_by_iso_week() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        // Monday 3.1.2022 and Sunday 9.1.2022 are in the same week.
+        for (cat, day, hours) in [
+            ("test", 3, 1),
+            ("test", 9, 2),
+            ("test_second", 5, 4),
+            ("test", 17, 3),
+        ] {
+            let start = NaiveDate::from_ymd(2022, 1, day).and_hms(9, 0, 0);
+            book.add_time_usage(cat, start, start + Duration::hours(hours), None)
+                .unwrap();
+        }
+
+        assert_eq!(
+            book.weekly_totals(Some("test"), ShownTimeSpan::All),
+            vec![
+                (NaiveDate::from_ymd(2022, 1, 3), Duration::hours(3)),
+                (NaiveDate::from_ymd(2022, 1, 10), Duration::zero()),
+                (NaiveDate::from_ymd(2022, 1, 17), Duration::hours(3)),
+            ]
+        );
+        assert_eq!(
+            book.weekly_totals(None, ShownTimeSpan::All)[0],
+            (NaiveDate::from_ymd(2022, 1, 3), Duration::hours(7))
+        );
+        assert!(book
+            .weekly_totals(Some("nonexistant"), ShownTimeSpan::All)
+            .is_empty());
+    }
+
+    #[test]
+    fn tags_can_be_renamed_across_categories() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        for (cat, hour, tags) in [
+            ("test", 9, vec!["mtg"]),
+            ("test_second", 10, vec!["mtg", "meeting"]),
+            ("test_second", 11, vec!["other"]),
+        ] {
+            book.add_time_usage_with(
+                cat,
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(hour, 0, 0),
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(hour, 30, 0),
+                UsageDetails {
+                    tags: tags.into_iter().map(String::from).collect(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        assert_eq!(book.rename_tag("mtg", "meeting"), 2);
+        assert_eq!(book.rename_tag("mtg", "meeting"), 0);
+
+        let tags: Vec<Vec<String>> = book
+            .recent(3)
+            .into_iter()
+            .map(|(_, _, usage)| usage.tags.clone())
+            .collect();
+
+        assert_eq!(
+            tags,
+            vec![
+                vec!["other".to_string()],
+                vec!["meeting".to_string()],
+                vec!["meeting".to_string()]
+            ]
+        );
+    }
+
+    #[test]
+    fn all_tags_are_counted() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        for (cat, hour, tags) in [
+            ("test", 9, vec!["b", "a"]),
+            ("test_second", 10, vec!["a", "c"]),
+            ("test_second", 11, vec!["a", "b"]),
+            ("test_second", 12, vec![]),
+        ] {
+            book.add_time_usage_with(
+                cat,
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(hour, 0, 0),
+                NaiveDate::from_ymd(2022, 1, 1).and_hms(hour, 30, 0),
+                UsageDetails {
+                    tags: tags.into_iter().map(String::from).collect(),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            book.all_tags(),
+            vec![
+                ("a".to_string(), 3),
+                ("b".to_string(), 2),
+                ("c".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn overtime_sums_differences_to_daily_target() {
+        let mut book = TimeBook::default();
+
+        book.set_now(Some(NaiveDate::from_ymd(2022, 1, 5).and_hms(20, 0, 0)));
+        book.add_category("test".to_string()).unwrap();
+
+        for (day, hours) in [(3, 10), (4, 7)] {
+            let start = NaiveDate::from_ymd(2022, 1, day).and_hms(8, 0, 0);
+            book.add_time_usage("test", start, start + Duration::hours(hours), None)
+                .unwrap();
+        }
+
+        assert_eq!(
+            book.overtime(ShownTimeSpan::All, Duration::hours(8), false),
+            Duration::hours(1)
+        );
+        // The 5th has no time spent yet.
+        assert_eq!(
+            book.overtime(ShownTimeSpan::All, Duration::hours(8), true),
+            Duration::hours(-7)
+        );
+        assert_eq!(
+            book.overtime(ShownTimeSpan::Today, Duration::hours(8), false),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn busiest_day_has_the_most_time_spent() {
+        let mut book = TimeBook::default();
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        assert_eq!(book.busiest_day(ShownTimeSpan::All), None);
+
+        for (cat, day, hours) in [
+            ("test", 1, 3),
+            ("test", 2, 2),
+            ("test_second", 2, 2),
+            ("test", 3, 4),
+        ] {
+            let start = NaiveDate::from_ymd(2022, 1, day).and_hms(8, 0, 0);
+            book.add_time_usage(cat, start, start + Duration::hours(hours), None)
+                .unwrap();
+        }
+
+        // The 2nd and 3rd are tied and the earlier one wins.
+        assert_eq!(
+            book.busiest_day(ShownTimeSpan::All),
+            Some((NaiveDate::from_ymd(2022, 1, 2), Duration::hours(4)))
+        );
+
+        let start = NaiveDate::from_ymd(2022, 1, 3).and_hms(18, 0, 0);
+        book.add_time_usage("test", start, start + Duration::hours(1), None)
+            .unwrap();
+
+        assert_eq!(
+            book.busiest_day(ShownTimeSpan::All),
+            Some((NaiveDate::from_ymd(2022, 1, 3), Duration::hours(5)))
+        );
+    }
+
+    #[test]
+    fn json_entries_can_be_imported() {
+        let mut book = TimeBook::default();
+
+        let invalid = r#"[
+            {"category": "test", "start": "2022-01-01T09:00:00", "stop": "2022-01-01T10:00:00"},
+            {"category": "test", "start": "yesterday", "stop": "2022-01-01T10:00:00"}
+        ]"#;
+
+        assert!(matches!(
+            book.import_json_entries(invalid).unwrap_err(),
+            Error::InvalidImportEntry(1, _)
+        ));
+        assert!(book.categories().is_empty());
+
+        let valid = r#"[
+            {"category": "test", "start": "2022-01-01T09:00:00", "stop": "2022-01-01T10:00:00"},
+            {"category": "test_second", "start": "2022-01-01T10:00:00",
+             "stop": "2022-01-01T10:30:00", "desc": "Imported"}
+        ]"#;
+
+        assert_eq!(book.import_json_entries(valid).unwrap(), 2);
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            "1/1/2022 10:00 - 1/1/2022 10:30: test_second (ID: 0)\n\tImported\n\n1/1/2022 09:00 - 1/1/2022 10:00: test (ID: 0)\n\n"
+        );
+        assert!(matches!(
+            book.import_json_entries("{}").unwrap_err(),
+            Error::InvalidImport(_)
+        ));
+    }
+
+    #[test]
+    fn rounded_category_times_sum_to_rounded_total() {
+        let mut book = TimeBook::default();
+        for cat in ["a", "b", "c"] {
+            book.add_category(cat.to_string()).unwrap();
+        }
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("a", day.and_hms(8, 0, 0), day.and_hms(8, 20, 0), None)
+            .unwrap();
+        book.add_time_usage("b", day.and_hms(9, 0, 0), day.and_hms(9, 20, 0), None)
+            .unwrap();
+        book.add_time_usage("c", day.and_hms(10, 0, 0), day.and_hms(10, 35, 0), None)
+            .unwrap();
+
+        let (spent, total) = book.time_spent_all_rounded(
+            ShownTimeSpan::All,
+            Duration::hours(1),
+            RoundingMode::Nearest,
+        );
+
+        assert_eq!(total, Duration::hours(1));
+        assert_eq!(spent["a"], Duration::zero());
+        assert_eq!(spent["b"], Duration::zero());
+        assert_eq!(spent["c"], Duration::hours(1));
+        assert_eq!(spent.values().fold(Duration::zero(), |t, d| t + *d), total);
+
+        let (spent, total) =
+            book.time_spent_all_rounded(ShownTimeSpan::All, Duration::hours(1), RoundingMode::Up);
+        assert_eq!(total, Duration::hours(2));
+        assert_eq!(spent["a"], Duration::hours(1));
+        assert_eq!(spent["b"], Duration::zero());
+        assert_eq!(spent["c"], Duration::hours(1));
+    }
+
+    #[test]
+    fn relative_times_are_formatted() {
+        let now = NaiveDate::from_ymd(2022, 1, 10).and_hms(12, 0, 0);
+
+        assert_eq!(
+            format_relative(now - Duration::seconds(59), now),
+            "just now"
+        );
+        assert_eq!(
+            format_relative(now + Duration::seconds(10), now),
+            "just now"
+        );
+        assert_eq!(
+            format_relative(now - Duration::minutes(1), now),
+            "1 minute ago"
+        );
+        assert_eq!(
+            format_relative(now - Duration::minutes(5), now),
+            "5 minutes ago"
+        );
+        assert_eq!(
+            format_relative(now - Duration::minutes(179), now),
+            "2 hours ago"
+        );
+        assert_eq!(
+            format_relative(now - Duration::hours(3), now),
+            "3 hours ago"
+        );
+        assert_eq!(format_relative(now - Duration::hours(24), now), "1 day ago");
+        assert_eq!(format_relative(now - Duration::days(2), now), "2 days ago");
+        assert_eq!(format_relative(now + Duration::hours(3), now), "in 3 hours");
+    }
+
+    #[test]
+    fn usages_overlapping_interval_are_found() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        // Fully outside.
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(12, 0, 0), None)
+            .unwrap();
+        // Partially overlapping.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(11, 0, 0),
+            day.and_hms(13, 0, 0),
+            None,
+        )
+        .unwrap();
+        // Fully inside.
+        book.add_time_usage("test", day.and_hms(14, 0, 0), day.and_hms(15, 0, 0), None)
+            .unwrap();
+        // Fully outside.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(18, 0, 0),
+            day.and_hms(19, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        let found: Vec<(&str, usize)> = book
+            .usages_overlapping(day.and_hms(12, 0, 0), day.and_hms(18, 0, 0))
+            .into_iter()
+            .map(|(cat, id, _)| (cat, id))
+            .collect();
+
+        assert_eq!(found, vec![("test_second", 0), ("test", 1)]);
+    }
+
+    #[test]
+    fn last_category_is_the_one_stopped_last() {
+        let mut book = TimeBook::default();
+        assert_eq!(book.last_category(), None);
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_category("test_third".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        // Starts before but stops after the others.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(7, 0, 0),
+            day.and_hms(12, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_third",
+            day.and_hms(10, 0, 0),
+            day.and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.last_category(), Some("test_second"));
+    }
+
+    #[test]
+    fn billable_categories_can_be_set() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(9, 0, 0),
+            day.and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        book.set_billable("test_second", true).unwrap();
+        assert_eq!(
+            book.set_billable("test_third", true).unwrap_err(),
+            Error::CategoryDoesntExist("test_third".to_string())
+        );
+
+        let mut summary = book.summary(ShownTimeSpan::All, None).unwrap();
+        summary.retain(|c| book.is_billable(&c.category));
+        assert_eq!(summary.categories.len(), 1);
+        assert_eq!(summary.total, Duration::hours(2));
+
+        book.set_billable("test_second", false).unwrap();
+        assert!(!book.is_billable("test_second"));
+
+        book.set_billable("test", true).unwrap();
+        book.remove_category("test").unwrap();
+        book.add_category("test".to_string()).unwrap();
+        assert!(!book.is_billable("test"));
+    }
+
+    #[test]
+    fn time_by_tags_matches_all_or_any() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let tagged = |tags: &[&str]| UsageDetails {
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            ..Default::default()
+        };
+        book.add_time_usage_with(
+            "test",
+            day.and_hms(8, 0, 0),
+            day.and_hms(9, 0, 0),
+            tagged(&["client-x", "onsite"]),
+        )
+        .unwrap();
+        book.add_time_usage_with(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(11, 0, 0),
+            tagged(&["client-x"]),
+        )
+        .unwrap();
+        book.add_time_usage_with(
+            "test",
+            day.and_hms(11, 0, 0),
+            day.and_hms(14, 0, 0),
+            tagged(&["onsite", "other"]),
+        )
+        .unwrap();
+        book.add_time_usage_with(
+            "test",
+            day.and_hms(14, 0, 0),
+            day.and_hms(18, 0, 0),
+            tagged(&["other"]),
+        )
+        .unwrap();
+
+        let tags = vec!["client-x".to_string(), "onsite".to_string()];
+        assert_eq!(
+            book.time_by_tags(&tags, TagMatch::All, ShownTimeSpan::All),
+            Duration::hours(1)
+        );
+        assert_eq!(
+            book.time_by_tags(&tags, TagMatch::Any, ShownTimeSpan::All),
+            Duration::hours(6)
+        );
+    }
+
+    #[test]
+    fn datetimes_are_rounded() {
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let five = Duration::minutes(5);
+
+        assert_eq!(
+            round_datetime(day.and_hms(9, 2, 0), five, RoundingMode::Nearest),
+            day.and_hms(9, 0, 0)
+        );
+        assert_eq!(
+            round_datetime(day.and_hms(9, 3, 0), five, RoundingMode::Nearest),
+            day.and_hms(9, 5, 0)
+        );
+        assert_eq!(
+            round_datetime(day.and_hms(9, 4, 0), five, RoundingMode::Down),
+            day.and_hms(9, 0, 0)
+        );
+        assert_eq!(
+            round_datetime(day.and_hms(23, 58, 0), five, RoundingMode::Nearest),
+            NaiveDate::from_ymd(2022, 1, 2).and_hms(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn day_is_exported_as_escaped_html() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 30, 0),
+            Some("a < b & c".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(23, 0, 0) + Duration::days(1),
+            day.and_hms(23, 30, 0) + Duration::days(1),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.day_to_html(day),
+            "<table>\n<tr><th>Start</th><th>Stop</th><th>Duration</th><th>Category</th><th>Description</th></tr>\n<tr><td>09:00</td><td>10:30</td><td>1h 30m</td><td>test</td><td>a &lt; b &amp; c</td></tr>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn usages_crossing_midnight_are_split() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let next_day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_time_usage(
+            "test",
+            day.and_hms(22, 0, 0),
+            next_day.and_hms(2, 0, 0),
+            Some("Late".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            next_day.and_hms(10, 0, 0),
+            next_day.and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.normalize_midnight(), 1);
+        assert_eq!(book.normalize_midnight(), 0);
+
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            "2/1/2022 10:00 - 2/1/2022 11:00: test (ID: 2)\n\n2/1/2022 00:00 - 2/1/2022 02:00: test (ID: 1)\n\tLate\n\n1/1/2022 22:00 - 2/1/2022 00:00: test (ID: 0)\n\tLate\n\n"
+        );
+    }
+
+    #[test]
+    fn categories_cant_be_added_beyond_limit() {
+        let mut book = TimeBook::default();
+        book.set_max_categories(Some(2));
+
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        assert_eq!(
+            book.add_category("test_third".to_string()).unwrap_err(),
+            Error::CategoryLimitReached(2)
+        );
+        assert_eq!(
+            book.add_category("test".to_string()).unwrap_err(),
+            Error::CategoryExists("test".to_string())
+        );
+        assert_eq!(
+            book.import_json_entries(
+                r#"[{"category": "test_third", "start": "2022-01-01T09:00:00", "stop": "2022-01-01T10:00:00"}]"#
+            )
+            .unwrap_err(),
+            Error::CategoryLimitReached(2)
+        );
+
+        book.remove_category("test").unwrap();
+        book.add_category("test_third".to_string()).unwrap();
+
+        book.set_max_categories(None);
+        book.add_category("test_fourth".to_string()).unwrap();
+        assert_eq!(book.categories().len(), 3);
+    }
+
+    #[test]
+    fn usages_can_be_ranked_by_duration() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(8, 30, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(12, 0, 0), day.and_hms(12, 30, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(13, 0, 0), day.and_hms(14, 0, 0), None)
+            .unwrap();
+
+        let ranked = book.usages_by_duration("test", ShownTimeSpan::All).unwrap();
+        assert_eq!(
+            ranked,
+            vec![
+                (1, Duration::hours(2)),
+                (3, Duration::hours(1)),
+                (0, Duration::minutes(30)),
+                (2, Duration::minutes(30)),
+            ]
+        );
+
+        // The ids still refer to the same usages.
+        let usages = &book.time_map["test"];
+        for (id, duration) in ranked {
+            assert_eq!(usages[id].stop - usages[id].start, duration);
+        }
+
+        assert_eq!(
+            book.usages_by_duration("test_second", ShownTimeSpan::All)
+                .unwrap_err(),
+            Error::CategoryDoesntExist("test_second".to_string())
+        );
+    }
+
+    #[test]
+    fn descriptions_can_be_required() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+
+        book.set_require_description(true);
+        assert_eq!(
+            book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 0, 0), None)
+                .unwrap_err(),
+            Error::DescriptionRequired
+        );
+        assert_eq!(
+            book.add_time_usage(
+                "test",
+                day.and_hms(9, 0, 0),
+                day.and_hms(10, 0, 0),
+                Some(" \t".to_string())
+            )
+            .unwrap_err(),
+            Error::DescriptionRequired
+        );
+        book.add_time_usage(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            Some("Described".to_string()),
+        )
+        .unwrap();
+
+        book.start("test".to_string(), Some(day.and_hms(10, 0, 0)))
+            .unwrap();
+        assert_eq!(
+            book.stop(Some(day.and_hms(11, 0, 0)), Some(String::new()))
+                .unwrap_err(),
+            Error::DescriptionRequired
+        );
+        // The recording continues after a failed stop.
+        assert!(book.status().is_ok());
+        book.stop(Some(day.and_hms(11, 0, 0)), Some("Done".to_string()))
+            .unwrap();
+
+        assert_eq!(book.time_map["test"].len(), 3);
+    }
+
+    #[test]
+    fn cumulative_series_accumulates_time_spent() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(10, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(11, 0, 0), day.and_hms(11, 30, 0), None)
+            .unwrap();
+
+        let series = book.cumulative_series("test", ShownTimeSpan::All).unwrap();
+        assert_eq!(
+            series,
+            vec![
+                (day.and_hms(9, 0, 0), Duration::hours(1)),
+                (day.and_hms(10, 0, 0), Duration::hours(1)),
+                (day.and_hms(11, 30, 0), Duration::minutes(90)),
+            ]
+        );
+        assert!(series.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(
+            series.last().unwrap().1,
+            book.time_spent("test", ShownTimeSpan::All).unwrap()
+        );
+    }
+
+    #[test]
+    fn overlaps_are_found_and_resolved() {
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let new_book = || {
+            let mut book = TimeBook::default();
+            book.add_category("test".to_string()).unwrap();
+            book.add_time_usage(
+                "test",
+                day.and_hms(9, 0, 0),
+                day.and_hms(11, 0, 0),
+                Some("First".to_string()),
+            )
+            .unwrap();
+            book.add_time_usage(
+                "test",
+                day.and_hms(10, 0, 0),
+                day.and_hms(12, 0, 0),
+                Some("Second".to_string()),
+            )
+            .unwrap();
+            book.add_time_usage("test", day.and_hms(13, 0, 0), day.and_hms(14, 0, 0), None)
+                .unwrap();
+            book
+        };
+        let intervals = |book: &TimeBook| -> Vec<(NaiveDateTime, NaiveDateTime)> {
+            book.time_map["test"]
+                .iter()
+                .map(|u| (u.start, u.stop))
+                .collect()
+        };
+
+        let mut book = new_book();
+        assert_eq!(book.overlapping_usages(), vec![("test".to_string(), 0, 1)]);
+        assert_eq!(
+            book.resolve_overlap("test", 1, 2, OverlapStrategy::Merge)
+                .unwrap_err(),
+            Error::UsagesDontOverlap(1, 2)
+        );
+        assert_eq!(
+            book.resolve_overlap("test", 0, 3, OverlapStrategy::Merge)
+                .unwrap_err(),
+            Error::TimeUsageDoesntExist(3)
+        );
+
+        book.resolve_overlap("test", 1, 0, OverlapStrategy::TrimFirst)
+            .unwrap();
+        assert_eq!(
+            intervals(&book)[..2],
+            [
+                (day.and_hms(9, 0, 0), day.and_hms(10, 0, 0)),
+                (day.and_hms(10, 0, 0), day.and_hms(12, 0, 0)),
+            ]
+        );
+        assert!(book.overlapping_usages().is_empty());
+
+        let mut book = new_book();
+        book.resolve_overlap("test", 0, 1, OverlapStrategy::TrimSecond)
+            .unwrap();
+        assert_eq!(
+            intervals(&book)[..2],
+            [
+                (day.and_hms(9, 0, 0), day.and_hms(11, 0, 0)),
+                (day.and_hms(11, 0, 0), day.and_hms(12, 0, 0)),
+            ]
+        );
+
+        let mut book = new_book();
+        book.resolve_overlap("test", 0, 1, OverlapStrategy::Merge)
+            .unwrap();
+        assert_eq!(
+            intervals(&book),
+            [
+                (day.and_hms(9, 0, 0), day.and_hms(12, 0, 0)),
+                (day.and_hms(13, 0, 0), day.and_hms(14, 0, 0)),
+            ]
+        );
+        assert_eq!(
+            book.time_map["test"][0].desc,
+            Some("First; Second".to_string())
+        );
+    }
+
+    #[test]
+    fn sessions_are_counted_within_span() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.set_now(Some(NaiveDate::from_ymd(2022, 1, 10).and_hms(12, 0, 0)));
+
+        let today = NaiveDate::from_ymd(2022, 1, 10);
+        let earlier = NaiveDate::from_ymd(2021, 6, 1);
+        book.add_time_usage("test", today.and_hms(8, 0, 0), today.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test",
+            today.and_hms(10, 0, 0),
+            today.and_hms(10, 5, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            earlier.and_hms(8, 0, 0),
+            earlier.and_hms(9, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.session_count("test", ShownTimeSpan::Today).unwrap(), 2);
+        assert_eq!(book.session_count("test", ShownTimeSpan::All).unwrap(), 3);
+        assert_eq!(
+            book.session_count("test_second", ShownTimeSpan::All)
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            book.session_count("test_third", ShownTimeSpan::All)
+                .unwrap_err(),
+            Error::CategoryDoesntExist("test_third".to_string())
+        );
+    }
+
+    #[test]
+    fn human_durations_roundtrip_through_strings() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Goal {
+            #[serde(with = "duration_human")]
+            target: Duration,
+        }
+
+        for (target, json) in [
+            (Duration::hours(5), r#"{"target":"5h"}"#),
+            (Duration::minutes(90), r#"{"target":"1h30m"}"#),
+            (Duration::seconds(45), r#"{"target":"45s"}"#),
+            (Duration::zero(), r#"{"target":"0s"}"#),
+        ] {
+            let goal = Goal { target };
+            assert_eq!(serde_json::to_string(&goal).unwrap(), json);
+            assert_eq!(serde_json::from_str::<Goal>(json).unwrap(), goal);
+        }
+
+        assert_eq!(
+            serde_json::from_str::<Goal>(r#"{"target":"90m"}"#).unwrap(),
+            Goal {
+                target: Duration::minutes(90)
+            }
+        );
+        assert!(serde_json::from_str::<Goal>(r#"{"target":"soon"}"#).is_err());
+    }
+
+    #[test]
+    fn weekend_time_counts_saturdays_and_sundays() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        // 2022-01-07 is a Friday.
+        let friday = NaiveDate::from_ymd(2022, 1, 7);
+        let saturday = NaiveDate::from_ymd(2022, 1, 8);
+        let sunday = NaiveDate::from_ymd(2022, 1, 9);
+        let monday = NaiveDate::from_ymd(2022, 1, 10);
+
+        book.add_time_usage(
+            "test",
+            friday.and_hms(22, 0, 0),
+            saturday.and_hms(1, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            saturday.and_hms(10, 0, 0),
+            saturday.and_hms(12, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            sunday.and_hms(10, 0, 0),
+            sunday.and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            monday.and_hms(8, 0, 0),
+            monday.and_hms(16, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.weekend_time(ShownTimeSpan::All, None),
+            Duration::hours(3)
+        );
+        assert_eq!(
+            book.weekend_time(ShownTimeSpan::All, Some("test")),
+            Duration::hours(2)
+        );
+        assert_eq!(
+            book.weekend_time(ShownTimeSpan::All, Some("test_third")),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn usage_reference_is_shown_in_log() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage_with(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            UsageDetails {
+                desc: Some("Fix bug".to_string()),
+                reference: Some("https://example.com/issues/1".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            "1/1/2022 09:00 - 1/1/2022 10:00: test (ID: 0)\n\tFix bug\n\tRef: https://example.com/issues/1\n\n"
+        );
+        assert!(book.roundtrip_ok());
+    }
+
+    #[test]
+    fn lifetime_total_equals_time_spent_on_all() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.set_now(Some(NaiveDate::from_ymd(2022, 1, 10).and_hms(12, 0, 0)));
+
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2019, 3, 1).and_hms(8, 0, 0),
+            NaiveDate::from_ymd(2019, 3, 1).and_hms(9, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 10).and_hms(8, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 10).and_hms(10, 30, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.lifetime_total("test").unwrap(), Duration::minutes(210));
+        assert_eq!(
+            book.lifetime_total("test").unwrap(),
+            book.time_spent("test", ShownTimeSpan::All).unwrap()
+        );
+        assert_eq!(
+            book.lifetime_total("test_second").unwrap_err(),
+            Error::CategoryDoesntExist("test_second".to_string())
+        );
+    }
+
+    #[test]
+    fn csv_export_can_be_filtered_by_description() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage(
+            "test",
+            day.and_hms(8, 0, 0),
+            day.and_hms(9, 0, 0),
+            Some("PROJ-42 planning".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            Some("PROJ-7".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(10, 0, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+
+        assert_eq!(
+            book.to_csv_filtered(ShownTimeSpan::All, Some("PROJ-42")),
+            "category,start,stop,duration_minutes,description,tags\ntest,2022-01-01T08:00:00,2022-01-01T09:00:00,60,PROJ-42 planning,\n"
+        );
+        assert_eq!(
+            book.to_csv_filtered(ShownTimeSpan::All, Some("PROJ-1")),
+            "category,start,stop,duration_minutes,description,tags\n"
+        );
+        assert_eq!(
+            book.to_csv_filtered(ShownTimeSpan::All, None),
+            book.to_csv(ShownTimeSpan::All)
+        );
+    }
+
+    #[test]
+    fn median_start_time_picks_lower_middle() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        assert_eq!(book.median_start_time(ShownTimeSpan::All, None), None);
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        for (i, (h, m)) in [(9, 30), (7, 0), (8, 15)].into_iter().enumerate() {
+            let start = (day + Duration::days(i as i64)).and_hms(h, m, 0);
+            book.add_time_usage("test", start, start + Duration::hours(1), None)
+                .unwrap();
+        }
+
+        assert_eq!(
+            book.median_start_time(ShownTimeSpan::All, None),
+            Some(NaiveTime::from_hms(8, 15, 0))
+        );
+
+        let start = day.and_hms(11, 0, 0);
+        book.add_time_usage("test_second", start, start + Duration::hours(1), None)
+            .unwrap();
+        assert_eq!(
+            book.median_start_time(ShownTimeSpan::All, None),
+            Some(NaiveTime::from_hms(8, 15, 0))
+        );
+        assert_eq!(
+            book.median_start_time(ShownTimeSpan::All, Some("test_second")),
+            Some(NaiveTime::from_hms(11, 0, 0))
+        );
+        assert_eq!(
+            book.median_start_time(ShownTimeSpan::All, Some("test_third")),
+            None
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn book_is_exported_to_sqlite() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_category("test_third".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            Some("Desc".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(10, 0, 0),
+            day.and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        let path = std::env::temp_dir().join(format!("vihr_sqlite_test_{}.db", std::process::id()));
+        book.to_sqlite(&path).unwrap();
+        // Exporting again would overwrite the data.
+        assert!(matches!(
+            book.to_sqlite(&path),
+            Err(Error::ExportTargetNotEmpty(_))
+        ));
+
+        let conn = rusqlite::Connection::open(&path).unwrap();
+        let count = |table: &str| -> usize {
+            conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+        };
+        assert_eq!(count("categories"), 3);
+        assert_eq!(count("usages"), 3);
+
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn daily_spans_include_gaps() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let next_day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(12, 0, 0),
+            day.and_hms(16, 30, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(13, 0, 0), day.and_hms(14, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test",
+            next_day.and_hms(10, 0, 0),
+            next_day.and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.daily_spans(ShownTimeSpan::All),
+            vec![
+                (day, Duration::minutes(510)),
+                (next_day, Duration::hours(1))
+            ]
+        );
+        assert_eq!(
+            book.daily_totals(ShownTimeSpan::All, None)[&day],
+            Duration::minutes(390)
+        );
+    }
+
+    #[test]
+    fn pinned_categories_are_listed_first() {
+        let mut book = TimeBook::default();
+        for cat in ["a", "b", "c", "d"] {
+            book.add_category(cat.to_string()).unwrap();
+        }
+        assert_eq!(book.categories(), vec!["a", "b", "c", "d"]);
+
+        book.pin_category("d").unwrap();
+        book.pin_category("b").unwrap();
+        book.pin_category("d").unwrap();
+        assert_eq!(
+            book.pin_category("e").unwrap_err(),
+            Error::CategoryDoesntExist("e".to_string())
+        );
+        assert_eq!(book.categories(), vec!["d", "b", "a", "c"]);
+
+        let summary = book.summary(ShownTimeSpan::All, None).unwrap();
+        let names: Vec<&str> = summary
+            .categories
+            .iter()
+            .map(|c| c.category.as_str())
+            .collect();
+        assert_eq!(names, vec!["d", "b", "a", "c"]);
+
+        book.unpin_category("d").unwrap();
+        assert_eq!(book.categories(), vec!["b", "a", "c", "d"]);
+
+        book.remove_category("b").unwrap();
+        book.add_category("b".to_string()).unwrap();
+        assert_eq!(book.categories(), vec!["a", "b", "c", "d"]);
+
+        book.pin_category("c").unwrap();
+        book.remove_category_reassign("c", "a").unwrap();
+        assert!(book.favorites.is_empty());
+    }
+
+    #[test]
+    fn plot_data_has_local_epochs_and_durations() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 30, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(23, 0, 0), day.and_hms(23, 0, 5), None)
+            .unwrap();
+
+        let data = book.plot_data("test", ShownTimeSpan::All).unwrap();
+        assert_eq!(data[0], (1641024000, 1641029400, 5400));
+        for (start, stop, duration) in data {
+            assert_eq!(duration, stop - start);
+        }
+
+        assert_eq!(
+            book.plot_data("test_second", ShownTimeSpan::All)
+                .unwrap_err(),
+            Error::CategoryDoesntExist("test_second".to_string())
+        );
+    }
+
+    #[test]
+    fn start_within_grace_reopens_last_usage() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.set_restart_grace(Some(Duration::seconds(30)));
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage_with(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            UsageDetails {
+                tags: vec!["kept".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // Another category is never reopened.
+        book.start("test_second".to_string(), Some(day.and_hms(10, 0, 10)))
+            .unwrap();
+        book.cancel().unwrap();
+
+        book.start("test".to_string(), Some(day.and_hms(10, 0, 20)))
+            .unwrap();
+        assert_eq!(book.status().unwrap(), ("test", day.and_hms(9, 0, 0)));
+        assert!(book.time_map["test"].is_empty());
+
+        book.stop(Some(day.and_hms(11, 0, 0)), None).unwrap();
+        assert_eq!(book.time_map["test"].len(), 1);
+        assert_eq!(book.time_map["test"][0].start, day.and_hms(9, 0, 0));
+        assert_eq!(book.time_map["test"][0].tags, vec!["kept".to_string()]);
+
+        // Outside the grace period a new usage is created.
+        book.start("test".to_string(), Some(day.and_hms(11, 0, 31)))
+            .unwrap();
+        assert_eq!(book.status().unwrap(), ("test", day.and_hms(11, 0, 31)));
+        book.stop(Some(day.and_hms(12, 0, 0)), None).unwrap();
+        assert_eq!(book.time_map["test"].len(), 2);
+
+        // Without a grace period nothing is reopened.
+        book.set_restart_grace(None);
+        book.start("test".to_string(), Some(day.and_hms(12, 0, 0)))
+            .unwrap();
+        assert_eq!(book.status().unwrap(), ("test", day.and_hms(12, 0, 0)));
+    }
+
+    #[test]
+    fn work_week_total_excludes_weekend() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        // 2022-01-03 is a Monday.
+        let monday = NaiveDate::from_ymd(2022, 1, 3);
+        let friday = NaiveDate::from_ymd(2022, 1, 7);
+        let saturday = NaiveDate::from_ymd(2022, 1, 8);
+        let previous_sunday = NaiveDate::from_ymd(2022, 1, 2);
+
+        book.add_time_usage(
+            "test",
+            monday.and_hms(8, 0, 0),
+            monday.and_hms(16, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            friday.and_hms(8, 0, 0),
+            friday.and_hms(12, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            saturday.and_hms(8, 0, 0),
+            saturday.and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            previous_sunday.and_hms(8, 0, 0),
+            previous_sunday.and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.work_week_total(None, saturday), Duration::hours(12));
+        assert_eq!(
+            book.work_week_total(Some("test"), NaiveDate::from_ymd(2022, 1, 5)),
+            Duration::hours(12)
+        );
+        assert_eq!(
+            book.work_week_total(None, previous_sunday),
+            Duration::zero()
+        );
+        assert_eq!(
+            book.work_week_total(Some("test_second"), monday),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn shared_descriptions_are_grouped() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(8, 0, 0),
+            day.and_hms(9, 0, 0),
+            Some("Meeting".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            Some("Meeting".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(10, 0, 0),
+            day.and_hms(11, 0, 0),
+            Some("meeting".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(11, 0, 0), day.and_hms(12, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(12, 0, 0), day.and_hms(13, 0, 0), None)
+            .unwrap();
+
+        assert_eq!(
+            book.description_groups(),
+            vec![(
+                "Meeting".to_string(),
+                vec![("test".to_string(), 0), ("test_second".to_string(), 0)]
+            )]
+        );
+    }
+
+    #[test]
+    fn remaining_to_target_counts_today() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        let today = NaiveDate::from_ymd(2022, 1, 10);
+        book.set_now(Some(today.and_hms(15, 0, 0)));
+
+        book.add_time_usage(
+            "test",
+            today.and_hms(8, 0, 0),
+            today.and_hms(12, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            today.and_hms(8, 0, 0) - Duration::days(1),
+            today.and_hms(16, 0, 0) - Duration::days(1),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.remaining_to_target(Duration::hours(8), false),
+            Duration::hours(4)
+        );
+        assert_eq!(
+            book.remaining_to_target(Duration::hours(3), false),
+            Duration::zero()
+        );
+
+        book.start("test".to_string(), Some(today.and_hms(13, 0, 0)))
+            .unwrap();
+        assert_eq!(
+            book.remaining_to_target(Duration::hours(8), false),
+            Duration::hours(4)
+        );
+        assert_eq!(
+            book.remaining_to_target(Duration::hours(8), true),
+            Duration::hours(2)
+        );
+        assert_eq!(
+            book.remaining_to_target(Duration::hours(5), true),
+            Duration::zero()
+        );
+
+        // Paused time isn't counted.
+        book.pause().unwrap();
+        book.set_now(Some(today.and_hms(16, 0, 0)));
+        assert_eq!(
+            book.remaining_to_target(Duration::hours(8), true),
+            Duration::hours(2)
+        );
+        book.resume().unwrap();
+        book.set_now(Some(today.and_hms(16, 30, 0)));
+        assert_eq!(
+            book.remaining_to_target(Duration::hours(8), true),
+            Duration::minutes(90)
+        );
+    }
+
+    #[test]
+    fn pivot_csv_has_month_columns() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        let march = NaiveDate::from_ymd(2024, 3, 5);
+        book.add_time_usage(
+            "test",
+            march.and_hms(8, 0, 0),
+            march.and_hms(9, 30, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            march.and_hms(10, 0, 0),
+            march.and_hms(10, 20, 0),
+            None,
+        )
+        .unwrap();
+        let other_year = NaiveDate::from_ymd(2023, 3, 5);
+        book.add_time_usage(
+            "test_second",
+            other_year.and_hms(8, 0, 0),
+            other_year.and_hms(9, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        let csv = book.to_pivot_csv(2024);
+        let rows: Vec<Vec<&str>> = csv.lines().map(|l| l.split(',').collect()).collect();
+
+        assert_eq!(rows.len(), 3);
+        assert!(rows.iter().all(|r| r.len() == 13));
+        assert_eq!(rows[0][3], "Mar");
+        assert_eq!(rows[1][0], "test");
+        assert_eq!(rows[1][3], "1.83");
+        assert_eq!(rows[1][4], "0");
+        assert_eq!(rows[2][3], "0");
+    }
+
+    #[test]
+    fn now_offset_shifts_today() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        let day = NaiveDate::from_ymd(2022, 1, 10);
+        book.set_now(Some(day.and_hms(12, 0, 0)));
+
+        let yesterday = day - Duration::days(1);
+        book.add_time_usage(
+            "test",
+            yesterday.and_hms(8, 0, 0),
+            yesterday.and_hms(9, 0, 0),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Today).unwrap(),
+            Duration::zero()
+        );
+
+        book.set_now_offset(-Duration::days(1));
+        assert_eq!(book.now(), yesterday.and_hms(12, 0, 0));
+        assert_eq!(
+            book.time_spent("test", ShownTimeSpan::Today).unwrap(),
+            Duration::hours(1)
+        );
+    }
+
+    #[test]
+    fn blocks_merge_small_gaps() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(10, 3, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(11, 5, 0), day.and_hms(12, 0, 0), None)
+            .unwrap();
+
+        assert_eq!(
+            book.contiguous_blocks("test", ShownTimeSpan::All, Duration::minutes(5))
+                .unwrap(),
+            vec![
+                (day.and_hms(9, 0, 0), day.and_hms(11, 0, 0)),
+                (day.and_hms(11, 5, 0), day.and_hms(12, 0, 0)),
+            ]
+        );
+        assert_eq!(
+            book.contiguous_blocks("test", ShownTimeSpan::All, Duration::zero())
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(book.time_map["test"].len(), 3);
+    }
+
+    #[test]
+    fn time_usage_can_be_adjusted() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(11, 0, 0), day.and_hms(12, 0, 0), None)
+            .unwrap();
+
+        book.adjust_time_usage("test", 0, Duration::zero(), -Duration::minutes(20))
+            .unwrap();
+        assert_eq!(book.time_map["test"][0].stop, day.and_hms(9, 40, 0));
+
+        // Moving a usage past another one keeps the usages sorted.
+        book.adjust_time_usage("test", 0, Duration::hours(3), Duration::hours(3))
+            .unwrap();
+        assert_eq!(book.time_map["test"][0].start, day.and_hms(11, 0, 0));
+        assert_eq!(
+            book.time_map["test"][1].start..book.time_map["test"][1].stop,
+            day.and_hms(12, 0, 0)..day.and_hms(12, 40, 0)
+        );
+
+        assert_eq!(
+            book.adjust_time_usage("test", 1, Duration::hours(1), Duration::zero())
+                .unwrap_err(),
+            Error::StopBeforeStart(1)
+        );
+        assert_eq!(book.time_map["test"][1].start, day.and_hms(12, 0, 0));
+        assert_eq!(
+            book.adjust_time_usage("test", 2, Duration::zero(), Duration::zero())
+                .unwrap_err(),
+            Error::TimeUsageDoesntExist(2)
+        );
+    }
+
+    #[test]
+    fn best_streak_category_has_longest_run_of_days() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        assert_eq!(book.best_streak_category(), None);
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        for offset in [0, 2, 4, 5] {
+            let start = (day + Duration::days(offset)).and_hms(8, 0, 0);
+            book.add_time_usage("test", start, start + Duration::hours(1), None)
+                .unwrap();
+        }
+        for offset in [10, 11, 11, 12, 20] {
+            let start = (day + Duration::days(offset)).and_hms(8, 0, 0);
+            book.add_time_usage("test_second", start, start + Duration::hours(1), None)
+                .unwrap();
+        }
+
+        assert_eq!(
+            book.best_streak_category(),
+            Some(("test_second".to_string(), 3))
+        );
+    }
+
+    #[test]
+    fn last_cancelled_is_most_recent() {
+        let mut book = TimeBook::default();
+        let start = NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        assert_eq!(book.last_cancelled(), None);
+
+        book.set_now(Some(start + Duration::minutes(5)));
+        book.start("test".to_string(), Some(start)).unwrap();
+        book.cancel_logged(None).unwrap();
+
+        book.set_now(Some(start + Duration::minutes(30)));
+        book.start(
+            "test_second".to_string(),
+            Some(start + Duration::minutes(18)),
+        )
+        .unwrap();
+        book.cancel_logged(None).unwrap();
+
+        let last = book.last_cancelled().unwrap();
+        assert_eq!(last.category, "test_second");
+        assert_eq!(last.cancelled_at - last.start, Duration::minutes(12));
+    }
+
+    #[test]
+    fn per_active_day_ignores_inactive_days() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let later = NaiveDate::from_ymd(2022, 1, 5);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(10, 0, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", later.and_hms(8, 0, 0), later.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(12, 0, 0) + Duration::days(1),
+            day.and_hms(13, 0, 0) + Duration::days(1),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.per_active_day("test", ShownTimeSpan::All).unwrap(),
+            Some(Duration::minutes(90))
+        );
+
+        book.add_category("test_third".to_string()).unwrap();
+        assert_eq!(
+            book.per_active_day("test_third", ShownTimeSpan::All)
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            book.per_active_day("test_fourth", ShownTimeSpan::All)
+                .unwrap_err(),
+            Error::CategoryDoesntExist("test_fourth".to_string())
+        );
+    }
+
+    #[test]
+    fn out_of_order_usages_are_sorted() {
+        let json = r#"{
+            "current_cat": null,
+            "current_cat_start": null,
+            "time_map": {"test": [
+                {"start": "2022-01-01T11:00:00", "stop": "2022-01-01T12:00:00", "desc": "Second"},
+                {"start": "2022-01-01T09:00:00", "stop": "2022-01-01T10:00:00", "desc": "First"}
+            ]}
+        }"#;
+        let mut book: TimeBook = serde_json::from_str(json).unwrap();
+
+        assert!(book.ensure_sorted());
+        assert!(!book.ensure_sorted());
+
+        assert_eq!(book.last_category(), Some("test"));
+        assert_eq!(
+            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
+            "1/1/2022 11:00 - 1/1/2022 12:00: test (ID: 1)\n\tSecond\n\n1/1/2022 09:00 - 1/1/2022 10:00: test (ID: 0)\n\tFirst\n\n"
+        );
+        book.remove_time_usage("test", 0).unwrap();
+        assert_eq!(book.time_map["test"][0].desc, Some("Second".to_string()));
+    }
+
+    #[test]
+    fn description_template_is_expanded() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.set_description_template(Some("{category}: {date} {start}-{stop}".to_string()));
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 30, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(11, 0, 0),
+            day.and_hms(12, 0, 0),
+            Some("Given".to_string()),
+        )
+        .unwrap();
+        book.start("test".to_string(), Some(day.and_hms(13, 0, 0)))
+            .unwrap();
+        book.stop(Some(day.and_hms(14, 0, 0)), None).unwrap();
+
+        let descs: Vec<Option<&str>> = book.time_map["test"]
+            .iter()
+            .map(|u| u.desc.as_deref())
+            .collect();
+        assert_eq!(
+            descs,
+            vec![
+                Some("test: 2022-01-01 09:00-10:30"),
+                Some("Given"),
+                Some("test: 2022-01-01 13:00-14:00"),
+            ]
+        );
+
+        book.set_description_template(None);
+        book.add_time_usage("test", day.and_hms(15, 0, 0), day.and_hms(16, 0, 0), None)
+            .unwrap();
+        assert_eq!(book.time_map["test"][3].desc, None);
+    }
+
+    #[test]
+    fn distribution_shares_sum_to_one() {
+        let mut book = TimeBook::default();
+        for cat in ["a", "b", "c", "d"] {
+            book.add_category(cat.to_string()).unwrap();
+        }
+        assert!(book.distribution(ShownTimeSpan::All).is_empty());
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("a", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("b", day.and_hms(9, 0, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("c", day.and_hms(11, 0, 0), day.and_hms(11, 20, 0), None)
+            .unwrap();
+
+        let shares = book.distribution(ShownTimeSpan::All);
+        let names: Vec<&str> = shares.iter().map(|(c, _)| c.as_str()).collect();
+        assert_eq!(names, vec!["b", "a", "c"]);
+        assert!((shares[0].1 - 0.6).abs() < 1e-9);
+
+        let sum: f64 = shares.iter().map(|(_, share)| share).sum();
+        assert!((sum - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn usages_before_cutoff_are_purged() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        let old = NaiveDate::from_ymd(2021, 12, 31);
+        let cutoff = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", old.and_hms(23, 0, 0), cutoff.and_hms(1, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test",
+            cutoff.and_hms(0, 0, 0),
+            cutoff.and_hms(1, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            old.and_hms(8, 0, 0),
+            old.and_hms(9, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.purge_before(cutoff), 2);
+        assert_eq!(book.time_map["test"].len(), 1);
+        assert_eq!(book.time_map["test"][0].start, cutoff.and_hms(0, 0, 0));
+        assert!(book.time_map["test_second"].is_empty());
+        assert_eq!(book.purge_before(cutoff), 0);
+    }
+
+    #[test]
+    fn tracking_since_is_earliest_start() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_category("test_third".to_string()).unwrap();
+        assert_eq!(book.tracking_since(), None);
+
+        let day = NaiveDate::from_ymd(2022, 1, 5);
+        let earliest = NaiveDate::from_ymd(2021, 11, 30);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            earliest.and_hms(23, 0, 0),
+            day.and_hms(1, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(10, 0, 0),
+            day.and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.tracking_since(), Some(earliest));
+    }
+
+    #[test]
+    fn finished_task_is_logged_until_now() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        let now = NaiveDate::from_ymd(2022, 1, 1).and_hms(12, 0, 0);
+        book.set_now(Some(now));
+
+        book.log_finished("test", Duration::minutes(45), Some("Tests".to_string()))
+            .unwrap();
+        assert_eq!(book.time_map["test"][0].start, now - Duration::minutes(45));
+        assert_eq!(book.time_map["test"][0].stop, now);
+
+        assert_eq!(
+            book.log_finished("test", Duration::hours(25), None)
+                .unwrap_err(),
+            Error::DurationTooLong(Duration::days(1))
+        );
+        assert_eq!(
+            book.log_finished("test", Duration::minutes(-30), None)
+                .unwrap_err(),
+            Error::DurationNotPositive
+        );
+        assert_eq!(
+            book.log_finished("test", Duration::zero(), None)
+                .unwrap_err(),
+            Error::DurationNotPositive
+        );
+        assert_eq!(
+            book.log_finished("test_second", Duration::hours(1), None)
+                .unwrap_err(),
+            Error::CategoryDoesntExist("test_second".to_string())
+        );
+        assert_eq!(book.time_map["test"].len(), 1);
+    }
+
+    #[test]
+    fn toggl_csv_has_toggl_columns() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.set_billable("test", true).unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 5, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 35, 30),
+            Some("Tests, more tests".to_string()),
+        )
+        .unwrap();
+
+        let csv = book.to_toggl_csv(ShownTimeSpan::All);
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "Email,Project,Client,Description,Billable,Start date,Start time,Duration,Tags"
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            ",test,,\"Tests, more tests\",Yes,2022-01-01,09:05:00,01:30:30,"
+        );
+        assert_eq!(lines.next(), None);
+    }
+
+    #[test]
+    fn time_since_last_pause_counts_from_resume() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+
+        assert_eq!(
+            book.time_since_last_pause().unwrap_err(),
+            Error::NotRecordingTime
+        );
+        assert_eq!(book.pause().unwrap_err(), Error::NotRecordingTime);
+
+        book.set_now(Some(day.and_hms(9, 0, 0)));
+        book.start("test".to_string(), None).unwrap();
+        assert_eq!(book.resume().unwrap_err(), Error::NotPaused);
+
+        book.set_now(Some(day.and_hms(10, 0, 0)));
+        assert_eq!(book.time_since_last_pause().unwrap(), Duration::hours(1));
+
+        book.pause().unwrap();
+        assert_eq!(book.pause().unwrap_err(), Error::AlreadyPaused);
+        book.set_now(Some(day.and_hms(10, 30, 0)));
+        assert!(book.is_paused());
+        assert_eq!(book.time_since_last_pause().unwrap(), Duration::zero());
+
+        book.resume().unwrap();
+        book.set_now(Some(day.and_hms(11, 15, 0)));
+        assert_eq!(book.time_since_last_pause().unwrap(), Duration::minutes(45));
+
+        book.stop(None, None).unwrap();
+        assert_eq!(book.time_map["test"].len(), 2);
+        assert_eq!(book.time_map["test"][0].start, day.and_hms(9, 0, 0));
+        assert_eq!(book.time_map["test"][0].stop, day.and_hms(10, 0, 0));
+        assert_eq!(book.time_map["test"][1].start, day.and_hms(10, 30, 0));
+        assert_eq!(book.time_map["test"][1].stop, day.and_hms(11, 15, 0));
+        assert!(book.current_pauses.is_empty());
+    }
+
+    #[test]
+    fn day_distribution_splits_usages_at_midnight() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_category("unused".to_string()).unwrap();
+
+        // Starts the previous day, so only 2 hours are counted.
+        book.add_time_usage(
+            "test",
+            day.pred().and_hms(22, 0, 0),
+            day.and_hms(2, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+        // Continues to the next day, so only 1 hour is counted.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(23, 0, 0),
+            day.succ().and_hms(3, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.day_distribution(day),
+            vec![
+                ("test".to_string(), Duration::hours(3)),
+                ("test_second".to_string(), Duration::hours(1)),
+            ]
+        );
+        assert_eq!(
+            book.day_distribution(day.succ()),
+            vec![("test_second".to_string(), Duration::hours(3))]
+        );
+        assert!(book.day_distribution(day.succ().succ()).is_empty());
+    }
+
+    #[test]
+    fn balance_score_measures_evenness() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        assert_eq!(book.balance_score(ShownTimeSpan::All), 0.0);
+
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+        assert_eq!(book.balance_score(ShownTimeSpan::All), 0.0);
+
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(12, 0, 0),
+            day.and_hms(14, 0, 0),
+            None,
+        )
+        .unwrap();
+        assert!((book.balance_score(ShownTimeSpan::All) - 1.0).abs() < 1e-9);
+
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(15, 0, 0),
+            day.and_hms(19, 0, 0),
+            None,
+        )
+        .unwrap();
+        let score = book.balance_score(ShownTimeSpan::All);
+        assert!(score > 0.0 && score < 1.0);
+    }
+
+    #[test]
+    fn goal_streak_stops_at_first_miss() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        let today = NaiveDate::from_ymd(2022, 1, 10);
+        book.set_now(Some(today.and_hms(12, 0, 0)));
+        let target = Duration::hours(8);
+
+        assert_eq!(book.goal_streak(target), 0);
+
+        let mut add_day = |date: NaiveDate, hours: u32| {
+            book.add_time_usage(
+                "test",
+                date.and_hms(8, 0, 0),
+                date.and_hms(8 + hours, 0, 0),
+                None,
+            )
+            .unwrap();
+        };
+        // Met days broken by a miss on the 6th.
+        add_day(NaiveDate::from_ymd(2022, 1, 5), 8);
+        add_day(NaiveDate::from_ymd(2022, 1, 6), 7);
+        add_day(NaiveDate::from_ymd(2022, 1, 7), 9);
+        add_day(NaiveDate::from_ymd(2022, 1, 8), 8);
+        add_day(NaiveDate::from_ymd(2022, 1, 9), 10);
+        // Not met yet today.
+        add_day(today, 2);
+
+        assert_eq!(book.goal_streak(target), 3);
+
+        book.add_time_usage("test", today.and_hms(0, 0, 0), today.and_hms(6, 0, 0), None)
+            .unwrap();
+        assert_eq!(book.goal_streak(target), 4);
+    }
+
+    #[test]
+    fn text_table_aligns_columns() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_category("a".to_string()).unwrap();
+        book.add_category("a_category_with_a_very_long_name".to_string())
+            .unwrap();
+        book.add_time_usage("a", day.and_hms(9, 0, 0), day.and_hms(11, 30, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "a_category_with_a_very_long_name",
+            day.and_hms(12, 0, 0),
+            day.and_hms(12, 5, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.to_text_table(ShownTimeSpan::All),
+            "+----------------------+--------+\n\
+             | Category             |   Time |\n\
+             +----------------------+--------+\n\
+             | a                    | 2h 30m |\n\
+             | a_category_with_a_v… |     5m |\n\
+             +----------------------+--------+\n\
+             | Total                | 2h 35m |\n\
+             +----------------------+--------+\n"
+        );
+    }
+
+    #[test]
+    fn active_categories_in_month_uses_start_month() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_category("unused".to_string()).unwrap();
+
+        // Straddles the boundary between January and February.
+        book.add_time_usage(
+            "test_second",
+            NaiveDate::from_ymd(2022, 1, 31).and_hms(23, 0, 0),
+            NaiveDate::from_ymd(2022, 2, 1).and_hms(1, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 10).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 10).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        // The same month of another year.
+        book.add_time_usage(
+            "unused",
+            NaiveDate::from_ymd(2021, 2, 10).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2021, 2, 10).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.active_categories_in_month(2022, 1),
+            vec!["test", "test_second"]
+        );
+        assert!(book.active_categories_in_month(2022, 2).is_empty());
+        assert_eq!(book.active_categories_in_month(2021, 2), vec!["unused"]);
+    }
+
+    #[test]
+    fn nonzero_durations_can_be_rounded_up_to_a_granularity() {
+        let quarter = Duration::minutes(15);
+        let short = Duration::minutes(3);
+
+        assert_eq!(
+            round_duration(short, quarter, RoundingMode::Nearest),
+            Duration::zero()
+        );
+        for mode in [RoundingMode::Nearest, RoundingMode::Down, RoundingMode::Up] {
+            assert_eq!(round_duration_with(short, quarter, mode, true), quarter);
+        }
+
+        // Durations that round to more than zero and zero itself are unaffected.
+        assert_eq!(
+            round_duration_with(Duration::minutes(17), quarter, RoundingMode::Down, true),
+            quarter
+        );
+        assert_eq!(
+            round_duration_with(Duration::minutes(25), quarter, RoundingMode::Nearest, true),
+            Duration::minutes(30)
+        );
+        assert_eq!(
+            round_duration_with(Duration::zero(), quarter, RoundingMode::Up, true),
+            Duration::zero()
+        );
+    }
+
+    #[test]
+    fn stale_recording_gets_suggested_stop_time() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let threshold = Duration::hours(12);
+        book.add_category("test".to_string()).unwrap();
+
+        assert_eq!(
+            book.is_recording_stale(threshold).unwrap_err(),
+            Error::NotRecordingTime
+        );
+
+        book.set_now(Some(day.and_hms(20, 0, 0)));
+        book.start("test".to_string(), Some(day.and_hms(9, 0, 0)))
+            .unwrap();
+        assert_eq!(book.is_recording_stale(threshold).unwrap(), None);
+
+        // Without earlier usages the threshold is used.
+        book.set_now(Some(day.succ().and_hms(9, 0, 0)));
+        assert_eq!(
+            book.is_recording_stale(threshold).unwrap(),
+            Some(day.and_hms(21, 0, 0))
+        );
+
+        book.add_time_usage(
+            "test",
+            day.pred().and_hms(9, 0, 0),
+            day.pred().and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            day.pred().and_hms(12, 0, 0),
+            day.pred().and_hms(15, 0, 0),
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            book.is_recording_stale(threshold).unwrap(),
+            Some(day.and_hms(11, 0, 0))
+        );
+    }
+
+    #[test]
+    fn average_session_is_bucketed_by_weekday() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        // 2022-01-03 is a Monday and 2022-01-07 a Friday.
+        let monday = NaiveDate::from_ymd(2022, 1, 3);
+        let friday = NaiveDate::from_ymd(2022, 1, 7);
+
+        book.add_time_usage(
+            "test",
+            monday.and_hms(9, 0, 0),
+            monday.and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            monday.and_hms(12, 0, 0),
+            monday.and_hms(15, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            friday.and_hms(9, 0, 0),
+            friday.and_hms(9, 30, 0),
+            None,
+        )
+        .unwrap();
+
+        let averages = book.avg_session_by_weekday(ShownTimeSpan::All, None);
+        assert_eq!(averages[0], Some(Duration::hours(2)));
+        assert_eq!(averages[4], Some(Duration::minutes(30)));
+        assert_eq!(averages.iter().filter(|a| a.is_some()).count(), 2);
+
+        let averages = book.avg_session_by_weekday(ShownTimeSpan::All, Some("test"));
+        assert_eq!(averages[4], None);
+        assert_eq!(
+            book.avg_session_by_weekday(ShownTimeSpan::All, Some("none")),
+            [None; 7]
+        );
+    }
+
+    #[test]
+    fn structure_roundtrips_to_new_book() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        let structure = book.export_structure();
+        assert_eq!(structure, r#"["test","test_second"]"#);
+
+        let mut new_book = TimeBook::default();
+        new_book.add_category("test".to_string()).unwrap();
+        assert_eq!(new_book.import_structure(&structure).unwrap(), 1);
+        assert_eq!(new_book.categories(), vec!["test", "test_second"]);
+        assert!(new_book.time_map["test"].is_empty());
+
+        assert!(matches!(
+            new_book.import_structure(r#"{"test": 1}"#).unwrap_err(),
+            Error::InvalidImport(_)
+        ));
+    }
+
+    #[test]
+    fn preview_adds_hypothetical_usage_without_mutating() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.set_now(Some(day.and_hms(20, 0, 0)));
+        book.add_category("test".to_string()).unwrap();
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+
+        let (before, after) = book
+            .preview_with(
+                "test",
+                day.and_hms(12, 0, 0),
+                day.and_hms(12, 45, 0),
+                ShownTimeSpan::Today,
+            )
+            .unwrap();
+        assert_eq!(before, Duration::hours(1));
+        assert_eq!(after, before + Duration::minutes(45));
+        assert_eq!(book.time_map["test"].len(), 1);
+
+        // Outside of the time span.
+        assert_eq!(
+            book.preview_with(
+                "test",
+                day.pred().and_hms(12, 0, 0),
+                day.pred().and_hms(13, 0, 0),
+                ShownTimeSpan::Today
+            )
+            .unwrap(),
+            (Duration::hours(1), Duration::hours(1))
+        );
+        assert_eq!(
+            book.preview_with(
+                "none",
+                day.and_hms(12, 0, 0),
+                day.and_hms(13, 0, 0),
+                ShownTimeSpan::Today
+            )
+            .unwrap_err(),
+            Error::CategoryDoesntExist("none".to_string())
+        );
+    }
+
+    #[test]
+    fn context_switches_count_category_changes() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        assert_eq!(book.context_switches(day), 0);
+
+        // test, test, test_second, test, test_second and one on the next day.
+        for (cat, hour) in [
+            ("test", 8),
+            ("test", 9),
+            ("test_second", 10),
+            ("test", 11),
+            ("test_second", 12),
+        ] {
+            book.add_time_usage(cat, day.and_hms(hour, 0, 0), day.and_hms(hour, 30, 0), None)
+                .unwrap();
+        }
+        book.add_time_usage(
+            "test",
+            day.succ().and_hms(8, 0, 0),
+            day.succ().and_hms(9, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.context_switches(day), 3);
+        assert_eq!(book.context_switches(day.succ()), 0);
+    }
+
+    #[test]
+    fn export_envelope_reimports_and_checks_schema() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.set_now(Some(day.and_hms(20, 0, 0)));
+        book.add_category("test".to_string()).unwrap();
+        book.add_time_usage_with(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            UsageDetails {
+                desc: Some("Tests".to_string()),
+                tags: vec!["tag".to_string()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let export = book.to_export_json(ShownTimeSpan::All);
+        let value: serde_json::Value = serde_json::from_str(&export).unwrap();
+        assert_eq!(value["schema"], EXPORT_SCHEMA);
+        assert_eq!(value["generated_at"], "2022-01-01T20:00:00");
+
+        let mut new_book = TimeBook::default();
+        assert_eq!(new_book.import_export_json(&export).unwrap(), 1);
+        assert!(TimeBook::same_contents(
+            &new_book.time_map["test"][0],
+            &book.time_map["test"][0]
+        ));
+
+        let unknown = export.replace(EXPORT_SCHEMA, "vihr-export/2");
+        assert_eq!(
+            new_book.import_export_json(&unknown).unwrap_err(),
+            Error::UnsupportedSchema("vihr-export/2".to_string())
+        );
+        assert_eq!(new_book.time_map["test"].len(), 1);
+    }
+
+    #[test]
+    fn total_idle_sums_gaps_within_days() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        assert_eq!(book.total_idle(ShownTimeSpan::All), Duration::zero());
+
+        // A two hour gap between 10 and 12.
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(12, 0, 0),
+            day.and_hms(13, 0, 0),
+            None,
+        )
+        .unwrap();
+        // Fully packed with overlapping usages.
+        let next = day.succ();
+        book.add_time_usage("test", next.and_hms(8, 0, 0), next.and_hms(12, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            next.and_hms(11, 0, 0),
+            next.and_hms(14, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.total_idle(ShownTimeSpan::All), Duration::hours(2));
+    }
+
+    #[test]
+    fn log_orders_equal_starts_by_category() {
+        let start = NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0);
+        let stop = NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0);
+
+        // Several books make it unlikely that the hash map order matches by chance.
+        for _ in 0..10 {
+            let mut book = TimeBook::default();
+            for cat in ["c", "a", "d", "b"] {
+                book.add_category(cat.to_string()).unwrap();
+                book.add_time_usage(cat, start, stop, None).unwrap();
+            }
+
+            let log = book.time_usage_log(ShownTimeSpan::All, None).unwrap();
+            let order: Vec<&str> = ["a", "b", "c", "d"]
+                .into_iter()
+                .map(|cat| (log.find(&format!(": {} (ID", cat)).unwrap(), cat))
+                .collect::<BTreeMap<_, _>>()
+                .into_values()
+                .collect();
+            // The latest usage is at the top of the log.
+            assert_eq!(order, vec!["d", "c", "b", "a"]);
+        }
+    }
+
+    #[test]
+    fn totals_are_bucketed_by_day_week_and_month() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        // 2022-01-31 is a Monday, so the 31st and 2022-02-01 share a week but not a month.
+        for (cat, date, hours) in [
+            ("test", NaiveDate::from_ymd(2022, 1, 30), 1),
+            ("test", NaiveDate::from_ymd(2022, 1, 31), 2),
+            ("test_second", NaiveDate::from_ymd(2022, 1, 31), 1),
+            ("test", NaiveDate::from_ymd(2022, 2, 1), 3),
+        ] {
+            book.add_time_usage(
+                cat,
+                date.and_hms(9, 0, 0),
+                date.and_hms(9 + hours, 0, 0),
+                None,
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            book.bucketed_totals(None, ShownTimeSpan::All, BucketSize::Day),
+            vec![
+                (NaiveDate::from_ymd(2022, 1, 30), Duration::hours(1)),
+                (NaiveDate::from_ymd(2022, 1, 31), Duration::hours(3)),
+                (NaiveDate::from_ymd(2022, 2, 1), Duration::hours(3)),
+            ]
+        );
+        assert_eq!(
+            book.bucketed_totals(None, ShownTimeSpan::All, BucketSize::Week),
+            vec![
+                (NaiveDate::from_ymd(2022, 1, 24), Duration::hours(1)),
+                (NaiveDate::from_ymd(2022, 1, 31), Duration::hours(6)),
+            ]
+        );
+        assert_eq!(
+            book.bucketed_totals(Some("test"), ShownTimeSpan::All, BucketSize::Month),
+            vec![
+                (NaiveDate::from_ymd(2022, 1, 1), Duration::hours(3)),
+                (NaiveDate::from_ymd(2022, 2, 1), Duration::hours(3)),
+            ]
+        );
+        assert!(book
+            .bucketed_totals(Some("none"), ShownTimeSpan::All, BucketSize::Month)
+            .is_empty());
+    }
+
+    #[test]
+    fn tentative_flag_survives_serialization() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+
+        assert_eq!(
+            book.set_tentative(true).unwrap_err(),
+            Error::NotRecordingTime
+        );
+
+        book.start("test".to_string(), None).unwrap();
+        book.set_tentative(true).unwrap();
+
+        let json = serde_json::to_string(&book).unwrap();
+        let mut loaded: TimeBook = serde_json::from_str(&json).unwrap();
+        assert!(loaded.is_tentative());
+
+        loaded.cancel().unwrap();
+        assert!(!loaded.is_tentative());
+    }
+
+    #[test]
+    fn largest_gaps_are_found_across_categories() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        assert!(book.largest_gaps(5).is_empty());
+
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(12, 0, 0), None)
+            .unwrap();
+        // Within the first usage, so there is no gap before it.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(13, 0, 0), day.and_hms(14, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(17, 0, 0),
+            day.and_hms(18, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(18, 30, 0), day.and_hms(19, 0, 0), None)
+            .unwrap();
+
+        assert_eq!(
+            book.largest_gaps(1),
+            vec![(
+                day.and_hms(14, 0, 0),
+                day.and_hms(17, 0, 0),
+                Duration::hours(3)
+            )]
+        );
+        assert_eq!(book.largest_gaps(5).len(), 3);
+        assert_eq!(book.largest_gaps(5)[2].2, Duration::minutes(30));
+    }
+
+    #[test]
+    fn month_names_are_localized() {
+        let datetime = NaiveDate::from_ymd(2022, 3, 7).and_hms(9, 0, 0);
+        assert_eq!(format_datetime(datetime, "%-d %B", None), "7 March");
+        assert_eq!(
+            format_datetime(datetime, "%-d %B", Some(Locale::fi_FI)),
+            "7 maaliskuu"
+        );
+
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_time_usage("test", datetime, datetime + Duration::hours(1), None)
+            .unwrap();
+        book.set_locale(Some(Locale::de_DE));
+        let log = book
+            .time_usage_log_formatted(ShownTimeSpan::All, None, "%A")
+            .unwrap();
+        assert!(log.starts_with("Montag - Montag: test"));
+    }
+
+    #[test]
+    fn steady_days_are_more_consistent_than_spiky_ones() {
+        let mut steady = TimeBook::default();
+        let mut spiky = TimeBook::default();
+        steady.add_category("test".to_string()).unwrap();
+        spiky.add_category("test".to_string()).unwrap();
+
+        assert_eq!(steady.consistency_index(ShownTimeSpan::All), 0.0);
+
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        for (i, (steady_hours, spiky_hours)) in [(4, 1), (4, 9), (5, 1), (4, 8)].iter().enumerate()
+        {
+            let date = day + Duration::days(i as i64);
+            steady
+                .add_time_usage(
+                    "test",
+                    date.and_hms(8, 0, 0),
+                    date.and_hms(8 + steady_hours, 0, 0),
+                    None,
+                )
+                .unwrap();
+            spiky
+                .add_time_usage(
+                    "test",
+                    date.and_hms(8, 0, 0),
+                    date.and_hms(8 + spiky_hours, 0, 0),
+                    None,
+                )
+                .unwrap();
+        }
+
+        let steady_index = steady.consistency_index(ShownTimeSpan::All);
+        let spiky_index = spiky.consistency_index(ShownTimeSpan::All);
+        assert!(steady_index > 0.85);
+        assert!(spiky_index < 0.3);
+
+        // Every day has the same total.
+        let mut even = TimeBook::default();
+        even.add_category("test".to_string()).unwrap();
+        even.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        assert_eq!(even.consistency_index(ShownTimeSpan::All), 1.0);
+    }
+
+    #[test]
+    fn chained_start_begins_at_last_stop() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.set_now(Some(day.and_hms(12, 0, 0)));
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        assert_eq!(
+            book.start_chained("test".to_string(), Vec::new())
+                .unwrap_err(),
+            Error::NoTimeUsages
+        );
+
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 30, 0), None)
+            .unwrap();
+        assert_eq!(
+            book.start_chained("test_second".to_string(), Vec::new())
+                .unwrap(),
+            Duration::minutes(90)
+        );
+        assert_eq!(
+            book.status().unwrap(),
+            ("test_second", day.and_hms(10, 30, 0))
+        );
+
+        // Chaining while recording stops the current recording.
+        book.set_now(Some(day.and_hms(13, 0, 0)));
+        assert_eq!(
+            book.start_chained("test".to_string(), Vec::new()).unwrap(),
+            Duration::zero()
+        );
+        assert_eq!(book.time_map["test_second"][0].stop, day.and_hms(13, 0, 0));
+        assert_eq!(book.status().unwrap(), ("test", day.and_hms(13, 0, 0)));
+
+        // A tentative recording isn't committed by chaining.
+        book.set_tentative(true).unwrap();
+        book.set_now(Some(day.and_hms(14, 0, 0)));
+        assert_eq!(
+            book.start_chained("test_second".to_string(), Vec::new())
+                .unwrap_err(),
+            Error::TentativeRecording
+        );
+        assert_eq!(book.time_map["test"].len(), 1);
+        assert_eq!(book.status().unwrap(), ("test", day.and_hms(13, 0, 0)));
+    }
+
+    #[test]
+    fn morning_and_rest_are_split_at_noon() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        // Crosses noon.
+        book.add_time_usage("test", day.and_hms(10, 0, 0), day.and_hms(13, 30, 0), None)
+            .unwrap();
+        // Overnight from the evening to the next morning.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(22, 0, 0),
+            day.succ().and_hms(2, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            book.morning_vs_rest(ShownTimeSpan::All, Some("test")),
+            (Duration::hours(2), Duration::minutes(90))
+        );
+        assert_eq!(
+            book.morning_vs_rest(ShownTimeSpan::All, None),
+            (Duration::hours(4), Duration::minutes(210))
+        );
+        assert_eq!(
+            book.morning_vs_rest(ShownTimeSpan::All, Some("none")),
+            (Duration::zero(), Duration::zero())
+        );
+    }
+
+    #[test]
+    fn invoice_subtotals_sum_to_total() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
 
-        book.remove_category("test").unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 30, 0),
+            Some("Planning".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(11, 0, 0), day.and_hms(12, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test",
+            day.succ().and_hms(9, 0, 0),
+            day.succ().and_hms(9, 45, 0),
+            Some("Review".to_string()),
+        )
+        .unwrap();
+        // Another category isn't invoiced.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(13, 0, 0),
+            day.and_hms(14, 0, 0),
+            None,
+        )
+        .unwrap();
 
-        assert!(book.categories().contains(&&("test1".to_string())));
-        assert_eq!(book.categories().len(), 1);
+        assert_eq!(
+            book.invoice("test", ShownTimeSpan::All).unwrap(),
+            "Invoice: test\n\
+             \n\
+             2022-01-01\n  \
+             09:00 - 10:30  1h 30m  Planning\n  \
+             11:00 - 12:00  1h  [NO DESCRIPTION]\n  \
+             Subtotal: 2h 30m\n\
+             \n\
+             2022-01-02\n  \
+             09:00 - 09:45  45m  Review\n  \
+             Subtotal: 45m\n\
+             \n\
+             Total: 3h 15m\n"
+        );
+        assert_eq!(
+            book.invoice("none", ShownTimeSpan::All).unwrap_err(),
+            Error::CategoryDoesntExist("none".to_string())
+        );
     }
 
     #[test]
-    fn trying_to_remove_nonexistant_category_fails() {
+    fn recording_category_cant_be_removed() {
         let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.start("test".to_string(), None).unwrap();
+
         assert_eq!(
             book.remove_category("test").unwrap_err(),
-            Error::CategoryDoesntExist("test".to_string())
+            Error::CategoryInUse("test".to_string())
+        );
+        assert_eq!(
+            book.remove_category_reassign("test", "test_second")
+                .unwrap_err(),
+            Error::CategoryInUse("test".to_string())
         );
+        book.remove_category("test_second").unwrap();
+        assert!(book.time_map.contains_key("test"));
     }
 
     #[test]
-    fn adding_already_existing_category_fails() {
+    fn orphaned_recording_is_detected_after_load() {
         let mut book = TimeBook::default();
         book.add_category("test".to_string()).unwrap();
+        assert_eq!(book.validate_recording_state(), Ok(()));
+
+        book.start("test".to_string(), None).unwrap();
+        assert_eq!(book.validate_recording_state(), Ok(()));
+
+        // Simulates a save file edited by hand.
+        let json = serde_json::to_string(&book)
+            .unwrap()
+            .replace(r#""time_map":{"test":[]}"#, r#""time_map":{}"#);
+        let mut loaded: TimeBook = serde_json::from_str(&json).unwrap();
         assert_eq!(
-            book.add_category("test".to_string()).unwrap_err(),
-            Error::CategoryExists("test".to_string())
+            loaded.validate_recording_state(),
+            Err(Error::OrphanedRecording("test".to_string()))
         );
+        loaded.set_now(Some(loaded.now() + Duration::days(1)));
+        assert_eq!(
+            loaded.is_recording_stale(Duration::hours(12)),
+            Err(Error::OrphanedRecording("test".to_string()))
+        );
+
+        loaded.cancel().unwrap();
+        assert_eq!(loaded.validate_recording_state(), Ok(()));
     }
 
     #[test]
-    fn time_usage_cannot_be_added_to_category_that_doesnt_exist() {
+    fn variance_report_compares_goals_to_actual_time() {
         let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 5);
+        book.set_now(Some(day.and_hms(20, 0, 0)));
+        for cat in ["over", "under", "unplanned", "unused"] {
+            book.add_category(cat.to_string()).unwrap();
+        }
+        book.set_goal("over", Some(Duration::hours(7))).unwrap();
+        book.set_goal("under", Some(Duration::hours(14))).unwrap();
         assert_eq!(
-            book.add_time_usage(
-                "test",
-                NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
-                NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
-                None
-            )
-            .unwrap_err(),
-            Error::CategoryDoesntExist("test".to_string())
+            book.set_goal("none", Some(Duration::hours(1))).unwrap_err(),
+            Error::CategoryDoesntExist("none".to_string())
+        );
+
+        book.add_time_usage("over", day.and_hms(8, 0, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("under", day.and_hms(12, 0, 0), day.and_hms(13, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "unplanned",
+            day.and_hms(14, 0, 0),
+            day.and_hms(14, 30, 0),
+            None,
+        )
+        .unwrap();
+
+        // A day is a seventh of the weekly goal.
+        assert_eq!(
+            book.variance_report(ShownTimeSpan::Today),
+            vec![
+                (
+                    "over".to_string(),
+                    Duration::hours(1),
+                    Duration::hours(3),
+                    Duration::hours(2)
+                ),
+                (
+                    "under".to_string(),
+                    Duration::hours(2),
+                    Duration::hours(1),
+                    Duration::hours(-1)
+                ),
+                (
+                    "unplanned".to_string(),
+                    Duration::zero(),
+                    Duration::minutes(30),
+                    Duration::minutes(30)
+                ),
+            ]
         );
+
+        book.set_goal("over", None).unwrap();
+        assert_eq!(book.goal("over"), None);
+        assert_eq!(book.goal("under"), Some(Duration::hours(14)));
     }
 
     #[test]
-    fn time_usage_can_be_added_and_removed_from_multiple_categories() {
+    fn capped_time_limits_each_day() {
         let mut book = TimeBook::default();
-
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let cap = Duration::hours(16);
         book.add_category("test".to_string()).unwrap();
-        book.add_category("test_second".to_string()).unwrap();
 
+        // Left running for 20 hours.
+        book.add_time_usage("test", day.and_hms(2, 0, 0), day.and_hms(22, 0, 0), None)
+            .unwrap();
         book.add_time_usage(
             "test",
-            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
-            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            day.succ().and_hms(9, 0, 0),
+            day.succ().and_hms(17, 0, 0),
             None,
         )
         .unwrap();
 
+        assert_eq!(
+            book.time_spent_capped("test", ShownTimeSpan::All, cap)
+                .unwrap(),
+            Duration::hours(24)
+        );
         assert_eq!(
             book.time_spent("test", ShownTimeSpan::All).unwrap(),
-            Duration::hours(1)
+            Duration::hours(28)
         );
         assert_eq!(
-            book.time_spent("test_second", ShownTimeSpan::All).unwrap(),
-            Duration::zero()
+            book.time_spent_capped("none", ShownTimeSpan::All, cap)
+                .unwrap_err(),
+            Error::CategoryDoesntExist("none".to_string())
+        );
+    }
+
+    #[test]
+    fn coalesced_descriptions_follow_merge_strategy() {
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let make_book = || {
+            let mut book = TimeBook::default();
+            book.add_category("test".to_string()).unwrap();
+            for (hour, desc) in [(9, "Tests"), (10, "Docs"), (11, "Tests")] {
+                book.add_time_usage(
+                    "test",
+                    day.and_hms(hour, 0, 0),
+                    day.and_hms(hour, 55, 0),
+                    Some(desc.to_string()),
+                )
+                .unwrap();
+            }
+            // Too far from the rest to be merged.
+            book.add_time_usage("test", day.and_hms(15, 0, 0), day.and_hms(16, 0, 0), None)
+                .unwrap();
+            book
+        };
+
+        let expected = [
+            (DescMerge::Join, "Tests; Docs; Tests"),
+            (DescMerge::First, "Tests"),
+            (DescMerge::Unique, "Tests; Docs"),
+        ];
+        for (strategy, desc) in expected {
+            let mut book = make_book();
+            assert_eq!(
+                book.coalesce("test", Duration::minutes(5), strategy)
+                    .unwrap(),
+                2
+            );
+            let usages = &book.time_map["test"];
+            assert_eq!(usages.len(), 2);
+            assert_eq!(usages[0].start, day.and_hms(9, 0, 0));
+            assert_eq!(usages[0].stop, day.and_hms(11, 55, 0));
+            assert_eq!(usages[0].desc.as_deref(), Some(desc));
+            assert_eq!(usages[1].desc, None);
+        }
+
+        assert_eq!(DescMerge::default(), DescMerge::Unique);
+        assert_eq!(
+            make_book()
+                .coalesce("none", Duration::zero(), DescMerge::default())
+                .unwrap_err(),
+            Error::CategoryDoesntExist("none".to_string())
         );
+    }
+
+    #[test]
+    fn timeline_pairs_starts_and_stops_chronologically() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
 
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+        // Starts when the first one stops.
         book.add_time_usage(
             "test_second",
-            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
-            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            day.and_hms(10, 0, 0),
+            day.and_hms(11, 0, 0),
             None,
         )
         .unwrap();
 
+        let event = |start: bool, category: &str, hour: u32| {
+            let category = category.to_string();
+            let at = day.and_hms(hour, 0, 0);
+            if start {
+                TimelineEvent::Start { category, at }
+            } else {
+                TimelineEvent::Stop { category, at }
+            }
+        };
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::All).unwrap(),
-            Duration::hours(1)
+            book.event_timeline(ShownTimeSpan::All),
+            vec![
+                event(true, "test", 9),
+                event(false, "test", 10),
+                event(true, "test_second", 10),
+                event(false, "test_second", 11),
+            ]
         );
+    }
+
+    #[test]
+    fn close_usages_count_as_one_sitting() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let max_gap = Duration::minutes(10);
+        book.add_category("test".to_string()).unwrap();
+
         assert_eq!(
-            book.time_spent("test_second", ShownTimeSpan::All).unwrap(),
-            Duration::hours(1)
+            book.sitting_count("test", ShownTimeSpan::All, max_gap)
+                .unwrap(),
+            0
         );
 
-        book.remove_time_usage("test", 0).unwrap();
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(10, 5, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+        book.add_time_usage("test", day.and_hms(14, 0, 0), day.and_hms(15, 0, 0), None)
+            .unwrap();
 
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::All).unwrap(),
-            Duration::zero()
+            book.sitting_count("test", ShownTimeSpan::All, max_gap)
+                .unwrap(),
+            2
         );
+        assert_eq!(book.session_count("test", ShownTimeSpan::All).unwrap(), 3);
         assert_eq!(
-            book.time_spent("test_second", ShownTimeSpan::All).unwrap(),
-            Duration::hours(1)
+            book.sitting_count("none", ShownTimeSpan::All, max_gap)
+                .unwrap_err(),
+            Error::CategoryDoesntExist("none".to_string())
         );
     }
 
-    fn time_book_with_usages() -> TimeBook {
-        let now = Local::now().naive_local();
-        let yesterday = Local::now().naive_local() - Duration::days(1);
-        let week_ago = Local::now().naive_local() - Duration::weeks(1);
-        let month_ago = Local::now().naive_local() - Duration::weeks(4);
-        let year_ago = Local::now().naive_local() - Duration::days(365);
-        let two_years_ago = Local::now().naive_local() - Duration::days(700);
-
+    #[test]
+    fn weekday_total_sums_every_matching_day() {
         let mut book = TimeBook::default();
-
         book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        // 2022-01-03 and 2022-01-10 are Mondays.
+        let monday = NaiveDate::from_ymd(2022, 1, 3);
+        let next_monday = NaiveDate::from_ymd(2022, 1, 10);
 
         book.add_time_usage(
             "test",
-            now,
-            now + Duration::minutes(30),
-            Some("Time usage of today".to_string()),
+            monday.and_hms(9, 0, 0),
+            monday.and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
+        // Crosses midnight to Tuesday but is attributed to Monday.
+        book.add_time_usage(
+            "test_second",
+            next_monday.and_hms(23, 0, 0),
+            next_monday.succ().and_hms(1, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            monday.succ().and_hms(9, 0, 0),
+            monday.succ().and_hms(12, 0, 0),
+            None,
         )
         .unwrap();
 
-        book.add_time_usage("test", yesterday, yesterday + Duration::minutes(30), None)
+        assert_eq!(
+            book.total_on_weekday(Weekday::Mon, ShownTimeSpan::All, None),
+            Duration::hours(4)
+        );
+        assert_eq!(
+            book.total_on_weekday(Weekday::Mon, ShownTimeSpan::All, Some("test")),
+            Duration::hours(2)
+        );
+        assert_eq!(
+            book.total_on_weekday(Weekday::Tue, ShownTimeSpan::All, None),
+            Duration::hours(3)
+        );
+    }
+
+    #[test]
+    fn business_hours_totals_clip_usages() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let open = NaiveTime::from_hms(9, 0, 0);
+        let close = NaiveTime::from_hms(17, 0, 0);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_category("unused".to_string()).unwrap();
+
+        // Starts an hour before opening.
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(10, 0, 0), None)
             .unwrap();
+        // From the evening until the next day's noon.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(16, 0, 0),
+            day.succ().and_hms(12, 0, 0),
+            None,
+        )
+        .unwrap();
 
+        let totals = book.business_hours_totals(ShownTimeSpan::All, open, close);
+        assert_eq!(totals["test"], Duration::hours(1));
+        assert_eq!(totals["test_second"], Duration::hours(4));
+        assert_eq!(totals["unused"], Duration::zero());
+
+        // Overnight business hours.
+        let totals = book.business_hours_totals(ShownTimeSpan::All, close, open);
+        assert_eq!(totals["test"], Duration::hours(1));
+        assert_eq!(totals["test_second"], Duration::hours(16));
+    }
+
+    #[test]
+    fn dot_export_has_a_node_per_usage_on_the_date() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(10, 0, 0),
+            day.and_hms(11, 30, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(12, 0, 0), day.and_hms(13, 0, 0), None)
+            .unwrap();
+        // A different day isn't included.
         book.add_time_usage(
             "test",
-            week_ago,
-            week_ago + Duration::minutes(30),
-            Some("Week ago".to_string()),
+            day.succ().and_hms(9, 0, 0),
+            day.succ().and_hms(10, 0, 0),
+            None,
         )
         .unwrap();
 
-        book.add_time_usage("test", month_ago, month_ago + Duration::minutes(30), None)
-            .unwrap();
+        let dot = book.to_dot(day);
+        assert!(dot.starts_with("digraph timeline {"));
+        assert_eq!(dot.matches("[label=").count(), 3);
+        assert!(dot.contains("u0 [label=\"test\\n09:00 - 10:00\", fillcolor=lightblue];"));
+        assert!(dot.contains("u1 [label=\"test_second\\n10:00 - 11:30\", fillcolor=lightpink];"));
+        assert!(dot.contains("u2 [label=\"test\\n12:00 - 13:00\", fillcolor=lightblue];"));
+        assert!(dot.contains("u0 -> u1;"));
+        assert!(dot.contains("u1 -> u2;"));
+        assert!(!dot.contains("u2 -> u3;"));
+    }
+
+    #[test]
+    fn totals_are_grouped_by_description_prefix() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
 
         book.add_time_usage(
             "test",
-            year_ago,
-            year_ago + Duration::minutes(30),
-            Some("A Year ago".to_string()),
+            day.and_hms(8, 0, 0),
+            day.and_hms(9, 0, 0),
+            Some("ACME: Planning".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(9, 0, 0),
+            day.and_hms(11, 0, 0),
+            Some("ACME: Meeting: weekly".to_string()),
         )
         .unwrap();
-
         book.add_time_usage(
             "test",
-            two_years_ago,
-            two_years_ago + Duration::minutes(30),
-            None,
+            day.and_hms(11, 0, 0),
+            day.and_hms(13, 0, 0),
+            Some("Globex: Review".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(13, 0, 0),
+            day.and_hms(13, 30, 0),
+            Some("Lunch".to_string()),
         )
         .unwrap();
 
-        book
+        assert_eq!(
+            book.totals_by_desc_prefix(ShownTimeSpan::All, ':'),
+            vec![
+                ("ACME".to_string(), Duration::hours(3)),
+                ("Globex".to_string(), Duration::hours(2)),
+                ("(none)".to_string(), Duration::minutes(30)),
+            ]
+        );
     }
 
     #[test]
-    fn time_usage_is_reported_correctly_for_the_correct_shown_time_spans() {
-        let book = time_book_with_usages();
+    fn removing_category_can_keep_its_time_as_uncategorized() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(9, 0, 0),
+            day.and_hms(11, 0, 0),
+            None,
+        )
+        .unwrap();
 
+        book.remove_category_keep_time("test").unwrap();
+        assert!(!book.categories().contains(&&"test".to_string()));
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::Today).unwrap(),
-            Duration::minutes(30)
+            book.time_spent(UNCATEGORIZED, ShownTimeSpan::All).unwrap(),
+            Duration::hours(1)
         );
+
+        // An existing uncategorized category is reused.
+        book.remove_category_keep_time("test_second").unwrap();
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::Yesterday).unwrap(),
-            Duration::minutes(30)
+            book.time_spent(UNCATEGORIZED, ShownTimeSpan::All).unwrap(),
+            Duration::hours(3)
         );
+        assert_eq!(book.categories(), vec![UNCATEGORIZED]);
+
+        assert!(matches!(
+            book.remove_category_keep_time("test"),
+            Err(Error::CategoryDoesntExist(_))
+        ));
+        book.remove_category_keep_time(UNCATEGORIZED).unwrap();
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::Week).unwrap(),
-            Duration::minutes(90)
+            book.time_spent(UNCATEGORIZED, ShownTimeSpan::All).unwrap(),
+            Duration::hours(3)
+        );
+    }
+
+    #[test]
+    fn described_ratio_is_weighted_by_duration() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        assert_eq!(book.described_ratio(ShownTimeSpan::All, None), 0.0);
+
+        book.add_time_usage(
+            "test",
+            day.and_hms(8, 0, 0),
+            day.and_hms(10, 0, 0),
+            Some("Planning".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(10, 0, 0), day.and_hms(12, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(12, 0, 0),
+            day.and_hms(13, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(book.described_ratio(ShownTimeSpan::All, Some("test")), 0.5);
+        assert_eq!(book.described_ratio(ShownTimeSpan::All, None), 0.4);
+        assert_eq!(
+            book.described_ratio(ShownTimeSpan::All, Some("missing")),
+            0.0
+        );
+
+        // A blank description isn't a description.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(13, 0, 0),
+            day.and_hms(14, 0, 0),
+            Some("  ".to_string()),
+        )
+        .unwrap();
+        assert_eq!(
+            book.described_ratio(ShownTimeSpan::All, Some("test_second")),
+            0.0
         );
+        assert_eq!(book.described_ratio(ShownTimeSpan::All, None), 1.0 / 3.0);
+    }
+
+    #[test]
+    fn activity_at_includes_the_live_recording() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(8, 0, 0),
+            day.and_hms(9, 0, 0),
+            None,
+        )
+        .unwrap();
+
+        book.set_now(Some(day.and_hms(10, 0, 0)));
+        book.start("test".to_string(), None).unwrap();
+        book.set_now(Some(day.and_hms(12, 0, 0)));
+
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::Month).unwrap(),
-            Duration::minutes(120)
+            book.activity_at(day.and_hms(11, 0, 0)),
+            Some(("test", day.and_hms(10, 0, 0), None))
         );
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::Year).unwrap(),
-            Duration::minutes(150)
+            book.activity_at(day.and_hms(8, 30, 0)),
+            Some((
+                "test_second",
+                day.and_hms(8, 0, 0),
+                Some(day.and_hms(9, 0, 0))
+            ))
         );
+        assert_eq!(book.activity_at(day.and_hms(9, 30, 0)), None);
+        // The recording hasn't reached the future yet.
+        assert_eq!(book.activity_at(day.and_hms(13, 0, 0)), None);
+
+        book.pause().unwrap();
+        book.set_now(Some(day.and_hms(13, 0, 0)));
+        assert_eq!(book.activity_at(day.and_hms(12, 30, 0)), None);
+        book.resume().unwrap();
+        book.set_now(Some(day.and_hms(14, 0, 0)));
+        assert_eq!(book.activity_at(day.and_hms(12, 30, 0)), None);
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::All).unwrap(),
-            Duration::minutes(180)
+            book.activity_at(day.and_hms(13, 30, 0)),
+            Some(("test", day.and_hms(10, 0, 0), None))
         );
     }
 
     #[test]
-    fn time_spent_returns_err_for_nonexistant_category() {
-        let book = TimeBook::default();
+    fn last_7_days_include_inactive_days() {
+        let mut book = TimeBook::default();
+        let today = NaiveDate::from_ymd(2022, 1, 10);
+        book.set_now(Some(today.and_hms(18, 0, 0)));
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+
+        // Too old to be included.
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 3).and_hms(8, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 3).and_hms(9, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2022, 1, 4).and_hms(8, 0, 0),
+            NaiveDate::from_ymd(2022, 1, 4).and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage("test", today.and_hms(8, 0, 0), today.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            today.and_hms(9, 0, 0),
+            today.and_hms(9, 30, 0),
+            None,
+        )
+        .unwrap();
+
+        let days = book.last_7_days();
+        let dates: Vec<NaiveDate> = days.iter().map(|(date, _)| *date).collect();
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::All).unwrap_err(),
-            Error::CategoryDoesntExist("test".to_string())
+            dates,
+            (4..=10)
+                .map(|d| NaiveDate::from_ymd(2022, 1, d))
+                .collect::<Vec<_>>()
         );
+        assert_eq!(days[0].1, Duration::hours(2));
+        assert!(days[1..6]
+            .iter()
+            .all(|(_, spent)| *spent == Duration::zero()));
+        assert_eq!(days[6].1, Duration::minutes(90));
     }
 
     #[test]
-    fn time_usage_log_is_written_correctly_for_the_correct_shown_time_spans() {
-        let book = time_book_with_usages();
-
-        let now = Local::now().naive_local();
-        let yesterday = Local::now().naive_local() - Duration::days(1);
-        let week_ago = Local::now().naive_local() - Duration::weeks(1);
-        let month_ago = Local::now().naive_local() - Duration::weeks(4);
-        let year_ago = Local::now().naive_local() - Duration::days(365);
-        let two_years_ago = Local::now().naive_local() - Duration::days(700);
-
-        let fstring = "%-d/%-m/%Y %H:%M";
-
-        let now_str = format!(
-            "{} - {}: test (ID: 5)\n\tTime usage of today\n\n",
-            now.format(fstring),
-            (now + Duration::minutes(30)).format(fstring)
-        );
+    fn matching_usages_can_be_tagged() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(8, 0, 0),
+            day.and_hms(9, 0, 0),
+            Some("Review for ACME".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            Some("ACME meeting".to_string()),
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            day.and_hms(10, 0, 0),
+            day.and_hms(11, 0, 0),
+            Some("Lunch".to_string()),
+        )
+        .unwrap();
 
-        let yesterday_str = format!(
-            "{} - {}: test (ID: 4)\n\n",
-            yesterday.format(fstring),
-            (yesterday + Duration::minutes(30)).format(fstring)
+        assert_eq!(book.tag_matching("ACME", "client").unwrap(), 2);
+        assert_eq!(book.time_map["test"][0].tags, vec!["client".to_string()]);
+        assert_eq!(
+            book.time_map["test_second"][0].tags,
+            vec!["client".to_string()]
         );
+        assert!(book.time_map["test"][1].tags.is_empty());
 
-        let week_str = format!(
-            "{} - {}: test (ID: 3)\n\tWeek ago\n\n",
-            week_ago.format(fstring),
-            (week_ago + Duration::minutes(30)).format(fstring)
-        );
+        // Running again changes nothing.
+        assert_eq!(book.tag_matching("ACME", "client").unwrap(), 0);
+        assert_eq!(book.time_map["test"][0].tags, vec!["client".to_string()]);
 
-        let month_str = format!(
-            "{} - {}: test (ID: 2)\n\n",
-            month_ago.format(fstring),
-            (month_ago + Duration::minutes(30)).format(fstring)
-        );
+        assert_eq!(book.tag_matching("ACME", "").unwrap_err(), Error::EmptyTag);
+    }
 
-        let year_str = format!(
-            "{} - {}: test (ID: 1)\n\tA Year ago\n\n",
-            year_ago.format(fstring),
-            (year_ago + Duration::minutes(30)).format(fstring)
-        );
+    #[test]
+    fn daily_windows_use_the_latest_stop() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
 
-        let two_years_ago_str = format!(
-            "{} - {}: test (ID: 0)\n\n",
-            two_years_ago.format(fstring),
-            (two_years_ago + Duration::minutes(30)).format(fstring)
-        );
+        book.add_time_usage("test", day.and_hms(8, 12, 0), day.and_hms(17, 45, 0), None)
+            .unwrap();
+        // Starts last but doesn't stop last.
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(12, 0, 0),
+            day.and_hms(13, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage(
+            "test",
+            day.succ().and_hms(9, 0, 0),
+            day.succ().and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
 
         assert_eq!(
-            book.time_usage_log(ShownTimeSpan::Today, None).unwrap(),
-            now_str
-        );
-        assert_eq!(
-            book.time_usage_log(ShownTimeSpan::Yesterday, None).unwrap(),
-            yesterday_str
-        );
-        assert_eq!(
-            book.time_usage_log(ShownTimeSpan::Week, None).unwrap(),
-            now_str.clone() + &yesterday_str + &week_str
+            book.daily_windows(ShownTimeSpan::All),
+            vec![
+                (
+                    day,
+                    NaiveTime::from_hms(8, 12, 0),
+                    NaiveTime::from_hms(17, 45, 0)
+                ),
+                (
+                    day.succ(),
+                    NaiveTime::from_hms(9, 0, 0),
+                    NaiveTime::from_hms(10, 0, 0)
+                ),
+            ]
         );
+    }
+
+    #[test]
+    fn csv_can_be_exported_for_a_single_tag() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
+            None,
+        )
+        .unwrap();
+        book.add_time_usage("test", day.and_hms(10, 0, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+        book.time_map.get_mut("test").unwrap()[0].tags = vec!["client-x".to_string()];
+        book.time_map.get_mut("test_second").unwrap()[0].tags =
+            vec!["client-x".to_string(), "meeting".to_string()];
+        book.time_map.get_mut("test").unwrap()[1].tags = vec!["client-y".to_string()];
+
         assert_eq!(
-            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
-            now_str + &yesterday_str + &week_str + &month_str + &year_str + &two_years_ago_str
+            book.export_tag_csv("client-x", ShownTimeSpan::All),
+            "category,start,stop,duration_minutes,description,tags\n\
+            test,2022-01-02T08:00:00,2022-01-02T09:00:00,60,,client-x\n\
+            test_second,2022-01-02T09:00:00,2022-01-02T10:00:00,60,,client-x;meeting\n"
         );
     }
 
     #[test]
-    fn time_usage_log_is_written_correctly_for_different_category_specifications() {
+    fn current_category_today_includes_the_live_recording() {
         let mut book = TimeBook::default();
-
+        let day = NaiveDate::from_ymd(2022, 1, 2);
         book.add_category("test".to_string()).unwrap();
         book.add_category("test_second".to_string()).unwrap();
+        book.set_now(Some(day.and_hms(12, 0, 0)));
+        assert_eq!(
+            book.current_category_today().unwrap_err(),
+            Error::NotRecordingTime
+        );
 
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(9, 0, 0), None)
+            .unwrap();
         book.add_time_usage(
             "test_second",
-            NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0),
-            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
+            day.and_hms(9, 0, 0),
+            day.and_hms(10, 0, 0),
             None,
         )
         .unwrap();
-
+        // Yesterday isn't counted.
         book.add_time_usage(
             "test",
-            NaiveDate::from_ymd(2022, 1, 1).and_hms(10, 0, 0),
-            NaiveDate::from_ymd(2022, 1, 1).and_hms(11, 0, 0),
+            day.pred().and_hms(8, 0, 0),
+            day.pred().and_hms(9, 0, 0),
             None,
         )
         .unwrap();
 
+        book.start("test".to_string(), None).unwrap();
+        book.set_now(Some(day.and_hms(12, 30, 0)));
         assert_eq!(
-            book.time_usage_log(ShownTimeSpan::All, None).unwrap(),
-            "1/1/2022 10:00 - 1/1/2022 11:00: test (ID: 0)\n\n1/1/2022 09:00 - 1/1/2022 10:00: test_second (ID: 0)\n\n"
-        );
-
-        assert_eq!(
-            book.time_usage_log(ShownTimeSpan::All, Some("test".to_string()))
-                .unwrap(),
-            "1/1/2022 10:00 - 1/1/2022 11:00: test (ID: 0)\n\n"
+            book.current_category_today().unwrap(),
+            Duration::minutes(90)
         );
 
+        book.pause().unwrap();
+        book.set_now(Some(day.and_hms(13, 0, 0)));
         assert_eq!(
-            book.time_usage_log(ShownTimeSpan::All, Some("test_second".to_string()))
-                .unwrap(),
-            "1/1/2022 09:00 - 1/1/2022 10:00: test_second (ID: 0)\n\n"
+            book.current_category_today().unwrap(),
+            Duration::minutes(90)
         );
     }
 
     #[test]
-    fn time_usage_log_returns_err_for_nonexistant_category() {
-        let book = TimeBook::default();
+    fn auto_stop_happens_at_the_first_cutoff_after_start() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        let cutoff = NaiveTime::from_hms(17, 0, 0);
+        book.add_category("test".to_string()).unwrap();
+        book.set_now(Some(day.and_hms(9, 0, 0)));
+        assert_eq!(book.auto_stop_at(cutoff), None);
+
+        book.start("test".to_string(), None).unwrap();
+        assert_eq!(book.auto_stop_at(cutoff), None);
+        book.set_now(Some(day.and_hms(17, 30, 0)));
+        assert_eq!(book.auto_stop_at(cutoff), Some(day.and_hms(17, 0, 0)));
+        book.set_now(Some(day.succ().and_hms(8, 0, 0)));
+        assert_eq!(book.auto_stop_at(cutoff), Some(day.and_hms(17, 0, 0)));
+
+        // Started after the cutoff, so the next day's cutoff applies.
+        book.cancel().unwrap();
+        book.set_now(Some(day.and_hms(18, 0, 0)));
+        book.start("test".to_string(), None).unwrap();
+        book.set_now(Some(day.succ().and_hms(10, 0, 0)));
+        assert_eq!(book.auto_stop_at(cutoff), None);
+        book.set_now(Some(day.succ().and_hms(17, 0, 0)));
         assert_eq!(
-            book.time_usage_log(ShownTimeSpan::All, Some("test".to_string()))
-                .unwrap_err(),
-            Error::CategoryDoesntExist("test".to_string())
+            book.auto_stop_at(cutoff),
+            Some(day.succ().and_hms(17, 0, 0))
         );
     }
 
     #[test]
-    fn cancelling_without_starting_fails() {
+    fn stopping_before_a_later_pause_ignores_the_pause() {
         let mut book = TimeBook::default();
-        assert_eq!(book.cancel().unwrap_err(), Error::NotRecordingTime);
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.set_now(Some(day.and_hms(9, 0, 0)));
+        book.start("test".to_string(), None).unwrap();
+        book.set_now(Some(day.and_hms(18, 0, 0)));
+        book.pause().unwrap();
+
+        book.stop(Some(day.and_hms(17, 0, 0)), None).unwrap();
+        assert_eq!(book.time_map["test"].len(), 1);
+        assert_eq!(book.time_map["test"][0].stop, day.and_hms(17, 0, 0));
     }
 
     #[test]
-    fn cancelling_causes_stop_and_cancel_to_fail_and_doesnt_modify_time_usages() {
+    fn categories_can_be_compared() {
         let mut book = TimeBook::default();
-
+        let day = NaiveDate::from_ymd(2022, 1, 2);
         book.add_category("test".to_string()).unwrap();
-        book.start("test".to_string(), None).unwrap();
-        book.cancel().unwrap();
+        book.add_category("test_second".to_string()).unwrap();
+        book.add_category("unused".to_string()).unwrap();
+
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(11, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test_second",
+            day.and_hms(11, 0, 0),
+            day.and_hms(13, 0, 0),
+            None,
+        )
+        .unwrap();
 
-        assert_eq!(book.cancel().unwrap_err(), Error::NotRecordingTime);
-        assert_eq!(book.stop(None, None).unwrap_err(), Error::NotRecordingTime);
         assert_eq!(
-            book.time_spent("test", ShownTimeSpan::All).unwrap(),
-            Duration::zero()
+            book.compare_categories("test", "test_second", ShownTimeSpan::All)
+                .unwrap(),
+            (Duration::hours(3), Duration::hours(2), 1.5)
+        );
+        assert_eq!(
+            book.compare_categories("test", "unused", ShownTimeSpan::All)
+                .unwrap()
+                .2,
+            f64::INFINITY
+        );
+        assert_eq!(
+            book.compare_categories("unused", "unused", ShownTimeSpan::All)
+                .unwrap()
+                .2,
+            0.0
         );
+        assert!(matches!(
+            book.compare_categories("test", "missing", ShownTimeSpan::All),
+            Err(Error::CategoryDoesntExist(_))
+        ));
     }
 
     #[test]
-    fn cancelling_stopping_and_status_fail_when_nothing_has_been_started() {
+    fn month_calendar_has_a_cell_for_every_day() {
         let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        let day = NaiveDate::from_ymd(2024, 5, 3);
+        book.add_time_usage("test", day.and_hms(8, 0, 0), day.and_hms(10, 30, 0), None)
+            .unwrap();
+        // Another month isn't shown.
+        book.add_time_usage(
+            "test",
+            NaiveDate::from_ymd(2024, 6, 1).and_hms(8, 0, 0),
+            NaiveDate::from_ymd(2024, 6, 1).and_hms(9, 0, 0),
+            None,
+        )
+        .unwrap();
 
-        assert_eq!(book.cancel().unwrap_err(), Error::NotRecordingTime);
-        assert_eq!(book.stop(None, None).unwrap_err(), Error::NotRecordingTime);
-        assert_eq!(book.status().unwrap_err(), Error::NotRecordingTime);
+        let day_cells = |calendar: &str| {
+            calendar
+                .lines()
+                .skip(2)
+                .flat_map(|line| {
+                    line.chars()
+                        .collect::<Vec<_>>()
+                        .chunks(9)
+                        .map(|cell| cell.iter().collect::<String>())
+                        .collect::<Vec<_>>()
+                })
+                .filter(|cell| !cell.trim().is_empty())
+                .collect::<Vec<_>>()
+        };
+
+        let calendar = book.month_calendar(2024, 5);
+        let lines: Vec<&str> = calendar.lines().collect();
+        assert_eq!(lines[0], "May 2024");
+        assert_eq!(lines[1].split_whitespace().next(), Some("Mon"));
+        assert!(lines[1].starts_with(&format!("{}Mon", " ".repeat(5))));
+        assert_eq!(lines.len(), 7);
+        let cells = day_cells(&calendar);
+        assert_eq!(cells.len(), 31);
+        // May 1st 2024 is a Wednesday.
+        assert!(lines[2].starts_with(&" ".repeat(18)));
+        assert_eq!(cells[2].trim(), "3   2.5");
+        assert_eq!(cells[3].trim(), "4");
+
+        book.set_week_start(Weekday::Sun);
+        let calendar = book.month_calendar(2024, 5);
+        assert!(calendar
+            .lines()
+            .nth(1)
+            .unwrap()
+            .trim_start()
+            .starts_with("Sun"));
+        assert_eq!(day_cells(&calendar).len(), 31);
+        assert_eq!(day_cells(&calendar)[0].trim(), "1");
+        assert!(calendar
+            .lines()
+            .nth(2)
+            .unwrap()
+            .starts_with(&" ".repeat(27)));
+
+        book.set_locale(Some(Locale::fi_FI));
+        let calendar = book.month_calendar(2024, 5);
+        assert_eq!(calendar.lines().next(), Some("toukokuu 2024"));
+        assert!(calendar
+            .lines()
+            .nth(1)
+            .unwrap()
+            .trim_start()
+            .starts_with("su"));
     }
 
     #[test]
-    fn starting_twice_fails() {
+    fn milestone_eta_follows_the_recent_pace() {
         let mut book = TimeBook::default();
-
+        let today = NaiveDate::from_ymd(2022, 1, 29);
+        book.set_now(Some(today.and_hms(0, 0, 0)));
         book.add_category("test".to_string()).unwrap();
-        book.start("test".to_string(), None).unwrap();
+        book.add_category("unused".to_string()).unwrap();
 
+        // An hour every day of the past week.
+        for days_ago in 1..=7 {
+            let day = today - Duration::days(days_ago);
+            book.add_time_usage("test", day.and_hms(18, 0, 0), day.and_hms(19, 0, 0), None)
+                .unwrap();
+        }
+
+        // 3 hours left at an hour a day.
         assert_eq!(
-            book.start("test".to_string(), None).unwrap_err(),
-            Error::AlreadyRecordingTime
+            book.milestone_eta("test", Duration::hours(10), ShownTimeSpan::Week)
+                .unwrap(),
+            Some(today + Duration::days(3))
+        );
+        assert_eq!(
+            book.milestone_eta("test", Duration::hours(5), ShownTimeSpan::Week)
+                .unwrap(),
+            Some(today)
         );
+        assert_eq!(
+            book.milestone_eta("unused", Duration::hours(5), ShownTimeSpan::Week)
+                .unwrap(),
+            None
+        );
+        assert!(matches!(
+            book.milestone_eta("missing", Duration::hours(5), ShownTimeSpan::Week),
+            Err(Error::CategoryDoesntExist(_))
+        ));
     }
 
     #[test]
-    fn starting_with_nonexistant_category_fails() {
+    fn stopping_before_the_start_fails_and_keeps_recording() {
         let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.start("test".to_string(), Some(day.and_hms(10, 0, 0)))
+            .unwrap();
+
         assert_eq!(
-            book.start("test".to_string(), None).unwrap_err(),
-            Error::CategoryDoesntExist("test".to_string())
+            book.stop(Some(day.and_hms(9, 0, 0)), None).unwrap_err(),
+            Error::StopBeforeRecordingStart
         );
+        assert!(book.time_map["test"].is_empty());
+        assert_eq!(book.status().unwrap(), ("test", day.and_hms(10, 0, 0)));
+
+        // Stopping right at the start still keeps the usage.
+        book.stop(Some(day.and_hms(10, 0, 0)), None).unwrap();
+        assert_eq!(book.time_map["test"].len(), 1);
     }
 
     #[test]
-    fn starting_updates_status_and_stop_adds_time_usage() {
+    fn failing_import_changes_nothing() {
         let mut book = TimeBook::default();
-
         book.add_category("test".to_string()).unwrap();
+        book.set_require_description(true);
 
-        let now = Local::now().naive_local();
-
-        book.start("test".to_string(), Some(now)).unwrap();
+        let json = r#"[
+            {"category": "new", "start": "2022-01-01T09:00:00", "stop": "2022-01-01T10:00:00", "desc": "Meeting"},
+            {"category": "test", "start": "2022-01-01T10:00:00", "stop": "2022-01-01T11:00:00"}
+        ]"#;
+        assert_eq!(
+            book.import_json_entries(json).unwrap_err(),
+            Error::DescriptionRequired
+        );
+        assert_eq!(book.categories(), vec!["test"]);
+        assert!(book.time_map["test"].is_empty());
+    }
 
-        assert_eq!(book.status().unwrap(), ("test", now));
+    #[test]
+    fn elapsed_doesnt_grow_while_paused() {
+        let mut book = TimeBook::default();
+        let day = NaiveDate::from_ymd(2022, 1, 2);
+        book.add_category("test".to_string()).unwrap();
+        book.set_now(Some(day.and_hms(9, 0, 0)));
+        book.start("test".to_string(), None).unwrap();
 
-        book.stop(Some(now + Duration::hours(2)), None).unwrap();
+        book.set_now(Some(day.and_hms(10, 0, 0)));
+        assert_eq!(book.elapsed().unwrap(), Duration::hours(1));
+        book.pause().unwrap();
+        book.set_now(Some(day.and_hms(10, 45, 0)));
+        assert_eq!(book.elapsed().unwrap(), Duration::hours(1));
 
-        assert!(book.time_spent("test", ShownTimeSpan::All).unwrap() >= Duration::hours(2));
+        book.resume().unwrap();
+        book.set_now(Some(day.and_hms(11, 0, 0)));
+        assert_eq!(book.elapsed().unwrap(), Duration::minutes(75));
+        assert_eq!(
+            book.elapsed_at(day.and_hms(10, 30, 0)).unwrap(),
+            Duration::hours(1)
+        );
     }
 }
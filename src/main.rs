@@ -1,26 +1,37 @@
-use chrono::NaiveDateTime;
+use chrono::{Datelike, Duration, Local, Locale, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
 use vihr::*;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::{env, fs, io, io::Write};
 
 fn main() {
+    let save_dir = env::var("VIHR_SAVE_DIR").ok().map(PathBuf::from);
     let filename = env::var("VIHR_SAVE_FILE");
 
-    if filename.is_err() {
+    if save_dir.is_none() && filename.is_err() {
         eprintln!("Environment variable 'VIHR_SAVE_FILE' is not defined.");
         std::process::exit(1);
     }
 
-    let filename = filename.unwrap();
+    let filename = filename.unwrap_or_default();
     let book;
 
     // Serde needs a string reference. It needs to live as long as `book`.
     let json_str;
 
-    if Path::new(&filename).exists() {
+    if let Some(dir) = &save_dir {
+        if !dir.exists() {
+            println!("Save directory doesn't exist. It will be created.");
+        }
+        book = Ok(read_dir_book(dir).unwrap_or_else(|e| {
+            eprintln!("Could not read save directory '{}'.", dir.display());
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }));
+    } else if Path::new(&filename).exists() {
         let json = fs::read_to_string(&filename);
 
         if let Err(e) = json {
@@ -45,11 +56,139 @@ fn main() {
 
     let mut book: TimeBook = book.unwrap();
 
-    if let Err(e) = handle_commands(&mut book) {
+    // The snapshot is taken before any changes made on startup so that they are shown and saved
+    // along with the changes of the command.
+    let cli = CliArgs::parse();
+    let before = cli.interactive.then(|| book.clone());
+
+    if book.ensure_sorted() {
+        eprintln!("Warning: the save file had time usages out of order. They have been sorted.");
+    }
+
+    if let Err(e) = book.validate_recording_state() {
+        eprintln!("Warning: {}", e);
+    }
+
+    if let Ok(require) = env::var("VIHR_REQUIRE_DESC") {
+        book.set_require_description(matches!(require.as_str(), "1" | "true" | "yes"));
+    }
+
+    if let Ok(offset) = env::var("VIHR_NOW_OFFSET") {
+        match parse_signed_duration(&offset) {
+            Ok(offset)
+                if Local::now()
+                    .naive_local()
+                    .checked_add_signed(offset)
+                    .is_none() =>
+            {
+                eprintln!("Environment variable 'VIHR_NOW_OFFSET' is too large.");
+                std::process::exit(1);
+            }
+            Ok(offset) => book.set_now_offset(offset),
+            Err(e) => {
+                eprintln!("Environment variable 'VIHR_NOW_OFFSET' is not a valid duration.");
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Ok(grace) = env::var("VIHR_RESTART_GRACE") {
+        match parse_duration(&grace) {
+            Ok(grace) => book.set_restart_grace(Some(grace)),
+            Err(e) => {
+                eprintln!("Environment variable 'VIHR_RESTART_GRACE' is not a valid duration.");
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Ok(cutoff) = env::var("VIHR_AUTO_STOP") {
+        match NaiveTime::parse_from_str(&cutoff, "%H:%M") {
+            Ok(cutoff) => {
+                auto_stop(&mut book, cutoff);
+            }
+            Err(e) => {
+                eprintln!(
+                    "Environment variable 'VIHR_AUTO_STOP' is not a valid time such as 17:00."
+                );
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Ok(locale) = env::var("VIHR_LOCALE") {
+        match Locale::try_from(locale.as_str()) {
+            Ok(locale) => book.set_locale(Some(locale)),
+            Err(_) => eprintln!("Warning: unknown locale '{}'. Using English.", locale),
+        }
+    }
+
+    if let Ok(week_start) = env::var("VIHR_WEEK_START") {
+        match parse_weekday(&week_start) {
+            Ok(week_start) => book.set_week_start(week_start),
+            Err(e) => {
+                eprintln!("Environment variable 'VIHR_WEEK_START' is not a valid weekday.");
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Ok(max) = env::var("VIHR_MAX_CATEGORIES") {
+        match max.parse() {
+            Ok(max) => book.set_max_categories(Some(max)),
+            Err(e) => {
+                eprintln!("Environment variable 'VIHR_MAX_CATEGORIES' is not a valid number.");
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let future = book.future_usages();
+    if !future.is_empty() {
+        eprintln!(
+            "Warning: {} time usage(s) start in the future. Is the system clock correct?",
+            future.len()
+        );
+        for (cat, id) in future {
+            eprintln!("\t{} (ID: {})", cat, id);
+        }
+    }
+
+    if let Err(e) = handle_commands(&mut book, cli) {
         eprintln!("{}", e);
         std::process::exit(1);
     }
 
+    if let Some(before) = before {
+        // Without listed changes the book is still saved as startup may have fixed something that
+        // isn't listed, such as the order of time usages.
+        let changes = change_summary(&before, &book);
+        if !changes.is_empty() {
+            println!("Changes:");
+            for change in changes {
+                println!("\t{}", change);
+            }
+            if !confirm("Save the changes") {
+                println!("Discarded the changes.");
+                return;
+            }
+        }
+    }
+
+    if let Some(dir) = &save_dir {
+        if let Err(e) = write_dir_book(&book, dir) {
+            eprintln!("Could not write save directory '{}'.", dir.display());
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let json = serde_json::to_string(&book);
 
     if let Err(e) = json {
@@ -72,6 +211,42 @@ fn main() {
 struct CliArgs {
     #[clap(subcommand)]
     command: Commands,
+    /// Measure the year, month and week time spans precisely from the current moment instead of
+    /// including whole days.
+    #[clap(value_parser, long, global = true)]
+    precise: bool,
+    /// Print a summary of the changes and ask for confirmation before saving them.
+    #[clap(value_parser, long, global = true)]
+    interactive: bool,
+}
+
+/// The formats spent times can be imported from.
+#[derive(Clone, Copy, ValueEnum)]
+enum ImportFormat {
+    /// A JSON array of objects with category, start, stop and optional desc fields.
+    JsonEntries,
+    /// JSON written by exporting with the json format.
+    Json,
+}
+
+/// The formats spent times can be exported to.
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportFormat {
+    /// Comma separated values with a header row.
+    Csv,
+    /// An HTML table of a single day.
+    Html,
+    /// Hours spent on each category in each month of a year as CSV.
+    Pivot,
+    /// CSV in the format Toggl imports.
+    Toggl,
+    /// JSON with a versioned schema meant for other tools.
+    Json,
+    /// A GraphViz DOT timeline of a single day.
+    Dot,
+    /// A SQLite database. Requires --output naming a file that doesn't exist or is empty.
+    #[cfg(feature = "sqlite")]
+    Sqlite,
 }
 
 #[derive(Subcommand)]
@@ -83,8 +258,27 @@ enum Commands {
         category: String,
         /// The starting point of the recording.
         /// If not specified the current moment will be used.
-        #[clap(value_parser, long, short)]
+        #[clap(value_parser = parse_datetime, long, short)]
         start_time: Option<NaiveDateTime>,
+        /// Start the recording this long before the current moment, for example 10m.
+        #[clap(value_parser = parse_duration, long, conflicts_with = "start_time")]
+        ago: Option<Duration>,
+        /// Round the starting point to the nearest multiple of this, for example 5m.
+        #[clap(value_parser = parse_duration, long)]
+        round_start: Option<Duration>,
+        /// A tag given to the spent time. Can be given multiple times.
+        #[clap(value_parser, long)]
+        tag: Vec<String>,
+        /// Ask whether to keep the recording when it's stopped.
+        #[clap(value_parser, long)]
+        tentative: bool,
+        /// Start from the last stop so that no time goes untracked. A current recording is
+        /// stopped first.
+        #[clap(value_parser, long, conflicts_with_all = ["start_time", "ago", "round_start"])]
+        chain: bool,
+        /// Warn when a chained recording covers more untracked time than this, for example 1h.
+        #[clap(value_parser = parse_duration, long, default_value = "1h")]
+        chain_warning: Duration,
     },
     /// Stops recording time.
     Stop {
@@ -92,25 +286,116 @@ enum Commands {
         #[clap(value_parser)]
         desc: Option<String>,
         /// The ending point of the recording.
-        /// If not specified the current moment will be used.
-        #[clap(value_parser, long, short)]
-        stop_time: Option<NaiveDateTime>,
+        /// If not specified the current moment will be used. A bare time before the start of
+        /// the recording refers to the next day.
+        #[clap(value_parser = parse_point_of_time, long, short)]
+        stop_time: Option<PointOfTime>,
+        /// A subtask done during the spent time. Can be given multiple times.
+        #[clap(value_parser, long)]
+        subtask: Vec<String>,
+        /// A tag given to the spent time. Can be given multiple times.
+        #[clap(value_parser, long)]
+        tag: Vec<String>,
+        /// A reference such as a URL related to the spent time.
+        #[clap(value_parser, long = "ref")]
+        reference: Option<String>,
     },
+    /// Starts recording time in the category of the last spent time.
+    Continue,
     /// Shows if time is currently being recorded.
-    Status,
+    Status {
+        /// The unit to which the elapsed time is rounded, for example 1m or 15m.
+        #[clap(value_parser = parse_duration, long, short, default_value = "1m")]
+        round: Duration,
+        /// How the elapsed time is rounded.
+        #[clap(value_enum, long, default_value_t = RoundingMode::Nearest)]
+        rounding_mode: RoundingMode,
+        /// Print the status as JSON. Not recording time isn't treated as an error.
+        #[clap(value_parser, long)]
+        json: bool,
+        /// Also print the last logged cancelled recording.
+        #[clap(value_parser, long)]
+        show_cancels: bool,
+        /// Suggest a break if time has been recorded this long without pausing, for example 1h.
+        #[clap(value_parser = parse_duration, long)]
+        break_after: Option<Duration>,
+    },
+    /// Stops or cancels a recording that has lasted suspiciously long, for example after a crash.
+    Recover {
+        /// How long a recording can last before it's considered stale, for example 12h.
+        #[clap(value_parser = parse_duration, long, short, default_value = "12h")]
+        threshold: Duration,
+    },
+    /// Pauses current time recording. Paused time isn't counted as spent.
+    Pause,
+    /// Resumes paused time recording.
+    Resume,
+    /// Prints the elapsed time of the current recording every second until interrupted.
+    Watch,
     /// Cancels current time recording.
-    Cancel,
+    Cancel {
+        /// Keep a record of the cancelled recording.
+        #[clap(value_parser, long, short)]
+        log: bool,
+        /// An optional reason for cancelling. Implies --log.
+        #[clap(value_parser)]
+        reason: Option<String>,
+        /// Cancel without asking for confirmation.
+        #[clap(value_parser, long, short)]
+        yes: bool,
+    },
+    /// Prints all logged cancelled recordings.
+    Cancelled,
     /// Adds spent time to a category.
     Add {
         /// The category to add the spent time to.
         #[clap(value_parser)]
         category: String,
         /// The starting point of the recording.
-        #[clap(value_parser)]
+        #[clap(value_parser = parse_datetime)]
         start_time: NaiveDateTime,
         /// The ending point of the recording.
+        #[clap(value_parser = parse_datetime)]
+        stop_time: NaiveDateTime,
+        /// An optional description of the spent time.
+        #[clap(value_parser)]
+        desc: Option<String>,
+        /// Round the starting point to the nearest multiple of this, for example 5m.
+        #[clap(value_parser = parse_duration, long)]
+        round_start: Option<Duration>,
+        /// A subtask done during the spent time. Can be given multiple times.
+        #[clap(value_parser, long)]
+        subtask: Vec<String>,
+        /// A tag given to the spent time. Can be given multiple times.
+        #[clap(value_parser, long)]
+        tag: Vec<String>,
+        /// A reference such as a URL related to the spent time.
+        #[clap(value_parser, long = "ref")]
+        reference: Option<String>,
+    },
+    /// Prints the time spent on a category before and after adding spent time without adding it.
+    Preview {
+        /// The category to add the spent time to.
         #[clap(value_parser)]
+        category: String,
+        /// The starting point of the spent time.
+        #[clap(value_parser = parse_datetime)]
+        start_time: NaiveDateTime,
+        /// The ending point of the spent time.
+        #[clap(value_parser = parse_datetime)]
         stop_time: NaiveDateTime,
+        /// The time span from which to count the time spent.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Adds spent time that lasted the given duration and ended now.
+    Quick {
+        /// The category to add the spent time to.
+        #[clap(value_parser)]
+        category: String,
+        /// How long the spent time lasted, for example 45m. At most a day.
+        #[clap(value_parser = parse_duration)]
+        duration: Duration,
         /// An optional description of the spent time.
         #[clap(value_parser)]
         desc: Option<String>,
@@ -124,6 +409,60 @@ enum Commands {
         #[clap(value_parser)]
         id: usize,
     },
+    /// Moves the start and stop of spent time by signed durations such as -20m or +5m.
+    Adjust {
+        /// The category of the spent time.
+        #[clap(value_parser)]
+        category: String,
+        /// The id of the spent time.
+        #[clap(value_parser)]
+        id: usize,
+        /// How much to move the start.
+        #[clap(value_parser = parse_signed_duration, long, allow_hyphen_values = true, default_value = "0m")]
+        start: Duration,
+        /// How much to move the stop.
+        #[clap(value_parser = parse_signed_duration, long, allow_hyphen_values = true, default_value = "0m")]
+        stop: Duration,
+    },
+    /// Removes all spent times starting before a date from all categories.
+    Purge {
+        /// The first day to keep, for example 2022-01-31.
+        #[clap(value_parser)]
+        date: NaiveDate,
+        /// Purge without asking for confirmation.
+        #[clap(value_parser, long, short)]
+        yes: bool,
+    },
+    /// Swaps the categories of two time usages.
+    Swap {
+        /// The category of the first time usage.
+        #[clap(value_parser)]
+        category_a: String,
+        /// The id of the first time usage.
+        #[clap(value_parser)]
+        id_a: usize,
+        /// The category of the second time usage.
+        #[clap(value_parser)]
+        category_b: String,
+        /// The id of the second time usage.
+        #[clap(value_parser)]
+        id_b: usize,
+    },
+    /// Adds spent time to a category filling the gap after an existing time usage.
+    InsertGap {
+        /// The category of the time usage after which the gap is.
+        #[clap(value_parser)]
+        category: String,
+        /// The id of the time usage after which the gap is.
+        #[clap(value_parser)]
+        id: usize,
+        /// The category to add the spent time to.
+        #[clap(value_parser)]
+        into: String,
+        /// An optional description of the spent time.
+        #[clap(value_parser)]
+        desc: Option<String>,
+    },
     /// Prints a summary of time spent.
     Summary {
         /// The time span from which to print the summary.
@@ -132,6 +471,74 @@ enum Commands {
         /// The category to print.
         #[clap(value_parser, long, short)]
         category: Option<String>,
+        /// Categories to leave out of the summary. Also prints the total of the rest.
+        #[clap(value_parser, long, short, value_delimiter = ',')]
+        exclude: Vec<String>,
+        /// Print only categories matching a pattern such as 'client-*' and their total.
+        #[clap(value_parser, long, short, conflicts_with = "category")]
+        matching: Option<String>,
+        /// Print only billable categories and their total.
+        #[clap(value_parser, long)]
+        billable_only: bool,
+        /// Print the number of spent times of each category.
+        #[clap(value_parser, long)]
+        with_count: bool,
+        /// Print a bar for each category scaled to the category with the most time spent.
+        #[clap(value_parser, long)]
+        bars: bool,
+        /// The width of the longest bar in characters.
+        #[clap(value_parser, long, default_value_t = 20)]
+        bar_width: usize,
+        /// Print the summary as JSON.
+        #[clap(value_parser, long)]
+        json: bool,
+        /// Print the summary as a bordered plain text table.
+        #[clap(value_parser, long, conflicts_with_all = ["json", "bars", "with_count"])]
+        table: bool,
+        /// Print the dates the summary covers first using a strftime style format, for example
+        /// '%a %d %b'.
+        #[clap(value_parser, long, short, conflicts_with = "json")]
+        date_format: Option<String>,
+    },
+    /// Pins a category so that it's listed first.
+    Pin {
+        /// The category to pin.
+        #[clap(value_parser)]
+        category: String,
+        /// Unpin the category instead.
+        #[clap(value_parser, long)]
+        off: bool,
+    },
+    /// Sets the description given to spent times without one. {category}, {date}, {start} and
+    /// {stop} are replaced with the values of the spent time.
+    SetTemplate {
+        /// The template. If not specified the template is removed.
+        #[clap(value_parser)]
+        template: Option<String>,
+    },
+    /// Sets the time planned to be spent on a category each week.
+    SetGoal {
+        /// The category.
+        #[clap(value_parser)]
+        category: String,
+        /// The weekly goal, for example 10h. If not specified the goal is removed.
+        #[clap(value_parser = parse_duration)]
+        weekly: Option<Duration>,
+    },
+    /// Prints the planned and actual time spent on each category and their difference.
+    Variance {
+        /// The time span to compare.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Sets whether all time spent on a category is billable.
+    Billable {
+        /// The category.
+        #[clap(value_parser)]
+        category: String,
+        /// Mark the category as not billable instead.
+        #[clap(value_parser, long)]
+        off: bool,
     },
     /// Prints a log of spent times.
     Log {
@@ -141,6 +548,271 @@ enum Commands {
         /// The category to print.
         #[clap(value_parser, long, short)]
         category: Option<String>,
+        /// The strftime style format used for dates, for example '%a %d %b %H:%M'.
+        #[clap(value_parser, long, short, default_value = DEFAULT_DATE_FORMAT)]
+        date_format: String,
+    },
+    /// Prints the most recent spent times across all categories.
+    Recent {
+        /// How many spent times to print.
+        #[clap(value_parser, default_value_t = 5)]
+        count: usize,
+    },
+    /// Prints the spent times across all categories overlapping an interval.
+    Between {
+        /// The start of the interval.
+        #[clap(value_parser = parse_datetime)]
+        from: NaiveDateTime,
+        /// The end of the interval.
+        #[clap(value_parser = parse_datetime)]
+        to: NaiveDateTime,
+    },
+    /// Prints percentiles of the lengths of spent times in a category.
+    Percentiles {
+        /// The category of the spent times.
+        #[clap(value_parser)]
+        category: String,
+        /// The percentiles to print, from 0 to 100.
+        #[clap(value_parser, required = true)]
+        percentiles: Vec<f64>,
+        /// The time span from which to compute the percentiles.
+        #[clap(value_enum, long, short, default_value_t = ShownTimeSpan::All)]
+        shown_span: ShownTimeSpan,
+    },
+    /// Prints the spent times of a category from the longest to the shortest.
+    Rank {
+        /// The category of the spent times.
+        #[clap(value_parser)]
+        category: String,
+        /// The time span from which to print the spent times.
+        #[clap(value_enum, long, short, default_value_t = ShownTimeSpan::All)]
+        shown_span: ShownTimeSpan,
+    },
+    /// Prints the spent times of a category merged into blocks when the gaps are small.
+    Blocks {
+        /// The category of the spent times.
+        #[clap(value_parser)]
+        category: String,
+        /// Spent times with a shorter gap between them are merged, for example 5m.
+        #[clap(value_parser = parse_duration, long, short, default_value = "5m")]
+        max_gap: Duration,
+        /// The time span from which to print the blocks.
+        #[clap(value_enum, long, short, default_value_t = ShownTimeSpan::All)]
+        shown_span: ShownTimeSpan,
+    },
+    /// Prints the average time spent on a category per day with time spent on it.
+    Intensity {
+        /// The category.
+        #[clap(value_parser)]
+        category: String,
+        /// The time span from which to compute the average.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Prints the time spent each week as a sparkline.
+    Trend {
+        /// The category to print. If not specified all categories are counted.
+        #[clap(value_parser)]
+        category: Option<String>,
+        /// The time span from which to print the trend.
+        #[clap(value_enum, long, short, default_value_t = ShownTimeSpan::Year)]
+        shown_span: ShownTimeSpan,
+    },
+    /// Prints the overtime or undertime compared to a daily target.
+    Overtime {
+        /// The time span from which to compute the overtime.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+        /// The target time spent each day, for example 8h.
+        #[clap(value_parser = parse_duration, long, short, default_value = "8h")]
+        target: Duration,
+        /// Count days without spent time as full undertime.
+        #[clap(value_parser, long, short)]
+        inactive: bool,
+    },
+    /// Prints the median time of day at which spent times start.
+    Rhythm {
+        /// The time span from which to compute the time.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+        /// Use only spent times of this category.
+        #[clap(value_parser, long, short)]
+        category: Option<String>,
+    },
+    /// Prints the time from the first start to the last stop of each day and the time spent.
+    Span {
+        /// The time span from which to print the days.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Prints the time spent on each category during a single day.
+    Day {
+        /// The day, for example 2022-01-31. Defaults to today.
+        #[clap(value_parser)]
+        date: Option<NaiveDate>,
+    },
+    /// Prints the earliest start and the latest stop of each day with spent time.
+    Windows {
+        /// The time span from which to print days.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Prints the time spent today on the category time is being recorded to.
+    Current,
+    /// Prints a calendar of a month with the hours spent on each day.
+    Calendar {
+        /// The year of the month, from 1 to 9999. Defaults to the current year.
+        #[clap(value_parser = clap::value_parser!(i32).range(1..=9999))]
+        year: Option<i32>,
+        /// The month, from 1 to 12. Defaults to the current month.
+        #[clap(value_parser = clap::value_parser!(u32).range(1..=12))]
+        month: Option<u32>,
+    },
+    /// Prints the time spent on today and each of the six days before it.
+    WeekSummary {
+        /// Print the days as JSON.
+        #[clap(value_parser, long)]
+        json: bool,
+    },
+    /// Prints how many times the category changed between spent times during a day.
+    Switches {
+        /// The day, for example 2022-01-31. Defaults to today.
+        #[clap(value_parser)]
+        date: Option<NaiveDate>,
+    },
+    /// Prints the time spent before noon and after it.
+    Daypart {
+        /// The time span from which to print the time spent.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+        /// Print the time spent only on this category.
+        #[clap(value_parser, long, short)]
+        category: Option<String>,
+    },
+    /// Prints the spent times of a category grouped by day with daily subtotals for invoicing.
+    Invoice {
+        /// The category to invoice.
+        #[clap(value_parser)]
+        category: String,
+        /// The time span from which to invoice the spent times.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Prints the time spent from Monday to Friday of a week.
+    WorkWeek {
+        /// Print the time spent only on this category.
+        #[clap(value_parser, long, short)]
+        category: Option<String>,
+        /// A day of the week, for example 2022-01-31. Defaults to today.
+        #[clap(value_parser, long)]
+        date: Option<NaiveDate>,
+    },
+    /// Prints the time spent on weekends.
+    Weekend {
+        /// The time span from which to print the time spent.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+        /// Print the time spent only on this category.
+        #[clap(value_parser, long, short)]
+        category: Option<String>,
+    },
+    /// Prints the time still to be spent today to reach a target.
+    Remaining {
+        /// The time to spend today, for example 7h30m.
+        #[clap(value_parser = parse_duration, long, short, default_value = "8h")]
+        target: Duration,
+        /// Don't count the current recording.
+        #[clap(value_parser, long)]
+        no_running: bool,
+    },
+    /// Prints the time spent on a weekday, for example Monday, across all weeks.
+    Weekday {
+        /// The weekday, for example Monday or mon.
+        #[clap(value_parser = parse_weekday)]
+        weekday: Weekday,
+        /// The time span from which to print the time spent.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+        /// Print the time spent only on this category.
+        #[clap(value_parser, long, short)]
+        category: Option<String>,
+    },
+    /// Prints the average length of spent times starting on each weekday.
+    WeekdayAvg {
+        /// The time span from which to average the spent times.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+        /// Average only the spent times of this category.
+        #[clap(value_parser, long, short)]
+        category: Option<String>,
+    },
+    /// Prints the longest gaps between spent times of all categories.
+    Gaps {
+        /// The number of gaps to print.
+        #[clap(value_parser, long, short, default_value_t = 5)]
+        top: usize,
+    },
+    /// Prints how evenly the spent time is spread across the days with spent time.
+    Consistency {
+        /// The time span from which to measure the consistency.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Prints an estimate of when the time spent on a category reaches a target.
+    Milestone {
+        /// The category.
+        #[clap(value_parser)]
+        category: String,
+        /// The total time to reach, for example 100h.
+        #[clap(value_parser = parse_duration)]
+        target: Duration,
+        /// The time span whose pace is used for the estimate.
+        #[clap(value_enum, long, short, default_value_t = ShownTimeSpan::Month)]
+        lookback: ShownTimeSpan,
+    },
+    /// Prints the time spent on two categories and the ratio between them.
+    Vs {
+        /// The first category.
+        #[clap(value_parser)]
+        a: String,
+        /// The second category.
+        #[clap(value_parser)]
+        b: String,
+        /// The time span from which to count spent time.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Prints how much of the spent time has a description.
+    Discipline {
+        /// The time span from which to count spent time.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+        /// Count only this category.
+        #[clap(value_parser, long, short)]
+        category: Option<String>,
+    },
+    /// Prints the category with the longest run of consecutive days with spent time.
+    BestStreak,
+    /// Prints the number of consecutive days up to today on which a daily target was met.
+    GoalStreak {
+        /// The time to spend each day, for example 8h.
+        #[clap(value_parser = parse_duration, long, short)]
+        target: Duration,
+    },
+    /// Prints the day with the most spent time.
+    Busiest {
+        /// The time span from which to find the day.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Prints a diary of spent times grouped by day.
+    Diary {
+        /// The time span from which to print the diary.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+        /// The category to print.
+        #[clap(value_parser, long, short)]
+        category: Option<String>,
     },
     /// Adds a new category.
     AddCategory {
@@ -148,110 +820,865 @@ enum Commands {
         #[clap(value_parser)]
         category: String,
     },
+    /// Adds categories listed in a file, one per line. Existing categories are skipped.
+    AddCategories {
+        /// The file listing the categories.
+        #[clap(value_parser)]
+        file: String,
+    },
     /// Removes a category.
     RemoveCategory {
         /// The category to remove.
         #[clap(value_parser)]
         category: String,
+        /// Move the spent times of the category into this category instead of removing them.
+        #[clap(value_parser, long, short)]
+        into: Option<String>,
+        /// Move the spent times of the category into the uncategorized category instead of
+        /// removing them.
+        #[clap(long, conflicts_with = "into")]
+        keep_time: bool,
     },
     /// Prints all categories.
     ListCategories,
+    /// Prints all tags and how many spent times have them.
+    TagsList,
+    /// Adds a tag to all spent times whose description contains a pattern.
+    Autotag {
+        /// The text the description must contain.
+        #[clap(value_parser)]
+        pattern: String,
+        /// The tag to add.
+        #[clap(value_parser)]
+        tag: String,
+    },
+    /// Renames a tag on all spent times.
+    RenameTag {
+        /// The tag to rename.
+        #[clap(value_parser)]
+        old: String,
+        /// The new name of the tag.
+        #[clap(value_parser)]
+        new: String,
+    },
+    /// Prints categories with no spent time in a time span.
+    Inactive {
+        /// The time span in which the categories have no spent time.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+    },
+    /// Exports spent times to another format.
+    Export {
+        /// The format to export to.
+        #[clap(value_enum, long, short, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+        /// The time span from which to export.
+        #[clap(value_parser)]
+        shown_span: Option<ShownTimeSpan>,
+        /// The file to write the export to. If not specified the export is printed. Required by the
+        /// sqlite format.
+        #[clap(value_parser, long, short, required_if_eq("format", "sqlite"))]
+        output: Option<PathBuf>,
+        /// The day to export in the html and dot formats, for example 2022-01-31. Defaults to today.
+        #[clap(value_parser, long)]
+        date: Option<NaiveDate>,
+        /// Export only spent times whose description contains this text in the csv format.
+        #[clap(value_parser, long)]
+        grep: Option<String>,
+        /// Export only spent times with this tag in the csv format.
+        #[clap(value_parser, long, conflicts_with = "grep")]
+        tag: Option<String>,
+        /// The year to export in the pivot format. Defaults to the current year.
+        #[clap(value_parser, long)]
+        year: Option<i32>,
+    },
+    /// Imports spent times from a file.
+    Import {
+        /// The format of the file.
+        #[clap(value_enum, long, short, default_value_t = ImportFormat::JsonEntries)]
+        format: ImportFormat,
+        /// The file to import.
+        #[clap(value_parser)]
+        file: String,
+    },
+    /// Prints the names of all categories as JSON without any spent times.
+    ExportStructure {
+        /// The file to write the names to. If not specified they are printed.
+        #[clap(value_parser, long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Creates the categories named in a file written by export-structure. Existing categories
+    /// are skipped.
+    ImportStructure {
+        /// The file to import.
+        #[clap(value_parser)]
+        file: String,
+    },
+    /// Merges spent times of a category that are close to each other.
+    Coalesce {
+        /// The category whose spent times to merge.
+        #[clap(value_parser)]
+        category: String,
+        /// The longest gap between spent times that are merged, for example 5m.
+        #[clap(value_parser = parse_duration, long, default_value = "0s")]
+        max_gap: Duration,
+        /// How the descriptions of merged spent times are combined.
+        #[clap(value_enum, long, default_value_t = DescMerge::Unique)]
+        desc_merge: DescMerge,
+    },
+    /// Lists descriptions shared by multiple spent times.
+    Duplicates,
+    /// Prints general information about the spent times.
+    Info,
+    /// Checks the time usages for likely mistakes.
+    Doctor,
+    /// Asks how to resolve each pair of overlapping spent times.
+    FixOverlap,
+    /// Prints how long it has been since time was last recorded.
+    Idle,
+    /// Checks that no data is lost when saving and loading.
+    Verify,
+    /// Prints the differences in spent times between the save file and another save file.
+    Diff {
+        /// The other save file.
+        #[clap(value_parser)]
+        other_file: String,
+    },
 }
 
-fn handle_commands(book: &mut TimeBook) -> Result<()> {
-    let cli = CliArgs::parse();
+fn handle_commands(book: &mut TimeBook, cli: CliArgs) -> Result<()> {
+    book.set_precise_spans(cli.precise);
 
     match cli.command {
         Commands::Start {
             category,
             start_time,
+            ago,
+            round_start,
+            tag,
+            tentative,
+            chain,
+            chain_warning,
         } => {
-            book.start(category, start_time)?;
-        }
-        Commands::Stop { desc, stop_time } => {
-            book.stop(stop_time, desc)?;
-        }
-        Commands::Status => {
-            let (s, d) = book.status()?;
-            println!("Since {}: {}", d, s);
+            if chain {
+                let filled = book.start_chained(category, tag)?;
+                if filled > chain_warning {
+                    println!(
+                        "Warning: the recording covers {} of untracked time.",
+                        format_duration(filled, Duration::minutes(1), RoundingMode::Nearest)
+                    );
+                }
+            } else {
+                let start_time = resolve_start_time(start_time, ago, round_start, book.now());
+                book.start_tagged(category, start_time, tag)?;
+            }
+            if tentative {
+                book.set_tentative(true)?;
+            }
         }
-        Commands::Cancel => {
-            book.cancel()?;
+        Commands::Continue => {
+            let category = book.last_category().ok_or(Error::NoTimeUsages)?.to_string();
+            book.start(category, None)?;
         }
-        Commands::Add {
-            category,
-            start_time,
-            stop_time,
+        Commands::Stop {
             desc,
+            stop_time,
+            subtask,
+            tag,
+            reference,
         } => {
-            book.add_time_usage(&category, start_time, stop_time, desc)?;
+            let stop_time = match stop_time {
+                Some(stop) => Some(resolve_stop_time(stop, book.status()?.1, book.now().date())),
+                None => None,
+            };
+            if book.is_tentative() {
+                let length = book.elapsed_at(stop_time.unwrap_or_else(|| book.now()))?;
+                // Discarding the recording at the end of input would lose it, so it's kept running.
+                match choose(
+                    &format!("Commit this {}-minute session (y/n)", length.num_minutes()),
+                    &['y', 'n'],
+                ) {
+                    Some('y') => {}
+                    Some(_) => {
+                        book.cancel()?;
+                        return Ok(());
+                    }
+                    None => {
+                        println!("Abort!");
+                        return Ok(());
+                    }
+                }
+            }
+            book.stop_with(
+                stop_time,
+                UsageDetails {
+                    desc,
+                    subtasks: subtask,
+                    tags: tag,
+                    reference,
+                },
+            )?;
         }
-        Commands::Remove { category, id } => {
-            book.remove_time_usage(&category, id)?;
+        Commands::Status {
+            round,
+            rounding_mode,
+            json,
+            show_cancels,
+            break_after,
+        } => {
+            if show_cancels && !json {
+                if let Some(cancelled) = book.last_cancelled() {
+                    println!("{}", cancel_notice(cancelled));
+                }
+            }
+            if json {
+                println!("{}", status_json(book));
+                return Ok(());
+            }
+
+            let (s, d) = book.status()?;
+            let elapsed = book.elapsed()?;
+            println!(
+                "Since {}: {} ({}){}",
+                d,
+                s,
+                format_duration(elapsed, round, rounding_mode),
+                if book.is_paused() { " (paused)" } else { "" }
+            );
+
+            if let Some(threshold) = break_after {
+                let since_pause = book.time_since_last_pause()?;
+                if since_pause >= threshold {
+                    println!(
+                        "No pauses in {}. Consider taking a break.",
+                        format_duration(since_pause, round, rounding_mode)
+                    );
+                }
+            }
+        }
+        Commands::Recover { threshold } => {
+            let Some(stop) = book.is_recording_stale(threshold)? else {
+                println!("The current recording isn't stale.");
+                return Ok(());
+            };
+
+            let (category, start) = book.status()?;
+            let fstring = DEFAULT_DATE_FORMAT;
+            match choose(
+                &format!(
+                    "{} has been recorded since {}. [s]top at {}, [c]ancel or [q]uit",
+                    category,
+                    start.format(fstring),
+                    stop.format(fstring)
+                ),
+                &['s', 'c', 'q'],
+            ) {
+                Some('s') => book.stop(Some(stop), None)?,
+                Some('c') => book.cancel()?,
+                _ => println!("Abort!"),
+            }
+        }
+        Commands::Pause => {
+            book.pause()?;
+        }
+        Commands::Resume => {
+            book.resume()?;
+        }
+        Commands::Watch => {
+            let category = book.status()?.0.to_string();
+            let mut stdout = io::stdout();
+            // Nothing is modified, so interrupting with Ctrl-C can't lose any data.
+            loop {
+                print!("\r{}", watch_line(&category, book.elapsed()?));
+                stdout.flush().expect("Failed to flush stdout");
+                std::thread::sleep(std::time::Duration::from_secs(1));
+            }
+        }
+        Commands::Cancel { log, reason, yes } => {
+            let (category, start) = book.status()?;
+            if !yes && !confirm(&cancel_prompt(category, start, book.now())) {
+                println!("Abort!");
+                return Ok(());
+            }
+
+            if log || reason.is_some() {
+                book.cancel_logged(reason)?;
+            } else {
+                book.cancel()?;
+            }
+        }
+        Commands::Cancelled => {
+            let fstring = DEFAULT_DATE_FORMAT;
+            for c in book.cancelled() {
+                println!(
+                    "{} - {}: {} (cancelled)",
+                    c.start.format(fstring),
+                    c.cancelled_at.format(fstring),
+                    c.category
+                );
+                if let Some(r) = &c.reason {
+                    println!("\t{}", r);
+                }
+            }
+        }
+        Commands::Add {
+            category,
+            start_time,
+            stop_time,
+            desc,
+            round_start,
+            subtask,
+            tag,
+            reference,
+        } => {
+            let start_time = match round_start {
+                Some(granularity) => round_datetime(start_time, granularity, RoundingMode::Nearest),
+                None => start_time,
+            };
+            book.add_time_usage_with(
+                &category,
+                start_time,
+                stop_time,
+                UsageDetails {
+                    desc,
+                    subtasks: subtask,
+                    tags: tag,
+                    reference,
+                },
+            )?;
+        }
+        Commands::Preview {
+            category,
+            start_time,
+            stop_time,
+            shown_span,
+        } => {
+            let (before, after) = book.preview_with(
+                &category,
+                start_time,
+                stop_time,
+                shown_span.unwrap_or(ShownTimeSpan::All),
+            )?;
+            print_spent("Before", before);
+            print_spent("After", after);
+        }
+        Commands::Quick {
+            category,
+            duration,
+            desc,
+        } => {
+            book.log_finished(&category, duration, desc)?;
+        }
+        Commands::Remove { category, id } => {
+            book.remove_time_usage(&category, id)?;
+        }
+        Commands::Adjust {
+            category,
+            id,
+            start,
+            stop,
+        } => {
+            book.adjust_time_usage(&category, id, start, stop)?;
+        }
+        Commands::Purge { date, yes } => {
+            if !yes
+                && !confirm(&format!(
+                    "Remove all spent times before {}",
+                    date.format("%-d/%-m/%Y")
+                ))
+            {
+                println!("Abort!");
+                return Ok(());
+            }
+            println!("Removed {} spent time(s).", book.purge_before(date));
+        }
+        Commands::Swap {
+            category_a,
+            id_a,
+            category_b,
+            id_b,
+        } => {
+            book.swap_usage_categories(&category_a, id_a, &category_b, id_b)?;
+        }
+        Commands::InsertGap {
+            category,
+            id,
+            into,
+            desc,
+        } => {
+            book.insert_in_gap(&category, id, &into, desc)?;
         }
         Commands::Summary {
             shown_span,
             category,
+            exclude,
+            matching,
+            billable_only,
+            with_count,
+            bars,
+            bar_width,
+            json,
+            table,
+            date_format,
+        } => {
+            let shown_span = shown_span.unwrap_or(ShownTimeSpan::All);
+            let mut summary = book.summary(shown_span, category.as_deref())?;
+            if let Some(date_format) = date_format {
+                if let Some(heading) = book.span_heading(shown_span, &date_format)? {
+                    println!("{}", heading);
+                }
+            }
+
+            if let Some(pattern) = &matching {
+                let matches = book.categories_matching(pattern);
+                summary.retain(|c| matches.contains(&&c.category));
+            }
+            summary.retain(|c| !exclude.contains(&c.category));
+            if billable_only {
+                summary.retain(|c| book.is_billable(&c.category));
+            }
+            if !exclude.is_empty() && category.is_none() && matching.is_none() && !billable_only {
+                summary.total = book.total_excluding(shown_span, &exclude);
+            }
+
+            if json {
+                // A summary only contains strings and numbers so serializing it can't fail.
+                println!("{}", serde_json::to_string(&summary).unwrap());
+            } else if table {
+                print!("{}", summary.to_text_table());
+            } else {
+                let max = summary.categories.iter().map(|c| c.spent).max();
+                for c in &summary.categories {
+                    let mut name = c.category.clone();
+                    if with_count {
+                        let count = book.session_count(&c.category, shown_span)?;
+                        name = format!("{} ({} session(s))", name, count);
+                    }
+                    if bars {
+                        let filled =
+                            bar_length(c.spent, max.unwrap_or_else(Duration::zero), bar_width);
+                        name = format!(
+                            "{} {}{}",
+                            name,
+                            "█".repeat(filled),
+                            "░".repeat(bar_width - filled)
+                        );
+                    }
+                    print_spent(&name, c.spent);
+                }
+                if matching.is_some() || !exclude.is_empty() || billable_only {
+                    print_spent("Total", summary.total);
+                }
+            }
+        }
+        Commands::Pin { category, off } => {
+            if off {
+                book.unpin_category(&category)?;
+            } else {
+                book.pin_category(&category)?;
+            }
+        }
+        Commands::SetTemplate { template } => {
+            book.set_description_template(template);
+        }
+        Commands::SetGoal { category, weekly } => {
+            book.set_goal(&category, weekly)?;
+        }
+        Commands::Variance { shown_span } => {
+            let fmt = |d| format_duration(d, Duration::minutes(1), RoundingMode::Nearest);
+            for (cat, planned, actual, difference) in
+                book.variance_report(shown_span.unwrap_or(ShownTimeSpan::Week))
+            {
+                let sign = if difference > Duration::zero() {
+                    "+"
+                } else {
+                    ""
+                };
+                println!(
+                    "{}: {} planned, {} actual ({}{})",
+                    cat,
+                    fmt(planned),
+                    fmt(actual),
+                    sign,
+                    fmt(difference)
+                );
+            }
+        }
+        Commands::Billable { category, off } => {
+            book.set_billable(&category, !off)?;
+        }
+        Commands::Log {
+            shown_span,
+            category,
+            date_format,
+        } => {
+            println!(
+                "{}",
+                book.time_usage_log_formatted(
+                    shown_span.unwrap_or(ShownTimeSpan::All),
+                    category,
+                    &date_format
+                )?
+            );
+        }
+        Commands::Recent { count } => {
+            let fstring = DEFAULT_DATE_FORMAT;
+            for (cat, id, usage) in book.recent(count) {
+                println!(
+                    "{} - {}: {} (ID: {})",
+                    usage.start.format(fstring),
+                    usage.stop.format(fstring),
+                    cat,
+                    id
+                );
+            }
+        }
+        Commands::Between { from, to } => {
+            let fstring = DEFAULT_DATE_FORMAT;
+            for (cat, id, usage) in book.usages_overlapping(from, to) {
+                println!(
+                    "{} - {}: {} (ID: {})",
+                    usage.start.format(fstring),
+                    usage.stop.format(fstring),
+                    cat,
+                    id
+                );
+            }
+        }
+        Commands::Rank {
+            category,
+            shown_span,
+        } => {
+            for (id, d) in book.usages_by_duration(&category, shown_span)? {
+                println!(
+                    "ID {}: {}",
+                    id,
+                    format_duration(d, Duration::minutes(1), RoundingMode::Nearest)
+                );
+            }
+        }
+        Commands::Blocks {
+            category,
+            max_gap,
+            shown_span,
+        } => {
+            let fstring = DEFAULT_DATE_FORMAT;
+            for (start, stop) in book.contiguous_blocks(&category, shown_span, max_gap)? {
+                println!(
+                    "{} - {}: {}",
+                    start.format(fstring),
+                    stop.format(fstring),
+                    format_duration(stop - start, Duration::minutes(1), RoundingMode::Nearest)
+                );
+            }
+        }
+        Commands::Intensity {
+            category,
+            shown_span,
+        } => match book.per_active_day(&category, shown_span.unwrap_or(ShownTimeSpan::All))? {
+            Some(average) => print_spent("Per active day", average),
+            None => println!("No time spent."),
+        },
+        Commands::Percentiles {
+            category,
+            percentiles,
+            shown_span,
+        } => {
+            let durations = book.duration_percentiles(&category, shown_span, &percentiles)?;
+            for (p, d) in percentiles.iter().zip(durations) {
+                println!(
+                    "p{}: {}",
+                    p,
+                    format_duration(d, Duration::minutes(1), RoundingMode::Nearest)
+                );
+            }
+        }
+        Commands::Trend {
+            category,
+            shown_span,
+        } => {
+            if let Some(c) = &category {
+                // Checks that the category exists.
+                book.time_spent(c, shown_span)?;
+            }
+
+            let totals = book.weekly_totals(category.as_deref(), shown_span);
+            let values: Vec<Duration> = totals.iter().map(|(_, total)| *total).collect();
+
+            if let (Some((first, _)), Some((last, _))) = (totals.first(), totals.last()) {
+                println!(
+                    "{} - {}",
+                    first.format("%-d/%-m/%Y"),
+                    last.format("%-d/%-m/%Y")
+                );
+                println!("{}", sparkline(&values));
+            } else {
+                println!("No time spent.");
+            }
+        }
+        Commands::Overtime {
+            shown_span,
+            target,
+            inactive,
+        } => {
+            let overtime =
+                book.overtime(shown_span.unwrap_or(ShownTimeSpan::Week), target, inactive);
+            let sign = if overtime > Duration::zero() { "+" } else { "" };
+            println!(
+                "Overtime: {}{}",
+                sign,
+                format_duration(overtime, Duration::minutes(1), RoundingMode::Nearest)
+            );
+        }
+        Commands::Rhythm {
+            shown_span,
+            category,
         } => {
+            match book.median_start_time(
+                shown_span.unwrap_or(ShownTimeSpan::All),
+                category.as_deref(),
+            ) {
+                Some(time) => println!("Median start time: {}", time.format("%H:%M")),
+                None => println!("No time spent."),
+            }
+        }
+        Commands::Span { shown_span } => {
             let shown_span = shown_span.unwrap_or(ShownTimeSpan::All);
+            let totals = book.daily_totals(shown_span, None);
+            let fmt = |d| format_duration(d, Duration::minutes(1), RoundingMode::Nearest);
 
-            if let Some(c) = category {
-                let spent = book.time_spent(&c, shown_span)?;
+            for (date, span) in book.daily_spans(shown_span) {
                 println!(
-                    "{}: {} h {} min(s)",
-                    c,
-                    spent.num_hours(),
-                    spent.num_minutes() - spent.num_hours() * 60
+                    "{}: {} span, {} tracked",
+                    date.format("%-d/%-m/%Y"),
+                    fmt(span),
+                    fmt(totals[&date])
                 );
+            }
+        }
+        Commands::Day { date } => {
+            let date = date.unwrap_or_else(|| book.now().date());
+            for (cat, spent) in book.day_distribution(date) {
+                print_spent(&cat, spent);
+            }
+        }
+        Commands::Windows { shown_span } => {
+            for (date, start, stop) in book.daily_windows(shown_span.unwrap_or(ShownTimeSpan::All))
+            {
+                println!(
+                    "{}: {}\u{2013}{}",
+                    date.format("%a %Y-%m-%d"),
+                    start.format("%H:%M"),
+                    stop.format("%H:%M")
+                );
+            }
+        }
+        Commands::Current => {
+            let (cat, _) = book.status()?;
+            print_spent(cat, book.current_category_today()?);
+        }
+        Commands::Calendar { year, month } => {
+            let today = book.now().date();
+            print!(
+                "{}",
+                book.month_calendar(
+                    year.unwrap_or_else(|| today.year()),
+                    month.unwrap_or_else(|| today.month())
+                )
+            );
+        }
+        Commands::WeekSummary { json } => {
+            if json {
+                println!("{}", week_summary_json(book));
             } else {
-                for cat in book.categories() {
-                    let spent = book.time_spent(cat, shown_span)?;
-                    println!(
-                        "{}: {} h {} min(s)",
-                        cat,
-                        spent.num_hours(),
-                        spent.num_minutes() - spent.num_hours() * 60
-                    );
+                for (date, spent) in book.last_7_days() {
+                    print_spent(&date.format("%a %Y-%m-%d").to_string(), spent);
                 }
             }
         }
-        Commands::Log {
+        Commands::Switches { date } => {
+            let date = date.unwrap_or_else(|| book.now().date());
+            println!("{} switch(es)", book.context_switches(date));
+        }
+        Commands::Daypart {
+            shown_span,
+            category,
+        } => {
+            let (morning, rest) = book.morning_vs_rest(
+                shown_span.unwrap_or(ShownTimeSpan::All),
+                category.as_deref(),
+            );
+            print_spent("Morning", morning);
+            print_spent("Afternoon and evening", rest);
+        }
+        Commands::Invoice {
+            category,
+            shown_span,
+        } => {
+            print!(
+                "{}",
+                book.invoice(&category, shown_span.unwrap_or(ShownTimeSpan::All))?
+            );
+        }
+        Commands::WorkWeek { category, date } => {
+            let week_of = date.unwrap_or_else(|| book.now().date());
+            print_spent(
+                "Work week",
+                book.work_week_total(category.as_deref(), week_of),
+            );
+        }
+        Commands::Weekend {
+            shown_span,
+            category,
+        } => {
+            print_spent(
+                "Weekend",
+                book.weekend_time(
+                    shown_span.unwrap_or(ShownTimeSpan::All),
+                    category.as_deref(),
+                ),
+            );
+        }
+        Commands::Remaining { target, no_running } => {
+            print_spent("Remaining", book.remaining_to_target(target, !no_running));
+        }
+        Commands::Weekday {
+            weekday,
+            shown_span,
+            category,
+        } => {
+            print_spent(
+                &weekday.to_string(),
+                book.total_on_weekday(
+                    weekday,
+                    shown_span.unwrap_or(ShownTimeSpan::All),
+                    category.as_deref(),
+                ),
+            );
+        }
+        Commands::WeekdayAvg {
+            shown_span,
+            category,
+        } => {
+            let averages = book.avg_session_by_weekday(
+                shown_span.unwrap_or(ShownTimeSpan::All),
+                category.as_deref(),
+            );
+            let days = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+            for (day, average) in days.iter().zip(averages) {
+                match average {
+                    Some(average) => print_spent(day, average),
+                    None => println!("{}: -", day),
+                }
+            }
+        }
+        Commands::Gaps { top } => {
+            let fstring = DEFAULT_DATE_FORMAT;
+            for (start, stop, length) in book.largest_gaps(top) {
+                print_spent(
+                    &format!("{} - {}", start.format(fstring), stop.format(fstring)),
+                    length,
+                );
+            }
+        }
+        Commands::Consistency { shown_span } => {
+            let index = book.consistency_index(shown_span.unwrap_or(ShownTimeSpan::All));
+            println!("{:.0} %", index * 100.0);
+        }
+        Commands::Milestone {
+            category,
+            target,
+            lookback,
+        } => {
+            print_spent("Spent", book.time_spent(&category, ShownTimeSpan::All)?);
+            match book.milestone_eta(&category, target, lookback)? {
+                Some(eta) => println!("Estimated to reach the target on {}", eta),
+                None => println!("No recent time spent to estimate from."),
+            }
+        }
+        Commands::Vs { a, b, shown_span } => {
+            let (spent_a, spent_b, ratio) =
+                book.compare_categories(&a, &b, shown_span.unwrap_or(ShownTimeSpan::All))?;
+            print_spent(&a, spent_a);
+            print_spent(&b, spent_b);
+            if ratio.is_infinite() {
+                println!("Ratio: infinite");
+            } else {
+                println!("Ratio: {:.2}", ratio);
+            }
+        }
+        Commands::Discipline {
+            shown_span,
+            category,
+        } => {
+            let ratio = book.described_ratio(
+                shown_span.unwrap_or(ShownTimeSpan::All),
+                category.as_deref(),
+            );
+            println!("{:.0} %", ratio * 100.0);
+        }
+        Commands::BestStreak => match book.best_streak_category() {
+            Some((cat, days)) => println!("{}: {} day(s)", cat, days),
+            None => println!("No time spent."),
+        },
+        Commands::GoalStreak { target } => {
+            println!("{} day(s)", book.goal_streak(target));
+        }
+        Commands::Busiest { shown_span } => {
+            match book.busiest_day(shown_span.unwrap_or(ShownTimeSpan::All)) {
+                Some((date, total)) => print_spent(&date.format("%-d/%-m/%Y").to_string(), total),
+                None => println!("No time spent."),
+            }
+        }
+        Commands::Diary {
             shown_span,
             category,
         } => {
             println!(
                 "{}",
-                book.time_usage_log(shown_span.unwrap_or(ShownTimeSpan::All), category)?
+                book.diary(shown_span.unwrap_or(ShownTimeSpan::All), category)?
             );
         }
         Commands::AddCategory { category } => {
             book.add_category(category)?;
         }
-        Commands::RemoveCategory { category } => {
-            let stdin = io::stdin();
-            let mut stdout = io::stdout();
-            let mut confirmation_buff = String::new();
+        Commands::AddCategories { file } => {
+            let contents = read_file(&file);
+            let names: Vec<String> = contents
+                .lines()
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty())
+                .collect();
 
-            loop {
-                print!("Remove category {} (y/n)? ", category);
-                stdout.flush().expect("Failed to flush stdout");
-                confirmation_buff.clear();
-                stdin
-                    .read_line(&mut confirmation_buff)
-                    .expect("Failed to read line");
-                confirmation_buff = confirmation_buff.to_lowercase().trim().to_string();
-
-                if &confirmation_buff != "y" && &confirmation_buff != "n" {
-                    eprintln!("Invalid option.");
-                    continue;
+            let mut added = 0;
+            for result in book.add_categories(&names) {
+                match result {
+                    Ok(()) => added += 1,
+                    Err(e) => println!("Skipped: {}", e),
                 }
-                break;
             }
+            println!("Added {} categories.", added);
+        }
+        Commands::RemoveCategory {
+            category,
+            into,
+            keep_time,
+        } => {
+            let prompt = match &into {
+                Some(into) => format!("Remove category {} moving its time to {}", category, into),
+                None if keep_time => format!(
+                    "Remove category {} moving its time to {}",
+                    category, UNCATEGORIZED
+                ),
+                None => format!("Remove category {}", category),
+            };
 
-            let confirmed = &confirmation_buff == "y";
-
-            if confirmed {
-                book.remove_category(&category)?;
+            if confirm(&prompt) {
+                match into {
+                    Some(into) => book.remove_category_reassign(&category, &into)?,
+                    None if keep_time => book.remove_category_keep_time(&category)?,
+                    None => book.remove_category(&category)?,
+                }
             } else {
                 println!("Abort!");
             }
@@ -261,7 +1688,955 @@ fn handle_commands(book: &mut TimeBook) -> Result<()> {
                 println!("{}", cat);
             }
         }
+        Commands::TagsList => {
+            for (tag, count) in book.all_tags() {
+                println!("{}: {}", tag, count);
+            }
+        }
+        Commands::Autotag { pattern, tag } => {
+            let changed = book.tag_matching(&pattern, &tag)?;
+            println!("Tagged {} spent time(s).", changed);
+        }
+        Commands::RenameTag { old, new } => {
+            let changed = book.rename_tag(&old, &new);
+            println!("Renamed the tag on {} spent time(s).", changed);
+        }
+        Commands::Inactive { shown_span } => {
+            for cat in book.inactive_categories(shown_span.unwrap_or(ShownTimeSpan::Month)) {
+                println!("{}", cat);
+            }
+        }
+        Commands::Idle => {
+            if let Some(idle) = book.idle_since() {
+                println!(
+                    "Idle for {}",
+                    format_duration(idle, Duration::minutes(1), RoundingMode::Down)
+                );
+            } else if book.status().is_ok() {
+                println!("Time is being recorded currently.");
+            } else {
+                println!("No time has been recorded yet.");
+            }
+        }
+        Commands::Verify => {
+            if !book.roundtrip_ok() {
+                return Err(Error::RoundTripMismatch);
+            }
+            println!("No data is lost when saving.");
+        }
+        Commands::Diff { other_file } => {
+            let other = read_book(&other_file);
+            let diff = book.diff(&other);
+            let fstring = DEFAULT_DATE_FORMAT;
+
+            for (prefix, usages) in [("+", diff.added), ("-", diff.removed), ("~", diff.modified)] {
+                for (cat, start) in usages {
+                    println!("{} {}: {}", prefix, start.format(fstring), cat);
+                }
+            }
+        }
+        Commands::Export {
+            format,
+            shown_span,
+            output,
+            date,
+            grep,
+            tag,
+            year,
+        } => {
+            let shown_span = shown_span.unwrap_or(ShownTimeSpan::All);
+            let exported = match format {
+                ExportFormat::Csv => match tag {
+                    Some(tag) => book.export_tag_csv(&tag, shown_span),
+                    None => book.to_csv_filtered(shown_span, grep.as_deref()),
+                },
+                ExportFormat::Html => book.day_to_html(date.unwrap_or_else(|| book.now().date())),
+                ExportFormat::Pivot => {
+                    book.to_pivot_csv(year.unwrap_or_else(|| book.now().date().year()))
+                }
+                ExportFormat::Toggl => book.to_toggl_csv(shown_span),
+                ExportFormat::Json => book.to_export_json(shown_span),
+                ExportFormat::Dot => book.to_dot(date.unwrap_or_else(|| book.now().date())),
+                #[cfg(feature = "sqlite")]
+                ExportFormat::Sqlite => {
+                    // Required by clap for sqlite so unwrap is ok.
+                    return book.to_sqlite(&output.unwrap());
+                }
+            };
+
+            write_output(output.as_deref(), exported.as_bytes())
+                .map_err(|e| Error::ExportFailed(e.to_string()))?;
+        }
+        Commands::Import { format, file } => {
+            let contents = read_file(&file);
+            let count = match format {
+                ImportFormat::JsonEntries => book.import_json_entries(&contents)?,
+                ImportFormat::Json => book.import_export_json(&contents)?,
+            };
+            println!("Imported {} spent time(s).", count);
+        }
+        Commands::ExportStructure { output } => {
+            write_output(output.as_deref(), book.export_structure().as_bytes())
+                .map_err(|e| Error::ExportFailed(e.to_string()))?;
+        }
+        Commands::ImportStructure { file } => {
+            let count = book.import_structure(&read_file(&file))?;
+            println!("Created {} category(s).", count);
+        }
+        Commands::FixOverlap => {
+            // Resolving an overlap can change the ids, so the overlaps are searched again after
+            // each one.
+            loop {
+                let overlaps = book.overlapping_usages();
+                let Some((cat, id_a, id_b)) = overlaps.first() else {
+                    println!("No overlaps found.");
+                    break;
+                };
+
+                let strategy = match choose(
+                    &format!(
+                        "{} (ID: {}) and (ID: {}) overlap. Trim [f]irst, trim [s]econd, [m]erge or [q]uit",
+                        cat, id_a, id_b
+                    ),
+                    &['f', 's', 'm', 'q'],
+                ) {
+                    Some('f') => OverlapStrategy::TrimFirst,
+                    Some('s') => OverlapStrategy::TrimSecond,
+                    Some('m') => OverlapStrategy::Merge,
+                    _ => break,
+                };
+                book.resolve_overlap(cat, *id_a, *id_b, strategy)?;
+            }
+        }
+        Commands::Coalesce {
+            category,
+            max_gap,
+            desc_merge,
+        } => {
+            let merged = book.coalesce(&category, max_gap, desc_merge)?;
+            println!("Merged {} spent time(s).", merged);
+        }
+        Commands::Duplicates => {
+            for (desc, members) in book.description_groups() {
+                println!("{}", desc);
+                for (cat, id) in members {
+                    println!("\t{} (ID: {})", cat, id);
+                }
+            }
+        }
+        Commands::Info => match book.tracking_since() {
+            Some(date) => println!(
+                "Tracking since {}",
+                format_datetime(date.and_hms(0, 0, 0), "%-d %b %Y", book.locale())
+            ),
+            None => println!("No time has been recorded yet."),
+        },
+        Commands::Doctor => {
+            let mut problems = 0;
+
+            for (cat, id) in book.future_usages() {
+                println!("{} (ID: {}) starts in the future.", cat, id);
+                problems += 1;
+            }
+            for (cat, id) in book.zero_length_usages() {
+                println!("{} (ID: {}) has no length.", cat, id);
+                problems += 1;
+            }
+            for (cat, id_a, id_b) in book.overlapping_usages() {
+                println!("{} (ID: {}) and (ID: {}) overlap.", cat, id_a, id_b);
+                problems += 1;
+            }
+
+            if problems == 0 {
+                println!("No problems found.");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print_spent(name: &str, spent: Duration) {
+    println!(
+        "{}: {} h {} min(s)",
+        name,
+        spent.num_hours(),
+        spent.num_minutes() - spent.num_hours() * 60
+    );
+}
+
+/// Describes the differences between a `TimeBook` before and after a command. Returns nothing if
+/// the books are the same.
+fn change_summary(before: &TimeBook, after: &TimeBook) -> Vec<String> {
+    // Serializing a `TimeBook` can't fail.
+    let before_json = serde_json::to_value(before).unwrap();
+    let after_json = serde_json::to_value(after).unwrap();
+    if before_json == after_json {
+        return Vec::new();
+    }
+
+    let fmt = |d| format_duration(d, Duration::minutes(1), RoundingMode::Nearest);
+    let mut changes = Vec::new();
+
+    let before_cats = before.categories();
+    for cat in &before_cats {
+        if !after.categories().contains(cat) {
+            changes.push(format!("removed category {}", cat));
+        }
+    }
+    for cat in after.categories() {
+        if !before_cats.contains(&cat) {
+            changes.push(format!("added category {}", cat));
+            continue;
+        }
+
+        // Both books have the category so unwrap is ok.
+        let spent_before = before.time_spent(cat, ShownTimeSpan::All).unwrap();
+        let spent_after = after.time_spent(cat, ShownTimeSpan::All).unwrap();
+        let today = fmt(after.time_spent(cat, ShownTimeSpan::Today).unwrap());
+        if spent_after > spent_before {
+            changes.push(format!(
+                "added {} to {}; total today now {}",
+                fmt(spent_after - spent_before),
+                cat,
+                today
+            ));
+        } else if spent_after < spent_before {
+            changes.push(format!(
+                "removed {} from {}; total today now {}",
+                fmt(spent_before - spent_after),
+                cat,
+                today
+            ));
+        } else if before_json["time_map"][cat] != after_json["time_map"][cat] {
+            changes.push(format!("edited spent times of {}", cat));
+        }
+    }
+
+    match (before.status().ok(), after.status().ok()) {
+        (None, Some((cat, _))) => changes.push(format!("started recording {}", cat)),
+        (Some((cat, _)), None) => changes.push(format!("stopped recording {}", cat)),
+        (Some(a), Some(b)) if a != b => changes.push(format!("now recording {}", b.0)),
+        _ => {}
+    }
+
+    if changes.is_empty() {
+        changes.push("changed settings".to_string());
+    }
+    changes
+}
+
+/// Stops a recording that has run past the daily cutoff at the cutoff and warns about it.
+/// Tentative recordings are only warned about as committing them needs a confirmation.
+fn auto_stop(book: &mut TimeBook, cutoff: NaiveTime) {
+    let Some(stop) = book.auto_stop_at(cutoff) else {
+        return;
+    };
+    // Checked by auto_stop_at so unwrap is ok.
+    let category = book.status().unwrap().0.to_string();
+
+    if book.is_tentative() {
+        eprintln!(
+            "Warning: the tentative recording of {} has run past {}. Stop or cancel it.",
+            category,
+            stop.format(DEFAULT_DATE_FORMAT)
+        );
+        return;
+    }
+
+    let result = match book.stop(Some(stop), None) {
+        // A description can't be asked for here, so a generic one is used rather than leaving the
+        // recording running.
+        Err(Error::DescriptionRequired) => {
+            book.stop(Some(stop), Some("Stopped automatically".to_string()))
+        }
+        result => result,
+    };
+    match result {
+        Ok(()) => eprintln!(
+            "Warning: the recording of {} was stopped automatically at {}.",
+            category,
+            stop.format(DEFAULT_DATE_FORMAT)
+        ),
+        Err(e) => eprintln!(
+            "Warning: the recording of {} couldn't be stopped automatically: {}",
+            category, e
+        ),
+    }
+}
+
+/// Asks the user a yes or no question until a valid answer is given. The end of input is
+/// treated as no.
+fn confirm(prompt: &str) -> bool {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut confirmation_buff = String::new();
+
+    loop {
+        print!("{} (y/n)? ", prompt);
+        stdout.flush().expect("Failed to flush stdout");
+        confirmation_buff.clear();
+        if stdin
+            .read_line(&mut confirmation_buff)
+            .expect("Failed to read line")
+            == 0
+        {
+            println!();
+            return false;
+        }
+        confirmation_buff = confirmation_buff.to_lowercase().trim().to_string();
+
+        if &confirmation_buff != "y" && &confirmation_buff != "n" {
+            eprintln!("Invalid option.");
+            continue;
+        }
+        break;
+    }
+
+    &confirmation_buff == "y"
+}
+
+/// Asks the user to choose one of the options until a valid answer is given. Returns `None` at
+/// the end of input.
+fn choose(prompt: &str, options: &[char]) -> Option<char> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut buff = String::new();
+
+    loop {
+        print!("{}? ", prompt);
+        stdout.flush().expect("Failed to flush stdout");
+        buff.clear();
+        if stdin.read_line(&mut buff).expect("Failed to read line") == 0 {
+            println!();
+            return None;
+        }
+        let answer = buff.trim().to_lowercase();
+
+        let mut chars = answer.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if options.contains(&c) => return Some(c),
+            _ => eprintln!("Invalid option."),
+        }
+    }
+}
+
+/// Reads a file to a string. Exits the process if the file can't be read.
+fn read_file(filename: &str) -> String {
+    fs::read_to_string(filename).unwrap_or_else(|e| {
+        eprintln!("Could not read file '{}'.", filename);
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+/// Reads a `TimeBook` from a file. Exits the process if the file can't be read or parsed.
+fn read_book(filename: &str) -> TimeBook {
+    let json = read_file(filename);
+
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        eprintln!("Could not parse json from file '{}'.", filename);
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+/// The file in a save directory that contains everything except the time usages.
+const STATE_FILE: &str = "state.json";
+
+/// Splits a `TimeBook` into the files of a save directory. Every category is saved to a file of
+/// its own and everything else to the state file. Returns the file names and their contents.
+fn split_book(book: &TimeBook) -> serde_json::Result<BTreeMap<String, String>> {
+    let mut state = serde_json::to_value(book)?;
+    let time_map = state["time_map"].take();
+    state["time_map"] = serde_json::json!({});
+
+    let mut files = BTreeMap::new();
+    files.insert(STATE_FILE.to_string(), serde_json::to_string(&state)?);
+
+    if let serde_json::Value::Object(categories) = time_map {
+        for (category, usages) in categories {
+            // The category name is saved in the file, so the file name only needs to be unique.
+            let stem: String = category
+                .chars()
+                .map(|c| {
+                    if c.is_alphanumeric() || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let mut name = format!("{}.json", stem);
+            let mut n = 1;
+            while name == STATE_FILE || files.contains_key(&name) {
+                n += 1;
+                name = format!("{}_{}.json", stem, n);
+            }
+
+            let contents = serde_json::json!({ "category": category, "usages": usages });
+            files.insert(name, serde_json::to_string(&contents)?);
+        }
+    }
+
+    Ok(files)
+}
+
+/// Merges the files of a save directory back into a single `TimeBook`. A missing state file is
+/// treated as an empty `TimeBook`.
+fn merge_book(files: &BTreeMap<String, String>) -> serde_json::Result<TimeBook> {
+    let mut state = match files.get(STATE_FILE) {
+        Some(json) => serde_json::from_str(json)?,
+        None => serde_json::to_value(TimeBook::default())?,
+    };
+
+    let mut time_map = serde_json::Map::new();
+    for (name, json) in files {
+        if name == STATE_FILE {
+            continue;
+        }
+
+        let mut contents: serde_json::Value = serde_json::from_str(json)?;
+        let category = match contents["category"].take() {
+            serde_json::Value::String(category) => category,
+            _ => {
+                return Err(serde::de::Error::custom(format!(
+                    "'{}' has no category name",
+                    name
+                )))
+            }
+        };
+        time_map.insert(category, contents["usages"].take());
+    }
+    state["time_map"] = serde_json::Value::Object(time_map);
+
+    serde_json::from_value(state)
+}
+
+/// Reads a `TimeBook` from every JSON file in a save directory. A directory that doesn't exist is
+/// treated as an empty `TimeBook`.
+fn read_dir_book(dir: &Path) -> io::Result<TimeBook> {
+    let mut files = BTreeMap::new();
+    if dir.exists() {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                files.insert(name, fs::read_to_string(&path)?);
+            }
+        }
+    }
+
+    Ok(merge_book(&files)?)
+}
+
+/// Writes a `TimeBook` to a save directory creating it if needed. Files of categories that no
+/// longer exist are removed once every other file has been written. Other files are left alone.
+fn write_dir_book(book: &TimeBook, dir: &Path) -> io::Result<()> {
+    let files = split_book(book)?;
+    fs::create_dir_all(dir)?;
+
+    for (name, contents) in &files {
+        write_output(Some(&dir.join(name)), contents.as_bytes())?;
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        if path.extension().is_some_and(|e| e == "json")
+            && !files.contains_key(&name)
+            && is_category_file(&path)
+        {
+            fs::remove_file(path)?;
+        }
     }
 
     Ok(())
 }
+
+/// Returns true if a file is a category file written by `write_dir_book`.
+fn is_category_file(path: &Path) -> bool {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok())
+        .is_some_and(|contents| contents["category"].is_string())
+}
+
+/// Parses a weekday such as `Monday` or `mon` given on the command line.
+fn parse_weekday(s: &str) -> std::result::Result<Weekday, String> {
+    s.parse()
+        .map_err(|_| format!("'{}' isn't a weekday such as Monday or mon", s))
+}
+
+/// Parses a point of time given on the command line. Accepts full date times such as
+/// `2022-10-02T10:50:00` as well as bare times such as `10:50` or `10:50:30` that refer to today.
+fn parse_datetime(s: &str) -> std::result::Result<NaiveDateTime, String> {
+    // The offset has been validated on startup.
+    let offset = env::var("VIHR_NOW_OFFSET")
+        .ok()
+        .and_then(|offset| parse_signed_duration(&offset).ok())
+        .unwrap_or_else(Duration::zero);
+    parse_datetime_on(s, (Local::now().naive_local() + offset).date())
+}
+
+/// Parses a duration like `parse_duration` but allows a leading `-` or `+` sign.
+fn parse_signed_duration(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    match s.strip_prefix('-') {
+        Some(rest) => Ok(-parse_duration(rest)?),
+        None => parse_duration(s.strip_prefix('+').unwrap_or(s)),
+    }
+}
+
+/// Parses a point of time like `parse_datetime` but bare times refer to the specified day.
+fn parse_datetime_on(s: &str, today: NaiveDate) -> std::result::Result<NaiveDateTime, String> {
+    Ok(match parse_point_of_time(s)? {
+        PointOfTime::TimeOfDay(time) => today.and_time(time),
+        PointOfTime::DateTime(datetime) => datetime,
+    })
+}
+
+/// A point of time given on the command line. The day of a bare time is decided later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PointOfTime {
+    TimeOfDay(NaiveTime),
+    DateTime(NaiveDateTime),
+}
+
+/// Parses a bare time such as `10:50` or a full point of time such as `2022-10-02T10:50:00`.
+fn parse_point_of_time(s: &str) -> std::result::Result<PointOfTime, String> {
+    let s = s.trim();
+
+    for fstring in ["%H:%M", "%H:%M:%S"] {
+        if let Ok(time) = NaiveTime::parse_from_str(s, fstring) {
+            return Ok(PointOfTime::TimeOfDay(time));
+        }
+    }
+
+    for fstring in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(s, fstring) {
+            return Ok(PointOfTime::DateTime(datetime));
+        }
+    }
+
+    Err(format!(
+        "Invalid time '{}'. Use yyyy-mm-ddThh:mm:ss, hh:mm or hh:mm:ss.",
+        s
+    ))
+}
+
+/// Resolves the start time of a recording from the options of the start command. Without a
+/// start time, an amount of time ago or rounding the recording starts at the current moment.
+fn resolve_start_time(
+    start_time: Option<NaiveDateTime>,
+    ago: Option<Duration>,
+    round_start: Option<Duration>,
+    now: NaiveDateTime,
+) -> Option<NaiveDateTime> {
+    let start_time = ago.map(|ago| now - ago).or(start_time);
+    match round_start {
+        Some(granularity) => Some(round_datetime(
+            start_time.unwrap_or(now),
+            granularity,
+            RoundingMode::Nearest,
+        )),
+        None => start_time,
+    }
+}
+
+/// Resolves the stop time of a recording. A bare time refers to today unless that is before the
+/// start of the recording, in which case it refers to the next day.
+fn resolve_stop_time(stop: PointOfTime, start: NaiveDateTime, today: NaiveDate) -> NaiveDateTime {
+    match stop {
+        PointOfTime::TimeOfDay(time) if today.and_time(time) < start => {
+            (today + Duration::days(1)).and_time(time)
+        }
+        PointOfTime::TimeOfDay(time) => today.and_time(time),
+        PointOfTime::DateTime(datetime) => datetime,
+    }
+}
+
+/// Returns a line describing a logged cancelled recording.
+fn cancel_notice(cancelled: &CancelledRecording) -> String {
+    format!(
+        "Last cancel: {} at {}, {} discarded",
+        cancelled.category,
+        cancelled.start.format("%H:%M"),
+        format_duration(
+            cancelled.cancelled_at - cancelled.start,
+            Duration::minutes(1),
+            RoundingMode::Down
+        )
+    )
+}
+
+/// Returns the question asked before cancelling a recording.
+fn cancel_prompt(category: &str, start: NaiveDateTime, now: NaiveDateTime) -> String {
+    format!(
+        "Cancel recording {} discarding {}",
+        category,
+        format_duration(now - start, Duration::minutes(1), RoundingMode::Down)
+    )
+}
+
+/// Returns the recording status as JSON in the same shape whether time is being recorded or not.
+fn status_json(book: &TimeBook) -> serde_json::Value {
+    match book
+        .status()
+        .and_then(|(category, start)| Ok((category, start, book.elapsed()?)))
+    {
+        Ok((category, start, elapsed)) => serde_json::json!({
+            "recording": true,
+            "category": category,
+            "started": start,
+            "elapsed_seconds": elapsed.num_seconds(),
+        }),
+        Err(_) => serde_json::json!({ "recording": false }),
+    }
+}
+
+/// Returns the totals of the last 7 days as a JSON array ordered from the oldest day.
+fn week_summary_json(book: &TimeBook) -> serde_json::Value {
+    book.last_7_days()
+        .iter()
+        .map(|(date, spent)| {
+            serde_json::json!({
+                "date": date,
+                "seconds": spent.num_seconds(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the line printed on every tick of the watch command.
+fn watch_line(category: &str, elapsed: Duration) -> String {
+    let secs = elapsed.num_seconds().max(0);
+    format!(
+        "{}: {:02}:{:02}:{:02}",
+        category,
+        secs / 3600,
+        secs % 3600 / 60,
+        secs % 60
+    )
+}
+
+/// Renders the durations as a line of bars scaled to the largest duration.
+fn sparkline(values: &[Duration]) -> String {
+    let bars = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let max = values.iter().max().copied().unwrap_or_else(Duration::zero);
+
+    values
+        .iter()
+        .map(|value| {
+            if max <= Duration::zero() {
+                bars[0]
+            } else {
+                let index = value.num_seconds() * (bars.len() as i64 - 1) / max.num_seconds();
+                bars[index as usize]
+            }
+        })
+        .collect()
+}
+
+/// Returns the number of filled characters in a bar of the width for the value scaled to the
+/// maximum value. Nothing is filled if the maximum is zero.
+fn bar_length(value: Duration, max: Duration, width: usize) -> usize {
+    if max <= Duration::zero() {
+        return 0;
+    }
+
+    let ratio = value.num_seconds() as f64 / max.num_seconds() as f64;
+    ((ratio * width as f64).round() as usize).min(width)
+}
+
+/// Writes the output of a command to a file or to stdout if no file is specified.
+/// The file is replaced atomically so that a failed write never leaves a partial file behind.
+fn write_output(output: Option<&Path>, contents: &[u8]) -> io::Result<()> {
+    match output {
+        Some(path) => {
+            let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+            tmp_name.push(".tmp");
+            let tmp_path = path.with_file_name(tmp_name);
+
+            fs::write(&tmp_path, contents)?;
+            fs::rename(&tmp_path, path)
+        }
+        None => {
+            let mut stdout = io::stdout();
+            stdout.write_all(contents)?;
+            stdout.flush()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_is_valid() {
+        CliArgs::command().debug_assert();
+    }
+
+    #[test]
+    fn bare_times_and_full_datetimes_are_parsed() {
+        let today = NaiveDate::from_ymd(2022, 1, 1);
+
+        assert_eq!(
+            parse_datetime_on("09:30", today).unwrap(),
+            today.and_hms(9, 30, 0)
+        );
+        assert_eq!(
+            parse_datetime_on("09:30:15", today).unwrap(),
+            today.and_hms(9, 30, 15)
+        );
+        assert_eq!(
+            parse_datetime_on("2022-10-2T10:50:00", today).unwrap(),
+            NaiveDate::from_ymd(2022, 10, 2).and_hms(10, 50, 0)
+        );
+        assert_eq!(
+            parse_datetime_on("2022-10-02 10:50", today).unwrap(),
+            NaiveDate::from_ymd(2022, 10, 2).and_hms(10, 50, 0)
+        );
+        assert!(parse_datetime_on("25:00", today).is_err());
+        assert!(parse_datetime_on("yesterday", today).is_err());
+    }
+
+    #[test]
+    fn output_is_written_to_file() {
+        let path = env::temp_dir().join(format!("vihr_output_test_{}.csv", std::process::id()));
+
+        write_output(Some(&path), b"first").unwrap();
+        write_output(Some(&path), b"category,start\n").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"category,start\n");
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn cancel_prompt_shows_discarded_time() {
+        let start = NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0);
+
+        assert_eq!(
+            cancel_prompt("test", start, start + Duration::minutes(72)),
+            "Cancel recording test discarding 1h 12m"
+        );
+    }
+
+    #[test]
+    fn status_json_has_consistent_shape() {
+        let start = NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0);
+        let mut book = TimeBook::default();
+        book.set_now(Some(start + Duration::minutes(30)));
+
+        assert_eq!(status_json(&book).to_string(), r#"{"recording":false}"#);
+
+        book.add_category("test".to_string()).unwrap();
+        book.start("test".to_string(), Some(start)).unwrap();
+
+        assert_eq!(
+            status_json(&book).to_string(),
+            r#"{"category":"test","elapsed_seconds":1800,"recording":true,"started":"2022-01-01T09:00:00"}"#
+        );
+    }
+
+    #[test]
+    fn watch_line_shows_elapsed_time() {
+        assert_eq!(watch_line("test", Duration::seconds(5)), "test: 00:00:05");
+        assert_eq!(
+            watch_line("test", Duration::hours(12) + Duration::seconds(61)),
+            "test: 12:01:01"
+        );
+    }
+
+    #[test]
+    fn bare_stop_time_before_start_is_next_day() {
+        let today = NaiveDate::from_ymd(2022, 1, 1);
+        let start = today.and_hms(23, 0, 0);
+
+        assert_eq!(
+            resolve_stop_time(parse_point_of_time("01:00").unwrap(), start, today),
+            NaiveDate::from_ymd(2022, 1, 2).and_hms(1, 0, 0)
+        );
+        assert_eq!(
+            resolve_stop_time(parse_point_of_time("23:30").unwrap(), start, today),
+            today.and_hms(23, 30, 0)
+        );
+        assert_eq!(
+            resolve_stop_time(
+                parse_point_of_time("2022-01-01T01:00").unwrap(),
+                start,
+                today
+            ),
+            today.and_hms(1, 0, 0)
+        );
+    }
+
+    #[test]
+    fn start_ago_starts_before_now() {
+        let now = NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0);
+        let cli = CliArgs::try_parse_from(["vihr", "start", "test", "--ago", "10m"]).unwrap();
+        let Commands::Start {
+            start_time,
+            ago,
+            round_start,
+            ..
+        } = cli.command
+        else {
+            panic!("Expected the start command");
+        };
+
+        assert_eq!(
+            resolve_start_time(start_time, ago, round_start, now),
+            Some(now - Duration::minutes(10))
+        );
+        assert_eq!(
+            resolve_start_time(None, ago, Some(Duration::minutes(15)), now),
+            Some(now - Duration::minutes(15))
+        );
+        assert_eq!(resolve_start_time(None, None, None, now), None);
+    }
+
+    #[test]
+    fn bar_length_is_scaled_to_maximum() {
+        let max = Duration::hours(4);
+
+        assert_eq!(bar_length(Duration::hours(4), max, 20), 20);
+        assert_eq!(bar_length(Duration::hours(2), max, 20), 10);
+        assert_eq!(bar_length(Duration::hours(1), max, 10), 3);
+        assert_eq!(bar_length(Duration::minutes(5), max, 20), 0);
+        assert_eq!(bar_length(Duration::zero(), max, 20), 0);
+        assert_eq!(bar_length(Duration::zero(), Duration::zero(), 20), 0);
+    }
+
+    #[test]
+    fn signed_durations_are_parsed() {
+        assert_eq!(parse_signed_duration("-1d").unwrap(), -Duration::days(1));
+        assert_eq!(parse_signed_duration("+2h").unwrap(), Duration::hours(2));
+        assert_eq!(parse_signed_duration("30m").unwrap(), Duration::minutes(30));
+        assert!(parse_signed_duration("-").is_err());
+    }
+
+    #[test]
+    fn cancel_notice_shows_discarded_time() {
+        let start = NaiveDate::from_ymd(2022, 1, 1).and_hms(9, 0, 0);
+        let cancelled = CancelledRecording {
+            category: "coding".to_string(),
+            start,
+            cancelled_at: start + Duration::minutes(12),
+            reason: None,
+        };
+
+        assert_eq!(
+            cancel_notice(&cancelled),
+            "Last cancel: coding at 09:00, 12m discarded"
+        );
+    }
+
+    #[test]
+    fn book_split_into_files_round_trips() {
+        let mut book = TimeBook::default();
+        book.add_category("test".to_string()).unwrap();
+        book.add_category("test/second".to_string()).unwrap();
+        book.add_category("empty".to_string()).unwrap();
+        book.set_billable("test", true).unwrap();
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        book.add_time_usage("test", day.and_hms(9, 0, 0), day.and_hms(10, 0, 0), None)
+            .unwrap();
+        book.add_time_usage(
+            "test/second",
+            day.and_hms(11, 0, 0),
+            day.and_hms(12, 0, 0),
+            Some("Tests".to_string()),
+        )
+        .unwrap();
+        book.start("test".to_string(), Some(day.and_hms(13, 0, 0)))
+            .unwrap();
+
+        let files = split_book(&book).unwrap();
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            vec!["empty.json", "state.json", "test.json", "test_second.json"]
+        );
+
+        let merged = merge_book(&files).unwrap();
+        assert_eq!(
+            serde_json::to_value(&merged).unwrap(),
+            serde_json::to_value(&book).unwrap()
+        );
+    }
+
+    #[test]
+    fn only_stale_category_files_are_removed() {
+        let dir = env::temp_dir().join(format!("vihr_dir_test_{}", std::process::id()));
+        let mut book = TimeBook::default();
+        book.add_category("kept".to_string()).unwrap();
+        book.add_category("removed".to_string()).unwrap();
+        write_dir_book(&book, &dir).unwrap();
+        fs::write(dir.join("settings.json"), r#"{"theme": "dark"}"#).unwrap();
+        fs::write(dir.join("notes.json"), "not json").unwrap();
+
+        book.remove_category("removed").unwrap();
+        write_dir_book(&book, &dir).unwrap();
+
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["kept.json", "notes.json", "settings.json", "state.json"]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn changes_are_summarized() {
+        let day = NaiveDate::from_ymd(2022, 1, 1);
+        let mut before = TimeBook::default();
+        before.set_now(Some(day.and_hms(20, 0, 0)));
+        before.add_category("coding".to_string()).unwrap();
+        before.add_category("old".to_string()).unwrap();
+        before
+            .add_time_usage("coding", day.and_hms(9, 0, 0), day.and_hms(11, 30, 0), None)
+            .unwrap();
+
+        let mut after = before.clone();
+        assert!(change_summary(&before, &after).is_empty());
+
+        after
+            .add_time_usage(
+                "coding",
+                day.and_hms(12, 0, 0),
+                day.and_hms(12, 45, 0),
+                None,
+            )
+            .unwrap();
+        after.remove_category("old").unwrap();
+        after.add_category("new".to_string()).unwrap();
+        after.start("new".to_string(), None).unwrap();
+
+        assert_eq!(
+            change_summary(&before, &after),
+            vec![
+                "removed category old",
+                "added 45m to coding; total today now 3h 15m",
+                "added category new",
+                "started recording new",
+            ]
+        );
+
+        let mut settings = before.clone();
+        settings.set_billable("coding", true).unwrap();
+        assert_eq!(change_summary(&before, &settings), vec!["changed settings"]);
+    }
+}